@@ -0,0 +1,215 @@
+// turns a mnemonic and its operands into an instruction, using the same
+// mnemonics as backend::disassemble prints
+
+// crates
+use backend::Instruction;
+
+use crate::expression::{self, Expr};
+use crate::lexer::{Token, TokenKind};
+
+// one comma separated operand
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+	// V0 - VF
+	Register(u8),
+	// I, DT, ST, K, F, HF, B or R - always upper case
+	Keyword(String),
+	// [I]
+	IndirectI,
+	// LONG expr, only used by LD I, LONG
+	Long(Expr, usize),
+	// anything else, and the column it starts at
+	Value(Expr, usize),
+}
+
+// names that mean something other than a symbol when they're a whole operand
+const KEYWORDS: [&str; 8] = ["I", "DT", "ST", "K", "F", "HF", "B", "R"];
+
+// V0 - VF, in either case
+pub fn register_number(name: &str) -> Option<u8> {
+	let mut characters = name.chars();
+	match (characters.next(), characters.next(), characters.next()) {
+		(Some('V' | 'v'), Some(digit), None) => digit.to_digit(16).map(|number| number as u8),
+		_ => None,
+	}
+}
+
+// whether a name is taken by a register or keyword, so can't be a label
+pub fn is_reserved(name: &str) -> bool {
+	register_number(name).is_some() || KEYWORDS.contains(&name.to_ascii_uppercase().as_str())
+}
+
+// split tokens at top level commas and work out what each operand is
+// end_column is the column just past the end of the line
+pub fn parse_operands(tokens: &[Token], end_column: usize) -> Result<Vec<Operand>, (usize, String)> {
+	let mut operands: Vec<Operand> = Vec::new();
+	if tokens.is_empty() {
+		return Ok(operands);
+	}
+
+	let mut depth: usize = 0;
+	let mut start: usize = 0;
+	for index in 0..=tokens.len() {
+		match tokens.get(index).map(|token| &token.kind) {
+			Some(TokenKind::Punct('(' | '[')) => depth += 1,
+			Some(TokenKind::Punct(')' | ']')) => depth = depth.saturating_sub(1),
+			Some(TokenKind::Punct(',')) if depth == 0 => {
+				operands.push(parse_operand(&tokens[start..index], tokens[index].column)?);
+				start = index + 1;
+			},
+			None => operands.push(parse_operand(&tokens[start..], end_column)?),
+			_ => {},
+		}
+	}
+
+	Ok(operands)
+}
+
+fn parse_operand(tokens: &[Token], end_column: usize) -> Result<Operand, (usize, String)> {
+	let Some(first) = tokens.first() else {
+		return Err((end_column, String::from("missing operand")));
+	};
+
+	let operand: Operand = match (&first.kind, tokens.len()) {
+		(TokenKind::Ident(name), 1) if register_number(name).is_some() => Operand::Register(register_number(name).unwrap_or(0)),
+		(TokenKind::Ident(name), 1) if is_reserved(name) => Operand::Keyword(name.to_ascii_uppercase()),
+		(TokenKind::Punct('['), 3) if matches!(&tokens[1].kind, TokenKind::Ident(name) if name.eq_ignore_ascii_case("I")) && tokens[2].kind == TokenKind::Punct(']') => Operand::IndirectI,
+		(TokenKind::Ident(name), _) if name.eq_ignore_ascii_case("LONG") => {
+			let column: usize = tokens.get(1).map_or(end_column, |token| token.column);
+			Operand::Long(expression::parse(&tokens[1..], end_column)?, column)
+		},
+		_ => Operand::Value(expression::parse(tokens, end_column)?, first.column),
+	};
+
+	Ok(operand)
+}
+
+// bytes an instruction takes, known before any labels are
+pub fn instruction_size(mnemonic: &str, operands: &[Operand]) -> u16 {
+	match operands {
+		[_, Operand::Long(..)] if mnemonic.eq_ignore_ascii_case("LD") => 4,
+		_ => 2,
+	}
+}
+
+// evaluates an expression - given by the assembler, which knows the symbols
+pub type Evaluator<'a> = dyn FnMut(&Expr) -> Result<i64, (usize, String)> + 'a;
+
+// bytes for one instruction
+// column is where the mnemonic is, for errors about the operands as a whole
+pub fn encode(mnemonic: &str, column: usize, operands: &[Operand], evaluate: &mut Evaluator) -> Result<Vec<u8>, (usize, String)> {
+	use Operand::{IndirectI, Keyword, Long, Register, Value};
+
+	let upper: String = mnemonic.to_ascii_uppercase();
+	let keyword = |operand: &Operand, name: &str| matches!(operand, Keyword(keyword) if keyword == name);
+
+	let instruction: Instruction = match (upper.as_str(), operands) {
+		("NOP", []) => Instruction::Nop,
+		("CLS", []) => Instruction::ClearScreen,
+		("RET", []) => Instruction::Return,
+		("SCR", []) => Instruction::ScrollRight,
+		("SCL", []) => Instruction::ScrollLeft,
+		("EXIT", []) => Instruction::Exit,
+		("LOW", []) => Instruction::LowRes,
+		("HIGH", []) => Instruction::HighRes,
+		("AUDIO", []) => Instruction::LoadAudioPattern,
+		("SCD", [Value(n, at)]) => Instruction::ScrollDown { n: nibble(evaluate, n, *at)? },
+		("SCU", [Value(n, at)]) => Instruction::ScrollUp { n: nibble(evaluate, n, *at)? },
+		("PLANE", [Value(n, at)]) => Instruction::SelectPlanes { planes: nibble(evaluate, n, *at)? },
+
+		("JP", [Value(address, at)]) => Instruction::Jump { address: address_value(evaluate, address, *at)? },
+		("JP", [Register(0), Value(address, at)]) => Instruction::JumpOffset { address: address_value(evaluate, address, *at)? },
+		("CALL", [Value(address, at)]) => Instruction::Call { address: address_value(evaluate, address, *at)? },
+
+		("SE", [Register(x), Register(y)]) => Instruction::SkipEqualRegister { x: *x, y: *y },
+		("SE", [Register(x), Value(byte, at)]) => Instruction::SkipEqualByte { x: *x, byte: byte_value(evaluate, byte, *at)? },
+		("SNE", [Register(x), Register(y)]) => Instruction::SkipNotEqualRegister { x: *x, y: *y },
+		("SNE", [Register(x), Value(byte, at)]) => Instruction::SkipNotEqualByte { x: *x, byte: byte_value(evaluate, byte, *at)? },
+		("SAVE", [Register(x), Register(y)]) => Instruction::SaveRange { x: *x, y: *y },
+		("LOAD", [Register(x), Register(y)]) => Instruction::LoadRange { x: *x, y: *y },
+
+		("LD", [Register(x), Register(y)]) => Instruction::Move { x: *x, y: *y },
+		("LD", [Register(x), Value(byte, at)]) => Instruction::LoadByte { x: *x, byte: byte_value(evaluate, byte, *at)? },
+		("LD", [i, Value(address, at)]) if keyword(i, "I") => Instruction::LoadI { address: address_value(evaluate, address, *at)? },
+		("LD", [i, Long(address, at)]) if keyword(i, "I") => {
+			// F000 then the whole 16 bit address
+			let address: u16 = word_value(evaluate, address, *at)?;
+			return Ok([0xF0, 0x00, (address >> 8) as u8, address as u8].to_vec());
+		},
+		("LD", [Register(x), dt]) if keyword(dt, "DT") => Instruction::LoadDelay { x: *x },
+		("LD", [Register(x), k]) if keyword(k, "K") => Instruction::WaitKey { x: *x },
+		("LD", [dt, Register(x)]) if keyword(dt, "DT") => Instruction::SetDelay { x: *x },
+		("LD", [st, Register(x)]) if keyword(st, "ST") => Instruction::SetSound { x: *x },
+		("LD", [f, Register(x)]) if keyword(f, "F") => Instruction::LoadFont { x: *x },
+		("LD", [hf, Register(x)]) if keyword(hf, "HF") => Instruction::LoadBigFont { x: *x },
+		("LD", [b, Register(x)]) if keyword(b, "B") => Instruction::StoreBcd { x: *x },
+		("LD", [IndirectI, Register(x)]) => Instruction::StoreRegisters { x: *x },
+		("LD", [Register(x), IndirectI]) => Instruction::LoadRegisters { x: *x },
+		("LD", [r, Register(x)]) if keyword(r, "R") => Instruction::StoreFlags { x: *x },
+		("LD", [Register(x), r]) if keyword(r, "R") => Instruction::LoadFlags { x: *x },
+
+		("ADD", [Register(x), Register(y)]) => Instruction::Add { x: *x, y: *y },
+		("ADD", [Register(x), Value(byte, at)]) => Instruction::AddByte { x: *x, byte: byte_value(evaluate, byte, *at)? },
+		("ADD", [i, Register(x)]) if keyword(i, "I") => Instruction::AddI { x: *x },
+		("OR", [Register(x), Register(y)]) => Instruction::Or { x: *x, y: *y },
+		("AND", [Register(x), Register(y)]) => Instruction::And { x: *x, y: *y },
+		("XOR", [Register(x), Register(y)]) => Instruction::Xor { x: *x, y: *y },
+		("SUB", [Register(x), Register(y)]) => Instruction::Sub { x: *x, y: *y },
+		("SUBN", [Register(x), Register(y)]) => Instruction::SubReverse { x: *x, y: *y },
+		// the one operand forms shift VX into itself
+		("SHR", [Register(x)]) => Instruction::ShiftRight { x: *x, y: *x },
+		("SHR", [Register(x), Register(y)]) => Instruction::ShiftRight { x: *x, y: *y },
+		("SHL", [Register(x)]) => Instruction::ShiftLeft { x: *x, y: *x },
+		("SHL", [Register(x), Register(y)]) => Instruction::ShiftLeft { x: *x, y: *y },
+
+		("RND", [Register(x), Value(byte, at)]) => Instruction::Random { x: *x, byte: byte_value(evaluate, byte, *at)? },
+		("DRW", [Register(x), Register(y), Value(n, at)]) => Instruction::Draw { x: *x, y: *y, n: nibble(evaluate, n, *at)? },
+		("SKP", [Register(x)]) => Instruction::SkipKeyPressed { x: *x },
+		("SKNP", [Register(x)]) => Instruction::SkipKeyNotPressed { x: *x },
+		("PITCH", [Register(x)]) => Instruction::SetPitch { x: *x },
+
+		_ if is_mnemonic(&upper) => return Err((column, format!("invalid operands for {}", upper))),
+		_ => return Err((column, format!("unknown instruction '{}'", mnemonic))),
+	};
+
+	Ok(instruction.encode().to_be_bytes().to_vec())
+}
+
+// every mnemonic encode understands
+fn is_mnemonic(upper: &str) -> bool {
+	const MNEMONICS: [&str; 32] = [
+		"NOP", "CLS", "RET", "SCR", "SCL", "EXIT", "LOW", "HIGH", "AUDIO", "SCD", "SCU", "PLANE",
+		"JP", "CALL", "SE", "SNE", "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SUBN",
+		"SHR", "SHL", "RND", "DRW", "SKP", "SKNP", "PITCH",
+	];
+	MNEMONICS.contains(&upper)
+}
+
+// value that must be in range, or an error pointing at the operand
+fn ranged(evaluate: &mut Evaluator, expr: &Expr, column: usize, min: i64, max: i64, what: &str) -> Result<i64, (usize, String)> {
+	let value: i64 = evaluate(expr)?;
+	if value < min || value > max {
+		return Err((column, format!("{} {} is out of range ({} to {})", what, value, min, max)));
+	}
+	Ok(value)
+}
+
+// 0 - 15
+fn nibble(evaluate: &mut Evaluator, expr: &Expr, column: usize) -> Result<u8, (usize, String)> {
+	Ok(ranged(evaluate, expr, column, 0, 0xF, "value")? as u8)
+}
+
+// 0 - 255, or -128 - -1 for two's complement
+pub fn byte_value(evaluate: &mut Evaluator, expr: &Expr, column: usize) -> Result<u8, (usize, String)> {
+	Ok(ranged(evaluate, expr, column, -0x80, 0xFF, "byte")? as u8)
+}
+
+// 12 bit address
+fn address_value(evaluate: &mut Evaluator, expr: &Expr, column: usize) -> Result<u16, (usize, String)> {
+	Ok(ranged(evaluate, expr, column, 0, 0xFFF, "address")? as u16)
+}
+
+// 0 - 65535, or -32768 - -1 for two's complement
+pub fn word_value(evaluate: &mut Evaluator, expr: &Expr, column: usize) -> Result<u16, (usize, String)> {
+	Ok(ranged(evaluate, expr, column, -0x8000, 0xFFFF, "word")? as u16)
+}
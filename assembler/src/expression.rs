@@ -0,0 +1,163 @@
+// arithmetic on numbers, labels and constants, e.g. "sprites + 5 * 2"
+
+use crate::lexer::{Token, TokenKind};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+	Number(i64),
+	// label or constant, and the column it was written at
+	Symbol(String, usize),
+	// - or ~
+	Unary(char, Box<Expr>),
+	// operator, left, right and the operator's column (for division by 0)
+	Binary(BinaryOp, Box<Expr>, Box<Expr>, usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+	Or,
+	Xor,
+	And,
+	ShiftLeft,
+	ShiftRight,
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Rem,
+}
+
+impl BinaryOp {
+	// higher binds tighter - the same order as C
+	fn precedence(self) -> u8 {
+		match self {
+			BinaryOp::Or => 1,
+			BinaryOp::Xor => 2,
+			BinaryOp::And => 3,
+			BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 4,
+			BinaryOp::Add | BinaryOp::Sub => 5,
+			BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 6,
+		}
+	}
+
+	fn from_token(kind: &TokenKind) -> Option<BinaryOp> {
+		match kind {
+			TokenKind::Punct('|') => Some(BinaryOp::Or),
+			TokenKind::Punct('^') => Some(BinaryOp::Xor),
+			TokenKind::Punct('&') => Some(BinaryOp::And),
+			TokenKind::ShiftLeft => Some(BinaryOp::ShiftLeft),
+			TokenKind::ShiftRight => Some(BinaryOp::ShiftRight),
+			TokenKind::Punct('+') => Some(BinaryOp::Add),
+			TokenKind::Punct('-') => Some(BinaryOp::Sub),
+			TokenKind::Punct('*') => Some(BinaryOp::Mul),
+			TokenKind::Punct('/') => Some(BinaryOp::Div),
+			TokenKind::Punct('%') => Some(BinaryOp::Rem),
+			_ => None,
+		}
+	}
+}
+
+// parse all of tokens as one expression
+// end_column is where to point if the expression is cut short
+pub fn parse(tokens: &[Token], end_column: usize) -> Result<Expr, (usize, String)> {
+	let mut parser = Parser { tokens, position: 0, end_column };
+	let expr: Expr = parser.binary(0)?;
+
+	match parser.tokens.get(parser.position) {
+		Some(token) => Err((token.column, String::from("unexpected text after expression"))),
+		None => Ok(expr),
+	}
+}
+
+// precedence climbing over a slice of tokens
+struct Parser<'a> {
+	tokens: &'a [Token],
+	position: usize,
+	end_column: usize,
+}
+
+impl<'a> Parser<'a> {
+	// operators that bind tighter than min_precedence, left to right
+	fn binary(&mut self, min_precedence: u8) -> Result<Expr, (usize, String)> {
+		let mut left: Expr = self.unary()?;
+
+		while let Some(token) = self.tokens.get(self.position) {
+			let op: BinaryOp = match BinaryOp::from_token(&token.kind) {
+				Some(op) if op.precedence() > min_precedence => op,
+				_ => break,
+			};
+			self.position += 1;
+
+			let right: Expr = self.binary(op.precedence())?;
+			left = Expr::Binary(op, Box::new(left), Box::new(right), token.column);
+		}
+
+		Ok(left)
+	}
+
+	fn unary(&mut self) -> Result<Expr, (usize, String)> {
+		let Some(token) = self.tokens.get(self.position) else {
+			return Err((self.end_column, String::from("expected a value")));
+		};
+		self.position += 1;
+
+		match &token.kind {
+			TokenKind::Number(value) => Ok(Expr::Number(*value)),
+			TokenKind::Ident(name) => Ok(Expr::Symbol(name.clone(), token.column)),
+			TokenKind::Punct('-') => Ok(Expr::Unary('-', Box::new(self.unary()?))),
+			TokenKind::Punct('~') => Ok(Expr::Unary('~', Box::new(self.unary()?))),
+			TokenKind::Punct('+') => self.unary(),
+			TokenKind::Punct('(') => {
+				let inner: Expr = self.binary(0)?;
+				match self.tokens.get(self.position) {
+					Some(Token { kind: TokenKind::Punct(')'), .. }) => {
+						self.position += 1;
+						Ok(inner)
+					},
+					Some(other) => Err((other.column, String::from("expected ')'"))),
+					None => Err((self.end_column, String::from("expected ')'"))),
+				}
+			},
+			_ => Err((token.column, String::from("expected a value"))),
+		}
+	}
+}
+
+// work out an expression, looking symbols up with resolve
+// resolve gets the name and column and returns the value or an error
+pub fn evaluate<F>(expr: &Expr, resolve: &mut F) -> Result<i64, (usize, String)>
+where
+	F: FnMut(&str, usize) -> Result<i64, (usize, String)>,
+{
+	match expr {
+		Expr::Number(value) => Ok(*value),
+		Expr::Symbol(name, column) => resolve(name, *column),
+		Expr::Unary(op, inner) => {
+			let value: i64 = evaluate(inner, resolve)?;
+			Ok(if *op == '-' {value.wrapping_neg()} else {!value})
+		},
+		Expr::Binary(op, left, right, column) => {
+			let left: i64 = evaluate(left, resolve)?;
+			let right: i64 = evaluate(right, resolve)?;
+
+			let value: i64 = match op {
+				BinaryOp::Or => left | right,
+				BinaryOp::Xor => left ^ right,
+				BinaryOp::And => left & right,
+				// anything shifted 64 or more places is all gone
+				BinaryOp::ShiftLeft => if (0..64).contains(&right) {left << right} else {0},
+				BinaryOp::ShiftRight => if (0..64).contains(&right) {left >> right} else {0},
+				BinaryOp::Add => left.wrapping_add(right),
+				BinaryOp::Sub => left.wrapping_sub(right),
+				BinaryOp::Mul => left.wrapping_mul(right),
+				BinaryOp::Div | BinaryOp::Rem if right == 0 => {
+					return Err((*column, String::from("division by zero")));
+				},
+				BinaryOp::Div => left.wrapping_div(right),
+				BinaryOp::Rem => left.wrapping_rem(right),
+			};
+
+			Ok(value)
+		},
+	}
+}
@@ -0,0 +1,111 @@
+// splits one line of source into tokens
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+	// label, constant, mnemonic, register or directive - case is kept
+	Ident(String),
+	// number or character literal
+	Number(i64),
+	// "quoted string", only used by DB and INCLUDE
+	Str(String),
+	// << and >>
+	ShiftLeft,
+	ShiftRight,
+	// any other single character, e.g. , : ( ) + -
+	Punct(char),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+	pub kind: TokenKind,
+	// 1-based, for error messages
+	pub column: usize,
+}
+
+// characters a token can be made of apart from punctuation
+fn is_ident_char(character: char) -> bool {
+	character.is_ascii_alphanumeric() || character == '_' || character == '.'
+}
+
+// break a line into tokens, stopping at a ; comment
+// errors are (column, message)
+pub fn tokenize(line: &str) -> Result<Vec<Token>, (usize, String)> {
+	let characters: Vec<char> = line.chars().collect();
+	let mut tokens: Vec<Token> = Vec::new();
+	let mut index: usize = 0;
+
+	while index < characters.len() {
+		let character: char = characters[index];
+		let column: usize = index + 1;
+
+		// whitespace between tokens
+		if character.is_whitespace() {
+			index += 1;
+			continue;
+		}
+		// rest of the line is a comment
+		if character == ';' {
+			break;
+		}
+
+		let kind: TokenKind = if character.is_ascii_digit() {
+			let start: usize = index;
+			while index < characters.len() && is_ident_char(characters[index]) {
+				index += 1;
+			}
+			let text: String = characters[start..index].iter().collect();
+			TokenKind::Number(parse_number(&text).ok_or((column, format!("invalid number '{}'", text)))?)
+		} else if is_ident_char(character) {
+			let start: usize = index;
+			while index < characters.len() && is_ident_char(characters[index]) {
+				index += 1;
+			}
+			TokenKind::Ident(characters[start..index].iter().collect())
+		} else if character == '"' {
+			// strings run to the next quote, with no escapes
+			let start: usize = index + 1;
+			index = start;
+			while index < characters.len() && characters[index] != '"' {
+				index += 1;
+			}
+			if index == characters.len() {
+				return Err((column, String::from("unterminated string")));
+			}
+			index += 1;
+			TokenKind::Str(characters[start..index - 1].iter().collect())
+		} else if character == '\'' {
+			// single character, e.g. 'A'
+			if index + 2 >= characters.len() || characters[index + 2] != '\'' {
+				return Err((column, String::from("character literals need exactly one character, e.g. 'A'")));
+			}
+			index += 3;
+			TokenKind::Number(characters[index - 2] as i64)
+		} else if character == '<' || character == '>' {
+			if characters.get(index + 1) != Some(&character) {
+				return Err((column, format!("unexpected '{}' - did you mean '{}{}'?", character, character, character)));
+			}
+			index += 2;
+			if character == '<' {TokenKind::ShiftLeft} else {TokenKind::ShiftRight}
+		} else {
+			index += 1;
+			TokenKind::Punct(character)
+		};
+
+		tokens.push(Token { kind, column });
+	}
+
+	Ok(tokens)
+}
+
+// 42, 0x2A or 0b101010
+fn parse_number(text: &str) -> Option<i64> {
+	let lower: String = text.to_ascii_lowercase();
+
+	if let Some(hex) = lower.strip_prefix("0x") {
+		i64::from_str_radix(hex, 16).ok()
+	} else if let Some(binary) = lower.strip_prefix("0b") {
+		i64::from_str_radix(binary, 2).ok()
+	} else {
+		lower.parse().ok()
+	}
+}
@@ -0,0 +1,410 @@
+// CHIP-8 assembler - turns source text into a ROM for backend::Emulator
+//
+// each line is made of an optional label, then an instruction or directive,
+// then an optional ; comment:
+//
+//   start:  LD V0, 0x2A        ; instructions use the same mnemonics as
+//           DRW V0, V1, 5      ; backend::disassemble prints
+//   SPEED   EQU 3 * 2          ; constant
+//           ORG 0x300          ; carry on from this address
+//   sprite: DB 0x3C, 0x42, "A" ; bytes (and strings)
+//           DW sprite + 1      ; 16 bit big-endian words
+//           INCLUDE "font.asm" ; another file, relative to this one
+//
+// numbers can be decimal, 0x hex, 0b binary or 'c' characters, and combined
+// with + - * / % & | ^ << >> ~ and brackets, using labels and constants
+// defined anywhere in the program
+// mnemonics, directives and register names are case insensitive, labels
+// and constants aren't
+
+// crates
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// modules
+mod encoder;
+mod expression;
+mod lexer;
+
+use encoder::Operand;
+use expression::Expr;
+use lexer::{Token, TokenKind};
+
+// where output starts unless ORG says otherwise - the same as Emulator::load_rom
+const DEFAULT_BASE: u16 = 0x200;
+// stops a file from including itself forever
+const MAX_INCLUDE_DEPTH: usize = 16;
+// stops constants defined in terms of each other from looping forever
+const MAX_CONSTANT_DEPTH: usize = 64;
+// name used in errors for source that didn't come from a file
+const SOURCE_NAME: &str = "<source>";
+
+// a line of source
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+	// path as it was given, or <source>
+	pub file: String,
+	// 1-based
+	pub line: usize,
+}
+
+// which line of source some bytes of the ROM came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceMapEntry {
+	pub address: u16,
+	// bytes from this line
+	pub length: u16,
+	pub location: SourceLocation,
+}
+
+// assembled program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Assembly {
+	// address the first byte belongs at
+	pub base: u16,
+	// ROM - goes to Emulator::load_rom, or load_rom_at with base if it isn't 0x200
+	pub bytes: Vec<u8>,
+	// every label and its address
+	pub labels: BTreeMap<String, u16>,
+	// in address order
+	pub source_map: Vec<SourceMapEntry>,
+}
+
+impl Assembly {
+	// line that produced the byte at an address
+	pub fn location_of(&self, address: u16) -> Option<&SourceLocation> {
+		self.source_map.iter()
+			.find(|entry| (entry.address as u32..entry.address as u32 + entry.length as u32).contains(&(address as u32)))
+			.map(|entry| &entry.location)
+	}
+
+	// address of the first byte produced by a line, if it produced any
+	pub fn address_of(&self, file: &str, line: usize) -> Option<u16> {
+		self.source_map.iter()
+			.find(|entry| entry.location.file == file && entry.location.line == line)
+			.map(|entry| entry.address)
+	}
+}
+
+// what went wrong and where
+// line and column are 1-based, and both 0 if the problem isn't on a line,
+// e.g. the file couldn't be read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssembleError {
+	pub file: String,
+	pub line: usize,
+	pub column: usize,
+	pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.line == 0 {
+			write!(f, "{}: {}", self.file, self.message)
+		} else {
+			write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+		}
+	}
+}
+
+impl Error for AssembleError {}
+
+// assemble source text - any INCLUDEs are relative to the current directory
+pub fn assemble(source: &str) -> Result<Assembly, AssembleError> {
+	let mut assembler = Assembler::default();
+	assembler.load(SOURCE_NAME, source, Path::new(""), 0)?;
+	assembler.assemble()
+}
+
+// assemble a file - any INCLUDEs are relative to it
+pub fn assemble_file(path: &Path) -> Result<Assembly, AssembleError> {
+	let name: String = path.display().to_string();
+	let source: String = fs::read_to_string(path).map_err(|error| AssembleError {
+		file: name.clone(),
+		line: 0,
+		column: 0,
+		message: error.to_string(),
+	})?;
+
+	let mut assembler = Assembler::default();
+	assembler.load(&name, &source, path.parent().unwrap_or(Path::new("")), 0)?;
+	assembler.assemble()
+}
+
+// one line after includes have been pulled in
+struct Line {
+	// index into Assembler::files
+	file: usize,
+	// 1-based
+	number: usize,
+	tokens: Vec<Token>,
+	// just past the last character, for "expected ..." at the end of a line
+	end_column: usize,
+}
+
+// something that ends up as bytes
+enum Statement {
+	Instruction { mnemonic: String, column: usize, operands: Vec<Operand> },
+	Bytes(Vec<DataItem>),
+	Words(Vec<(Expr, usize)>),
+}
+
+// one item of a DB
+enum DataItem {
+	Text(Vec<u8>),
+	Value(Expr, usize),
+}
+
+// statement and where it goes, found in the first pass
+struct Placed {
+	// index into Assembler::lines
+	line: usize,
+	address: u16,
+	size: u16,
+	statement: Statement,
+}
+
+// label with its address, or constant with its expression
+enum Symbol {
+	Label(u16),
+	Constant(Expr),
+}
+
+#[derive(Default)]
+struct Assembler {
+	files: Vec<String>,
+	lines: Vec<Line>,
+	symbols: HashMap<String, Symbol>,
+}
+
+impl Assembler {
+	// tokenize a file, pulling INCLUDEs in as they're found
+	fn load(&mut self, name: &str, source: &str, directory: &Path, depth: usize) -> Result<(), AssembleError> {
+		let file: usize = self.files.len();
+		self.files.push(name.to_string());
+
+		for (index, text) in source.lines().enumerate() {
+			let number: usize = index + 1;
+			let tokens: Vec<Token> = lexer::tokenize(text).map_err(|(column, message)| AssembleError {
+				file: name.to_string(),
+				line: number,
+				column,
+				message,
+			})?;
+			let end_column: usize = text.chars().count() + 1;
+
+			// INCLUDE "path"
+			if let Some(Token { kind: TokenKind::Ident(directive), column }) = tokens.first() {
+				if directive.eq_ignore_ascii_case("INCLUDE") {
+					let error = |column: usize, message: String| AssembleError { file: name.to_string(), line: number, column, message };
+
+					let path: PathBuf = match &tokens[1..] {
+						[Token { kind: TokenKind::Str(path), .. }] => directory.join(path),
+						_ => return Err(error(*column, String::from("INCLUDE needs a single \"path\""))),
+					};
+					if depth == MAX_INCLUDE_DEPTH {
+						return Err(error(*column, String::from("INCLUDEs are nested too deeply")));
+					}
+
+					let included: String = fs::read_to_string(&path)
+						.map_err(|io_error| error(tokens[1].column, format!("can't read {}: {}", path.display(), io_error)))?;
+					self.load(&path.display().to_string(), &included, path.parent().unwrap_or(Path::new("")), depth + 1)?;
+					continue;
+				}
+			}
+
+			if !tokens.is_empty() {
+				self.lines.push(Line { file, number, tokens, end_column });
+			}
+		}
+
+		Ok(())
+	}
+
+	// error at a column of a line
+	fn error(&self, line: usize, (column, message): (usize, String)) -> AssembleError {
+		AssembleError {
+			file: self.files[self.lines[line].file].clone(),
+			line: self.lines[line].number,
+			column,
+			message,
+		}
+	}
+
+	// first pass finds every label's address, second pass produces the bytes
+	fn assemble(mut self) -> Result<Assembly, AssembleError> {
+		let mut placed: Vec<Placed> = Vec::new();
+		// where the next byte goes - wider than an address so running off the end can be caught
+		let mut address: u32 = DEFAULT_BASE as u32;
+		// address of the first byte, once there is one
+		let mut base: Option<u16> = None;
+
+		for index in 0..self.lines.len() {
+			let line: &Line = &self.lines[index];
+			let mut tokens: &[Token] = &line.tokens;
+			let end_column: usize = line.end_column;
+
+			// label:
+			if let [Token { kind: TokenKind::Ident(name), column }, Token { kind: TokenKind::Punct(':'), .. }, ..] = tokens {
+				let (name, column) = (name.clone(), *column);
+				self.define(index, name, column, Symbol::Label(address as u16))?;
+				tokens = &self.lines[index].tokens[2..];
+			}
+
+			let (first, rest) = match tokens.split_first() {
+				Some((Token { kind: TokenKind::Ident(first), column }, rest)) => ((first.clone(), *column), rest),
+				Some((token, _)) => return Err(self.error(index, (token.column, String::from("expected an instruction or directive")))),
+				// just a label
+				None => continue,
+			};
+
+			// NAME EQU expr
+			if let Some(Token { kind: TokenKind::Ident(equ), .. }) = rest.first() {
+				if equ.eq_ignore_ascii_case("EQU") {
+					if tokens.len() != self.lines[index].tokens.len() {
+						return Err(self.error(index, (first.1, String::from("a constant can't have a label"))));
+					}
+					let expr: Expr = expression::parse(&rest[1..], end_column).map_err(|error| self.error(index, error))?;
+					self.define(index, first.0, first.1, Symbol::Constant(expr))?;
+					continue;
+				}
+			}
+
+			let statement: Statement = match first.0.to_ascii_uppercase().as_str() {
+				"ORG" => {
+					// has to be known now, so only earlier labels can be used
+					let expr: Expr = expression::parse(rest, end_column).map_err(|error| self.error(index, error))?;
+					let column: usize = rest.first().map_or(end_column, |token| token.column);
+					let new_address: i64 = expression::evaluate(&expr, &mut |name, column| self.symbol_value(name, column, 0))
+						.map_err(|error| self.error(index, error))?;
+
+					if !(0..=0xFFFF).contains(&new_address) {
+						return Err(self.error(index, (column, format!("address {} is out of range (0 to 65535)", new_address))));
+					}
+					if base.is_some() && (new_address as u32) < address {
+						return Err(self.error(index, (column, format!("ORG {:#06X} would go back over earlier output at {:#06X}", new_address, address))));
+					}
+
+					address = new_address as u32;
+					continue;
+				},
+				"DB" => Statement::Bytes(self.data_items(index, rest)?),
+				"DW" => Statement::Words(self.data_items(index, rest)?.into_iter().map(|item| match item {
+					DataItem::Value(expr, column) => Ok((expr, column)),
+					DataItem::Text(_) => Err(self.error(index, (first.1, String::from("strings can only be used with DB")))),
+				}).collect::<Result<_, _>>()?),
+				"INCLUDE" => return Err(self.error(index, (first.1, String::from("INCLUDE has to be on a line of its own")))),
+				_ => {
+					let operands: Vec<Operand> = encoder::parse_operands(rest, end_column).map_err(|error| self.error(index, error))?;
+					Statement::Instruction { mnemonic: first.0, column: first.1, operands }
+				},
+			};
+
+			let size: u16 = match &statement {
+				Statement::Instruction { mnemonic, operands, .. } => encoder::instruction_size(mnemonic, operands),
+				Statement::Bytes(items) => items.iter().map(|item| match item {
+					DataItem::Text(text) => text.len() as u16,
+					DataItem::Value(..) => 1,
+				}).sum(),
+				Statement::Words(words) => 2 * words.len() as u16,
+			};
+
+			if address + size as u32 > 0x10000 {
+				return Err(self.error(index, (first.1, String::from("program goes past the end of memory at 0xFFFF"))));
+			}
+
+			base.get_or_insert(address as u16);
+			placed.push(Placed { line: index, address: address as u16, size, statement });
+			address += size as u32;
+		}
+
+		let base: u16 = base.unwrap_or(address as u16);
+		let mut bytes: Vec<u8> = Vec::new();
+		let mut source_map: Vec<SourceMapEntry> = Vec::new();
+
+		for placement in placed.iter() {
+			let mut evaluate = |expr: &Expr| expression::evaluate(expr, &mut |name, column| self.symbol_value(name, column, 0));
+
+			let output: Vec<u8> = match &placement.statement {
+				Statement::Instruction { mnemonic, column, operands } => encoder::encode(mnemonic, *column, operands, &mut evaluate),
+				Statement::Bytes(items) => items.iter().try_fold(Vec::new(), |mut output, item| {
+					match item {
+						DataItem::Text(text) => output.extend_from_slice(text),
+						DataItem::Value(expr, column) => output.push(encoder::byte_value(&mut evaluate, expr, *column)?),
+					}
+					Ok(output)
+				}),
+				Statement::Words(words) => words.iter().try_fold(Vec::new(), |mut output, (expr, column)| {
+					output.extend_from_slice(&encoder::word_value(&mut evaluate, expr, *column)?.to_be_bytes());
+					Ok(output)
+				}),
+			}.map_err(|error| self.error(placement.line, error))?;
+
+			// gap left by ORG is filled with 0s
+			bytes.resize((placement.address - base) as usize, 0);
+			bytes.extend_from_slice(&output);
+
+			if placement.size > 0 {
+				let line: &Line = &self.lines[placement.line];
+				source_map.push(SourceMapEntry {
+					address: placement.address,
+					length: placement.size,
+					location: SourceLocation { file: self.files[line.file].clone(), line: line.number },
+				});
+			}
+		}
+
+		let labels: BTreeMap<String, u16> = self.symbols.iter().filter_map(|(name, symbol)| match symbol {
+			Symbol::Label(address) => Some((name.clone(), *address)),
+			Symbol::Constant(_) => None,
+		}).collect();
+
+		Ok(Assembly { base, bytes, labels, source_map })
+	}
+
+	// add a label or constant, checking the name is free
+	fn define(&mut self, line: usize, name: String, column: usize, symbol: Symbol) -> Result<(), AssembleError> {
+		if encoder::is_reserved(&name) {
+			return Err(self.error(line, (column, format!("'{}' is a register name, so can't be a label or constant", name))));
+		}
+		if self.symbols.contains_key(&name) {
+			return Err(self.error(line, (column, format!("'{}' is already defined", name))));
+		}
+
+		self.symbols.insert(name, symbol);
+		Ok(())
+	}
+
+	// comma separated DB/DW values
+	fn data_items(&self, line: usize, tokens: &[Token]) -> Result<Vec<DataItem>, AssembleError> {
+		let end_column: usize = self.lines[line].end_column;
+		let mut items: Vec<DataItem> = Vec::new();
+
+		for item in tokens.split(|token| token.kind == TokenKind::Punct(',')) {
+			let item: DataItem = match item {
+				[Token { kind: TokenKind::Str(text), .. }] => DataItem::Text(text.bytes().collect()),
+				_ => {
+					let column: usize = item.first().map_or(end_column, |token| token.column);
+					DataItem::Value(expression::parse(item, end_column).map_err(|error| self.error(line, error))?, column)
+				},
+			};
+			items.push(item);
+		}
+
+		Ok(items)
+	}
+
+	// value of a label or constant used at column
+	fn symbol_value(&self, name: &str, column: usize, depth: usize) -> Result<i64, (usize, String)> {
+		match self.symbols.get(name) {
+			Some(Symbol::Label(address)) => Ok(*address as i64),
+			Some(Symbol::Constant(_)) if depth == MAX_CONSTANT_DEPTH => Err((column, format!("'{}' is defined in terms of itself", name))),
+			// errors inside the constant are reported where it's used
+			Some(Symbol::Constant(expr)) => expression::evaluate(expr, &mut |inner, _| self.symbol_value(inner, column, depth + 1)),
+			None => Err((column, format!("unknown label or constant '{}'", name))),
+		}
+	}
+}
@@ -0,0 +1,37 @@
+// crates
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use assembler::Assembly;
+
+fn main() -> ExitCode {
+	let args: Vec<String> = env::args().collect();
+
+	// output defaults to the source with a .ch8 extension
+	let (source_path, output_path): (&Path, PathBuf) = match args.as_slice() {
+		[_, source_path] => (Path::new(source_path), Path::new(source_path).with_extension("ch8")),
+		[_, source_path, output_path] => (Path::new(source_path), PathBuf::from(output_path)),
+		_ => {
+			println!("Usage: cargo run -p assembler path_to_source [path_to_output]");
+			return ExitCode::FAILURE;
+		},
+	};
+
+	let assembly: Assembly = match assembler::assemble_file(source_path) {
+		Ok(assembly) => assembly,
+		Err(error) => {
+			println!("{}", error);
+			return ExitCode::FAILURE;
+		},
+	};
+
+	if let Err(error) = fs::write(&output_path, &assembly.bytes) {
+		println!("Couldn't write {}: {}", output_path.display(), error);
+		return ExitCode::FAILURE;
+	}
+
+	println!("Wrote {} bytes to {} (starting at {:#06X})", assembly.bytes.len(), output_path.display(), assembly.base);
+	ExitCode::SUCCESS
+}
@@ -0,0 +1,143 @@
+// assembles source and checks the ROM, labels and source map that come out,
+// and that mistakes are reported at the right line and column
+
+// crates
+use std::path::{Path, PathBuf};
+
+use assembler::{assemble, assemble_file, AssembleError, Assembly, SourceLocation};
+use backend::{disassemble, DisassembledLine};
+
+// bytes from source that's expected to assemble
+fn bytes(source: &str) -> Vec<u8> {
+	match assemble(source) {
+		Ok(assembly) => assembly.bytes,
+		Err(error) => panic!("{}\n{}", error, source),
+	}
+}
+
+// error from source that's expected not to assemble
+fn error(source: &str) -> AssembleError {
+	match assemble(source) {
+		Ok(assembly) => panic!("assembled to {:02X?}\n{}", assembly.bytes, source),
+		Err(error) => error,
+	}
+}
+
+// line, column and message of an error
+fn position(error: &AssembleError) -> (usize, usize, &str) {
+	(error.line, error.column, error.message.as_str())
+}
+
+// every opcode, disassembled and assembled again, comes out as the same bytes
+#[test]
+fn disassembly_round_trips() {
+	for opcode in 0..=0xFFFFu16 {
+		// F000 is followed by the address it loads
+		let rom: Vec<u8> = if opcode == 0xF000 {
+			vec![0xF0, 0x00, 0x12, 0x34]
+		} else {
+			opcode.to_be_bytes().to_vec()
+		};
+
+		let lines: Vec<DisassembledLine> = disassemble(&rom, 0x200);
+		let source: String = lines.iter().map(|line| format!("{}\n", line.mnemonic)).collect();
+		assert_eq!(bytes(&source), rom, "{:04X} disassembled as {}", opcode, source);
+	}
+}
+
+#[test]
+fn data_directives() {
+	assert_eq!(bytes("DB 1, 0x2, 0b11, 'A', \"hi\""), [1, 2, 3, b'A', b'h', b'i']);
+	assert_eq!(bytes("DW 0x1234, 5"), [0x12, 0x34, 0x00, 0x05]);
+	assert_eq!(position(&error("DW \"no\"")), (1, 1, "strings can only be used with DB"));
+	assert_eq!(position(&error("DB 256")), (1, 4, "byte 256 is out of range (-128 to 255)"));
+}
+
+#[test]
+fn constants_and_labels() {
+	let source: &str = "\
+		SIZE  EQU HALF * 2\n\
+		HALF  EQU 3\n\
+		      LD V0, SIZE\n\
+		loop: JP loop\n\
+		      DW end\n\
+		end:\n";
+	let assembly: Assembly = assemble(source).unwrap();
+	assert_eq!(assembly.bytes, [0x60, 0x06, 0x12, 0x02, 0x02, 0x06]);
+	assert_eq!(assembly.labels.get("loop"), Some(&0x202));
+	assert_eq!(assembly.labels.get("end"), Some(&0x206));
+	assert!(!assembly.labels.contains_key("SIZE"));
+
+	assert_eq!(position(&error("P EQU Q\nQ EQU P\nLD V0, P")), (3, 8, "'P' is defined in terms of itself"));
+	assert_eq!(position(&error("x: CLS\nx: CLS")), (2, 1, "'x' is already defined"));
+	assert_eq!(position(&error("V1: CLS")), (1, 1, "'V1' is a register name, so can't be a label or constant"));
+}
+
+#[test]
+fn org_moves_output_and_fills_the_gap() {
+	let assembly: Assembly = assemble("ORG 0x300\nCLS\nORG 0x304\nRET").unwrap();
+	assert_eq!(assembly.base, 0x300);
+	assert_eq!(assembly.bytes, [0x00, 0xE0, 0x00, 0x00, 0x00, 0xEE]);
+	assert_eq!(assembly.address_of("<source>", 4), Some(0x304));
+
+	assert_eq!(position(&error("CLS\nORG 0x100")), (2, 5, "ORG 0x0100 would go back over earlier output at 0x0202"));
+	assert_eq!(position(&error("ORG 0x10000")), (1, 5, "address 65536 is out of range (0 to 65535)"));
+}
+
+#[test]
+fn include_pulls_in_another_file() {
+	let path: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/include/main.asm");
+	let assembly: Assembly = assemble_file(&path).unwrap();
+	assert_eq!(assembly.bytes, [0xA2, 0x02, 0xF0, 0x90, 0x12, 0x00]);
+	assert_eq!(assembly.labels.get("digit"), Some(&0x202));
+
+	// each byte knows which file it came from
+	let included: &SourceLocation = assembly.location_of(0x203).unwrap();
+	assert!(included.file.ends_with("sprites.asm"));
+	assert_eq!(included.line, 1);
+	assert_eq!(assembly.location_of(0x204).unwrap().line, 4);
+
+	assert_eq!(position(&error("INCLUDE")), (1, 1, "INCLUDE needs a single \"path\""));
+	assert_eq!(position(&error("x: INCLUDE \"y.asm\"")), (1, 4, "INCLUDE has to be on a line of its own"));
+}
+
+// the same order as C - * before + before << before & before ^ before |
+#[test]
+fn expression_precedence() {
+	let cases: [(&str, u8); 9] = [
+		("1 + 2 * 3", 7),
+		("(1 + 2) * 3", 9),
+		("10 - 4 - 3", 3),
+		("64 / 4 / 2", 8),
+		("1 << 2 + 1", 8),
+		("0xF0 | 0x0F & 0x03", 0xF3),
+		("6 ^ 3 & 1", 7),
+		("~0 & 0xFF", 0xFF),
+		("-1 + 2 * -3 % 4 + 20", 17),
+	];
+	for (expression, value) in cases {
+		assert_eq!(bytes(&format!("DB {}", expression)), [value], "{}", expression);
+	}
+
+	assert_eq!(position(&error("DB 1 / (2 - 2)")), (1, 6, "division by zero"));
+	assert_eq!(position(&error("DB (1 + 2")), (1, 10, "expected ')'"));
+	assert_eq!(position(&error("DB 1 +")), (1, 7, "expected a value"));
+	assert_eq!(position(&error("DB 1 2")), (1, 6, "unexpected text after expression"));
+}
+
+// mistakes found while splitting lines into tokens
+#[test]
+fn lexer_errors_point_at_the_token() {
+	assert_eq!(position(&error("CLS\n  LD V0, 0x2G")), (2, 10, "invalid number '0x2G'"));
+	assert_eq!(position(&error("CLS\nCLS\n DB \"open")), (3, 5, "unterminated string"));
+	assert_eq!(position(&error("DB 'AB'")), (1, 4, "character literals need exactly one character, e.g. 'A'"));
+	assert_eq!(position(&error("DB 1 < 2")), (1, 6, "unexpected '<' - did you mean '<<'?"));
+}
+
+#[test]
+fn instruction_errors_point_at_the_instruction() {
+	assert_eq!(position(&error("  FOO V0")), (1, 3, "unknown instruction 'FOO'"));
+	assert_eq!(position(&error("JP nowhere")), (1, 4, "unknown label or constant 'nowhere'"));
+	assert_eq!(position(&error("LD V0,")), (1, 7, "missing operand"));
+	assert_eq!(error("LD V0, 0x2G").to_string(), "<source>:1:8: invalid number '0x2G'");
+}
@@ -0,0 +1,4 @@
+; pulls in sprites.asm, whose labels are used before and after it
+start:  LD I, digit
+        INCLUDE "sprites.asm"
+        JP start
@@ -0,0 +1 @@
+digit:  DB 0xF0, 0x90
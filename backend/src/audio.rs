@@ -0,0 +1,284 @@
+// crates
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+use crate::{Emulator, AUDIO_PATTERN_SIZE};
+
+// XO-CHIP plays its pattern at 4000 bits a second when the pitch is 64
+const PATTERN_BASE_RATE: f32 = 4000.0;
+// bits in the XO-CHIP pattern buffer
+const PATTERN_BITS: usize = AUDIO_PATTERN_SIZE * 8;
+
+// shape of the beep
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+	Square,
+	Sine,
+	Triangle,
+	Sawtooth,
+}
+
+impl Waveform {
+	// every waveform
+	pub const ALL: [Waveform; 4] = [Waveform::Square, Waveform::Sine, Waveform::Triangle, Waveform::Sawtooth];
+
+	// name used in settings
+	pub fn name(&self) -> &'static str {
+		match self {
+			Waveform::Square => "square",
+			Waveform::Sine => "sine",
+			Waveform::Triangle => "triangle",
+			Waveform::Sawtooth => "sawtooth",
+		}
+	}
+}
+
+// parse the name of a waveform
+impl FromStr for Waveform {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Self, Self::Err> {
+		Waveform::ALL.iter()
+			.find(|waveform| waveform.name() == name)
+			.copied()
+			.ok_or_else(|| {
+				let names: Vec<&str> = Waveform::ALL.iter().map(|waveform| waveform.name()).collect();
+				format!("unknown waveform '{}', expected one of: {}", name, names.join(", "))
+			})
+	}
+}
+
+// how the beep sounds, and the rate the frontend plays samples at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioSettings {
+	// pitch of the beep in Hz
+	pub frequency: f32,
+	pub waveform: Waveform,
+	// 0.0 is silent, 1.0 is full scale
+	pub volume: f32,
+	// samples per second the frontend's audio device wants
+	pub sample_rate: u32,
+}
+
+impl Default for AudioSettings {
+	fn default() -> Self {
+		Self {
+			frequency: 440.0,
+			waveform: Waveform::Square,
+			volume: 0.25,
+			sample_rate: 44100,
+		}
+	}
+}
+
+// turns the sound timer into samples - lives on the frontend's audio thread,
+// and gets told what the emulator is doing once a frame with update
+pub struct ToneGenerator {
+	settings: AudioSettings,
+	// whether the sound timer is running
+	active: bool,
+	// XO-CHIP pattern and its playback rate in bits per second, if the game has loaded one
+	pattern: Option<([u8; AUDIO_PATTERN_SIZE], f32)>,
+	// how far through the current wave (0.0 - 1.0), or through the pattern (0.0 - 128.0)
+	phase: f32,
+}
+
+impl ToneGenerator {
+	// new constructor for ToneGenerator class
+	pub fn new(settings: AudioSettings) -> Self {
+		Self {
+			settings,
+			active: false,
+			pattern: None,
+			phase: 0.0,
+		}
+	}
+
+	pub fn get_settings(&self) -> &AudioSettings {
+		&self.settings
+	}
+
+	pub fn set_settings(&mut self, settings: AudioSettings) {
+		self.settings = settings;
+	}
+
+	// copy across whether the emulator is beeping, and what with
+	pub fn update(&mut self, emulator: &Emulator) {
+		self.active = emulator.is_sound_active();
+		self.pattern = emulator.get_audio_pattern().map(|pattern| (*pattern, emulator.get_pattern_rate()));
+	}
+
+	// fill a buffer with the next samples - silence when the sound timer is at 0
+	pub fn fill(&mut self, samples: &mut [f32]) {
+		if !self.active {
+			samples.fill(0.0);
+			self.phase = 0.0;
+			return;
+		}
+
+		let sample_rate: f32 = self.settings.sample_rate as f32;
+
+		for sample in samples.iter_mut() {
+			let level: f32 = match self.pattern {
+				// XO-CHIP - each bit of the pattern is fully on or fully off
+				Some((pattern, rate)) => {
+					let bit: usize = self.phase as usize % PATTERN_BITS;
+					self.phase = (self.phase + rate / sample_rate) % PATTERN_BITS as f32;
+
+					if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {1.0} else {-1.0}
+				},
+				// everything else - a plain tone
+				None => {
+					let level: f32 = match self.settings.waveform {
+						Waveform::Square => if self.phase < 0.5 {1.0} else {-1.0},
+						Waveform::Sine => (2.0 * PI * self.phase).sin(),
+						Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+						Waveform::Sawtooth => 2.0 * self.phase - 1.0,
+					};
+					self.phase = (self.phase + self.settings.frequency / sample_rate) % 1.0;
+
+					level
+				},
+			};
+
+			*sample = level * self.settings.volume;
+		}
+	}
+}
+
+impl Emulator {
+	// whether the game wants a beep right now
+	pub fn is_sound_active(&self) -> bool {
+		self.sound_timer > 0
+	}
+
+	// XO-CHIP audio pattern, if the game has loaded one with F002
+	pub fn get_audio_pattern(&self) -> Option<&[u8; AUDIO_PATTERN_SIZE]> {
+		if self.audio_pattern_loaded {
+			Some(&self.audio_pattern)
+		} else {
+			None
+		}
+	}
+
+	// XO-CHIP pattern playback rate in bits per second, set by FX3A
+	pub fn get_pattern_rate(&self) -> f32 {
+		PATTERN_BASE_RATE * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Platform, Profile};
+
+	// loads an XO-CHIP pattern, sets the pitch to 112 and then back to 64
+	const PATTERN_ROM: [u8; 28] = [
+		0xA2, 0x0C, // 0x200: I = 0x20C
+		0xF0, 0x02, // 0x202: load the pattern from I
+		0x60, 0x70, // 0x204: V0 = 112
+		0xF0, 0x3A, // 0x206: pitch = V0
+		0x61, 0x40, // 0x208: V1 = 64
+		0xF1, 0x3A, // 0x20A: pitch = V1
+		0xF0, 0x0F, 0xAA, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x20C: pattern
+		0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+	];
+
+	// a tone at a frequency that's a whole number of samples, with exact phase steps
+	fn tone(waveform: Waveform, volume: f32) -> ToneGenerator {
+		ToneGenerator::new(AudioSettings { frequency: 100.0, waveform, volume, sample_rate: 800 })
+	}
+
+	fn beeping_emulator() -> Emulator {
+		let mut emulator: Emulator = Emulator::with_seed(Profile::default(), 0).unwrap();
+		emulator.set_sound_timer(10);
+		emulator
+	}
+
+	fn samples(generator: &mut ToneGenerator, count: usize) -> Vec<f32> {
+		let mut samples: Vec<f32> = vec![f32::NAN; count];
+		generator.fill(&mut samples);
+		samples
+	}
+
+	#[test]
+	fn silent_while_the_sound_timer_is_zero() {
+		let mut emulator: Emulator = Emulator::with_seed(Profile::default(), 0).unwrap();
+		let mut generator: ToneGenerator = tone(Waveform::Square, 1.0);
+		assert!(!emulator.is_sound_active());
+
+		generator.update(&emulator);
+		assert_eq!(samples(&mut generator, 16), [0.0; 16]);
+
+		emulator.set_sound_timer(1);
+		assert!(emulator.is_sound_active());
+		generator.update(&emulator);
+		assert_eq!(samples(&mut generator, 2), [1.0, 1.0]);
+
+		// the timer running out stops the sound straight away
+		emulator.set_sound_timer(0);
+		generator.update(&emulator);
+		assert_eq!(samples(&mut generator, 2), [0.0, 0.0]);
+	}
+
+	#[test]
+	fn waves_repeat_at_the_frequency() {
+		let emulator: Emulator = beeping_emulator();
+
+		// 100 Hz at 800 samples a second is 8 samples a wave
+		let mut square: ToneGenerator = tone(Waveform::Square, 1.0);
+		square.update(&emulator);
+		assert_eq!(samples(&mut square, 12), [1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0, 1.0, 1.0, 1.0, 1.0]);
+
+		let mut triangle: ToneGenerator = tone(Waveform::Triangle, 1.0);
+		triangle.update(&emulator);
+		assert_eq!(samples(&mut triangle, 10), [-1.0, -0.5, 0.0, 0.5, 1.0, 0.5, 0.0, -0.5, -1.0, -0.5]);
+
+		let mut sawtooth: ToneGenerator = tone(Waveform::Sawtooth, 1.0);
+		sawtooth.update(&emulator);
+		assert_eq!(samples(&mut sawtooth, 5), [-1.0, -0.75, -0.5, -0.25, 0.0]);
+	}
+
+	#[test]
+	fn volume_scales_every_sample() {
+		let emulator: Emulator = beeping_emulator();
+
+		let mut quiet: ToneGenerator = tone(Waveform::Triangle, 0.5);
+		quiet.update(&emulator);
+		assert_eq!(samples(&mut quiet, 5), [-0.5, -0.25, 0.0, 0.25, 0.5]);
+
+		let mut silent: ToneGenerator = tone(Waveform::Square, 0.0);
+		silent.update(&emulator);
+		assert_eq!(samples(&mut silent, 4), [0.0; 4]);
+	}
+
+	#[test]
+	fn patterns_play_at_the_pitch_rate() {
+		let mut emulator: Emulator = Emulator::with_seed(Platform::XoChip.profile(), 0).unwrap();
+		emulator.load_rom(&PATTERN_ROM).unwrap();
+		assert_eq!(emulator.get_audio_pattern(), None);
+		assert_eq!(emulator.get_pattern_rate(), 4000.0);
+
+		for _ in 0..4 {
+			emulator.tick().unwrap();
+		}
+		assert_eq!(emulator.get_audio_pattern().unwrap()[..3], [0xF0, 0x0F, 0xAA]);
+		assert_eq!(emulator.get_pattern_rate(), 8000.0);
+
+		// pitch 64 is 4000 bits a second
+		for _ in 0..2 {
+			emulator.tick().unwrap();
+		}
+		assert_eq!(emulator.get_pattern_rate(), 4000.0);
+
+		// so at 4000 samples a second, each sample is the next bit
+		emulator.set_sound_timer(10);
+		let mut generator: ToneGenerator = ToneGenerator::new(AudioSettings { sample_rate: 4000, volume: 1.0, ..AudioSettings::default() });
+		generator.update(&emulator);
+		let expected: Vec<f32> = [1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 0, 1, 0, 1, 0, 1, 0]
+			.iter()
+			.map(|bit| if *bit == 1 {1.0} else {-1.0})
+			.collect();
+		assert_eq!(samples(&mut generator, 24), expected);
+	}
+}
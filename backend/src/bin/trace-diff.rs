@@ -0,0 +1,72 @@
+// compares two execution traces (see backend's tracer.rs) and says where
+// they first differ, e.g. to see what a quirk changes or where another
+// emulator behaves differently
+//
+// exits with 0 if the traces are the same, 1 if they differ, and 2 if they
+// couldn't be read, like diff
+
+// crates
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::process::ExitCode;
+
+use backend::Divergence;
+
+fn main() -> ExitCode {
+	let args: Vec<String> = env::args().collect();
+
+	let [_, left_path, right_path] = args.as_slice() else {
+		eprintln!("Usage: cargo run -p backend --bin trace-diff path_to_trace path_to_trace");
+		return ExitCode::from(2);
+	};
+
+	let divergence: io::Result<Option<Divergence>> = File::open(left_path)
+		.and_then(|left| File::open(right_path).map(|right| (left, right)))
+		.and_then(|(left, right)| backend::compare_traces(BufReader::new(left), BufReader::new(right)));
+
+	match divergence {
+		Ok(None) => {
+			println!("Traces are the same");
+			ExitCode::SUCCESS
+		},
+		Ok(Some(divergence)) => {
+			report(&divergence, left_path, right_path);
+			ExitCode::from(1)
+		},
+		Err(error) => {
+			eprintln!("Unable to read traces: {}", error);
+			ExitCode::from(2)
+		},
+	}
+}
+
+// print both lines, with the characters that differ marked underneath
+fn report(divergence: &Divergence, left_path: &str, right_path: &str) {
+	match divergence.cycle {
+		Some(cycle) => println!("First difference is at cycle {} (line {})", cycle, divergence.line),
+		None => println!("First difference is at line {}", divergence.line),
+	}
+
+	let (left, right): (&str, &str) = match (&divergence.left, &divergence.right) {
+		(Some(left), Some(right)) => (left, right),
+		(None, _) => {
+			println!("{} ended first", left_path);
+			return;
+		},
+		(_, None) => {
+			println!("{} ended first", right_path);
+			return;
+		},
+	};
+
+	let left_characters: Vec<char> = left.chars().collect();
+	let right_characters: Vec<char> = right.chars().collect();
+	let markers: String = (0..left_characters.len().max(right_characters.len()))
+		.map(|index| if left_characters.get(index) == right_characters.get(index) {' '} else {'^'})
+		.collect();
+
+	println!("< {}", left);
+	println!("> {}", right);
+	println!("  {}", markers.trim_end());
+}
@@ -0,0 +1,438 @@
+// crates
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::{Emulator, EmulatorError, Instruction};
+
+// part of the machine a breakpoint condition looks at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+	// V0 - VF
+	V(u8),
+	I,
+	DelayTimer,
+	SoundTimer,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+	Equal,
+	NotEqual,
+	Less,
+	LessOrEqual,
+	Greater,
+	GreaterOrEqual,
+}
+
+// e.g. "V3 == 0x10" - a conditional breakpoint only stops when this is true
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+	pub register: Register,
+	pub comparison: Comparison,
+	pub value: u16,
+}
+
+impl Condition {
+	pub fn is_met(&self, emulator: &Emulator) -> bool {
+		let current: u16 = match self.register {
+			Register::V(index) => emulator.v_registers.get(index as usize).copied().unwrap_or(0) as u16,
+			Register::I => emulator.i_register,
+			Register::DelayTimer => emulator.delay_timer as u16,
+			Register::SoundTimer => emulator.sound_timer as u16,
+		};
+
+		match self.comparison {
+			Comparison::Equal => current == self.value,
+			Comparison::NotEqual => current != self.value,
+			Comparison::Less => current < self.value,
+			Comparison::LessOrEqual => current <= self.value,
+			Comparison::Greater => current > self.value,
+			Comparison::GreaterOrEqual => current >= self.value,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+	Read,
+	Write,
+}
+
+// RAM an instruction used - the emulator keeps these for the last instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct MemoryAccess {
+	pub(crate) range: Range<usize>,
+	pub(crate) kind: AccessKind,
+}
+
+// stop when an instruction reads or writes any of length bytes from address
+// instruction fetches don't count, only the data instructions use, e.g. FX55 or DXYN
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+	pub address: u16,
+	pub length: u16,
+	pub read: bool,
+	pub write: bool,
+}
+
+impl Watchpoint {
+	// whether an access by an instruction should stop, and the first address it hit
+	fn hit(&self, access: &MemoryAccess) -> Option<u16> {
+		let wanted: bool = match access.kind {
+			AccessKind::Read => self.read,
+			AccessKind::Write => self.write,
+		};
+		let start: usize = access.range.start.max(self.address as usize);
+		let end: usize = access.range.end.min(self.address as usize + self.length as usize);
+
+		if wanted && start < end {Some(start as u16)} else {None}
+	}
+}
+
+// why the debugger handed control back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+	// step, step over or step out finished
+	Step,
+	// about to run the instruction at a breakpoint
+	Breakpoint { address: u16 },
+	// instruction at pc has just read or written a watched address
+	Watchpoint { address: u16, access: AccessKind, pc: u16 },
+	// game has run the SUPER-CHIP exit opcode
+	Halted,
+	// instruction couldn't be run - the pc is left pointing at it
+	Error(EmulatorError),
+	// a sprite was drawn with the display wait quirk, so nothing more runs
+	// until tick_timers is called for the next frame
+	WaitingForFrame,
+	// ran the most instructions allowed without anything else stopping it
+	TickLimit,
+}
+
+// breakpoints and watchpoints, and ways of running an emulator until they're hit
+// every run takes the most instructions it may run, so a frontend can keep its
+// frames going (and call tick_timers) while the game runs under the debugger
+#[derive(Clone, Debug, Default)]
+pub struct Debugger {
+	// address, and the condition if it only stops sometimes
+	breakpoints: BTreeMap<u16, Option<Condition>>,
+	watchpoints: Vec<Watchpoint>,
+}
+
+impl Debugger {
+	// new constructor for Debugger class - nothing is set to start with
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// stop before running the instruction at address
+	pub fn add_breakpoint(&mut self, address: u16) {
+		self.breakpoints.insert(address, None);
+	}
+
+	// stop before running the instruction at address, if condition is true then
+	pub fn add_conditional_breakpoint(&mut self, address: u16, condition: Condition) {
+		self.breakpoints.insert(address, Some(condition));
+	}
+
+	// returns whether there was one to remove
+	pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+		self.breakpoints.remove(&address).is_some()
+	}
+
+	pub fn clear_breakpoints(&mut self) {
+		self.breakpoints.clear();
+	}
+
+	// every breakpoint in address order
+	pub fn get_breakpoints(&self) -> impl Iterator<Item = (u16, Option<Condition>)> + '_ {
+		self.breakpoints.iter().map(|(&address, &condition)| (address, condition))
+	}
+
+	pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+		self.watchpoints.push(watchpoint);
+	}
+
+	// returns whether there was one to remove
+	pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+		let length: usize = self.watchpoints.len();
+		self.watchpoints.retain(|&existing| existing != watchpoint);
+		self.watchpoints.len() != length
+	}
+
+	pub fn clear_watchpoints(&mut self) {
+		self.watchpoints.clear();
+	}
+
+	pub fn get_watchpoints(&self) -> &[Watchpoint] {
+		&self.watchpoints
+	}
+
+	// run exactly one instruction
+	pub fn step(&self, emulator: &mut Emulator) -> StopReason {
+		self.run_until(emulator, 1, |_| true)
+	}
+
+	// run one instruction, or the whole subroutine if it's a CALL
+	pub fn step_over(&self, emulator: &mut Emulator, max_ticks: usize) -> StopReason {
+		let opcode: Option<u16> = emulator.ram.get(emulator.pc as usize..emulator.pc as usize + 2)
+			.map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+
+		match opcode.and_then(Instruction::decode) {
+			Some(Instruction::Call { .. }) => {
+				// back at the next instruction with the call's stack slot gone again
+				let return_address: u16 = emulator.pc.wrapping_add(2);
+				let stack_pointer: u16 = emulator.stack_pointer;
+				self.run_until(emulator, max_ticks, |emulator| emulator.pc == return_address && emulator.stack_pointer == stack_pointer)
+			},
+			_ => self.step(emulator),
+		}
+	}
+
+	// run until the current subroutine returns
+	// outside of a subroutine there's nothing to return from, so this runs
+	// until something else stops it
+	pub fn step_out(&self, emulator: &mut Emulator, max_ticks: usize) -> StopReason {
+		let stack_pointer: u16 = emulator.stack_pointer;
+		self.run_until(emulator, max_ticks, |emulator| emulator.stack_pointer < stack_pointer)
+	}
+
+	// run until a breakpoint or watchpoint is hit
+	// the first instruction always runs, so this carries on from a breakpoint
+	pub fn run(&self, emulator: &mut Emulator, max_ticks: usize) -> StopReason {
+		self.run_until(emulator, max_ticks, |_| false)
+	}
+
+	// tick until done says so, or something else stops it first
+	// memory accesses are only recorded while this runs, so ticks outside a
+	// debugger don't pay for them
+	fn run_until<F>(&self, emulator: &mut Emulator, max_ticks: usize, done: F) -> StopReason
+	where
+		F: Fn(&Emulator) -> bool,
+	{
+		let watching_memory: bool = emulator.watching_memory;
+		emulator.watching_memory = !self.watchpoints.is_empty();
+		let reason: StopReason = self.tick_until(emulator, max_ticks, done);
+		emulator.watching_memory = watching_memory;
+
+		reason
+	}
+
+	fn tick_until<F>(&self, emulator: &mut Emulator, max_ticks: usize, done: F) -> StopReason
+	where
+		F: Fn(&Emulator) -> bool,
+	{
+		for _ in 0..max_ticks {
+			if emulator.halted {
+				return StopReason::Halted;
+			}
+			if emulator.waiting_for_vblank {
+				return StopReason::WaitingForFrame;
+			}
+
+			let pc: u16 = emulator.pc;
+			if let Err(error) = emulator.tick() {
+				return StopReason::Error(error);
+			}
+
+			if let Some(reason) = self.check_watchpoints(emulator, pc) {
+				return reason;
+			}
+			if done(emulator) {
+				return StopReason::Step;
+			}
+			// stop before the next instruction runs, rather than after
+			if self.is_breakpoint_hit(emulator) {
+				return StopReason::Breakpoint { address: emulator.pc };
+			}
+		}
+
+		StopReason::TickLimit
+	}
+
+	// whether there's a breakpoint at the pc that should stop, for frontends
+	// that step one instruction at a time themselves
+	pub fn is_breakpoint_hit(&self, emulator: &Emulator) -> bool {
+		match self.breakpoints.get(&emulator.pc) {
+			Some(Some(condition)) => condition.is_met(emulator),
+			Some(None) => true,
+			None => false,
+		}
+	}
+
+	// first watched address the last instruction used
+	fn check_watchpoints(&self, emulator: &Emulator, pc: u16) -> Option<StopReason> {
+		for access in emulator.memory_accesses.iter() {
+			for watchpoint in self.watchpoints.iter() {
+				if let Some(address) = watchpoint.hit(access) {
+					return Some(StopReason::Watchpoint { address, access: access.kind, pc });
+				}
+			}
+		}
+
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Platform, Profile};
+
+	// counts up in V0 forever, calling a subroutine that stores V0 at 0x300 and loads it back
+	const COUNTER_ROM: [u8; 24] = [
+		0x60, 0x00, // 0x200: V0 = 0
+		0x70, 0x01, // 0x202: V0 += 1
+		0x22, 0x10, // 0x204: call 0x210
+		0x12, 0x02, // 0x206: jump 0x202
+		0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+		0xA3, 0x00, // 0x210: I = 0x300
+		0xF0, 0x55, // 0x212: store V0 at I
+		0xF0, 0x65, // 0x214: load V0 from I
+		0x00, 0xEE, // 0x216: return
+	];
+
+	// a subroutine that calls another one
+	const NESTED_ROM: [u8; 16] = [
+		0x22, 0x06, // 0x200: call 0x206
+		0x12, 0x02, // 0x202: jump 0x202
+		0x00, 0x00,
+		0x22, 0x0C, // 0x206: call 0x20C
+		0x61, 0x01, // 0x208: V1 = 1
+		0x00, 0xEE, // 0x20A: return
+		0x62, 0x01, // 0x20C: V2 = 1
+		0x00, 0xEE, // 0x20E: return
+	];
+
+	fn emulator_with(profile: Profile, rom: &[u8]) -> Emulator {
+		let mut emulator: Emulator = Emulator::with_seed(profile, 0).unwrap();
+		emulator.load_rom(rom).unwrap();
+		emulator
+	}
+
+	#[test]
+	fn breakpoints_stop_before_the_instruction() {
+		let mut emulator: Emulator = emulator_with(Profile::default(), &COUNTER_ROM);
+		let mut debugger: Debugger = Debugger::new();
+		debugger.add_breakpoint(0x210);
+
+		assert_eq!(debugger.run(&mut emulator, 100), StopReason::Breakpoint { address: 0x210 });
+		assert_eq!(emulator.get_pc(), 0x210);
+		assert_eq!(emulator.get_stack_pointer(), 1);
+
+		// running again carries on from the breakpoint, and stops there next time round
+		assert_eq!(debugger.run(&mut emulator, 100), StopReason::Breakpoint { address: 0x210 });
+		assert_eq!(emulator.get_v_registers()[0], 2);
+
+		assert!(debugger.remove_breakpoint(0x210));
+		assert_eq!(debugger.run(&mut emulator, 100), StopReason::TickLimit);
+	}
+
+	#[test]
+	fn conditional_breakpoints_only_stop_when_met() {
+		let mut emulator: Emulator = emulator_with(Profile::default(), &COUNTER_ROM);
+		let mut debugger: Debugger = Debugger::new();
+		debugger.add_conditional_breakpoint(0x204, Condition { register: Register::V(0), comparison: Comparison::Equal, value: 3 });
+
+		assert_eq!(debugger.run(&mut emulator, 1000), StopReason::Breakpoint { address: 0x204 });
+		assert_eq!(emulator.get_v_registers()[0], 3);
+
+		// I is 0x300 from the second time round onwards
+		debugger.clear_breakpoints();
+		debugger.add_conditional_breakpoint(0x202, Condition { register: Register::I, comparison: Comparison::Greater, value: 0 });
+		assert_eq!(debugger.run(&mut emulator, 1000), StopReason::Breakpoint { address: 0x202 });
+		assert_eq!(emulator.get_i_register(), 0x300);
+	}
+
+	#[test]
+	fn watchpoints_stop_after_the_access() {
+		let mut emulator: Emulator = emulator_with(Profile::default(), &COUNTER_ROM);
+		let mut debugger: Debugger = Debugger::new();
+		let write: Watchpoint = Watchpoint { address: 0x2FF, length: 2, read: false, write: true };
+		debugger.add_watchpoint(write);
+
+		assert_eq!(debugger.run(&mut emulator, 100), StopReason::Watchpoint { address: 0x300, access: AccessKind::Write, pc: 0x212 });
+		assert_eq!(emulator.get_pc(), 0x214);
+
+		// the read straight after doesn't stop a write-only watchpoint
+		assert!(debugger.remove_watchpoint(write));
+		debugger.add_watchpoint(Watchpoint { address: 0x300, length: 1, read: true, write: false });
+		assert_eq!(debugger.run(&mut emulator, 100), StopReason::Watchpoint { address: 0x300, access: AccessKind::Read, pc: 0x214 });
+
+		// nor do accesses next to the watched range
+		debugger.clear_watchpoints();
+		debugger.add_watchpoint(Watchpoint { address: 0x301, length: 4, read: true, write: true });
+		assert_eq!(debugger.run(&mut emulator, 100), StopReason::TickLimit);
+
+		// once the debugger stops, ticks don't record accesses any more
+		assert!(!emulator.watching_memory);
+		for _ in 0..20 {
+			emulator.tick().unwrap();
+		}
+		assert!(emulator.memory_accesses.is_empty());
+	}
+
+	#[test]
+	fn step_over_runs_the_whole_call() {
+		let mut emulator: Emulator = emulator_with(Profile::default(), &COUNTER_ROM);
+		let mut debugger: Debugger = Debugger::new();
+
+		assert_eq!(debugger.step(&mut emulator), StopReason::Step);
+		assert_eq!(debugger.step_over(&mut emulator, 100), StopReason::Step);
+		assert_eq!(emulator.get_pc(), 0x204);
+
+		assert_eq!(debugger.step_over(&mut emulator, 100), StopReason::Step);
+		assert_eq!(emulator.get_pc(), 0x206);
+		assert_eq!(emulator.get_stack_pointer(), 0);
+		assert_eq!(emulator.get_i_register(), 0x300);
+
+		// a breakpoint inside the call still stops it
+		emulator.set_pc(0x204);
+		debugger.add_breakpoint(0x214);
+		assert_eq!(debugger.step_over(&mut emulator, 100), StopReason::Breakpoint { address: 0x214 });
+	}
+
+	#[test]
+	fn step_out_returns_to_the_caller() {
+		let mut emulator: Emulator = emulator_with(Profile::default(), &NESTED_ROM);
+		let debugger: Debugger = Debugger::new();
+
+		// into both subroutines, then out one at a time
+		debugger.step(&mut emulator);
+		debugger.step(&mut emulator);
+		assert_eq!((emulator.get_pc(), emulator.get_stack_pointer()), (0x20C, 2));
+
+		assert_eq!(debugger.step_out(&mut emulator, 100), StopReason::Step);
+		assert_eq!((emulator.get_pc(), emulator.get_stack_pointer()), (0x208, 1));
+		assert_eq!(debugger.step_out(&mut emulator, 100), StopReason::Step);
+		assert_eq!((emulator.get_pc(), emulator.get_stack_pointer()), (0x202, 0));
+
+		// stepping out of the outer subroutine goes past the inner one's return
+		let mut emulator: Emulator = emulator_with(Profile::default(), &NESTED_ROM);
+		debugger.step(&mut emulator);
+		assert_eq!(debugger.step_out(&mut emulator, 100), StopReason::Step);
+		assert_eq!((emulator.get_pc(), emulator.get_stack_pointer()), (0x202, 0));
+		assert_eq!(emulator.get_v_registers()[1..3], [1, 1]);
+	}
+
+	#[test]
+	fn other_stop_reasons() {
+		let debugger: Debugger = Debugger::new();
+
+		let mut emulator: Emulator = emulator_with(Profile::default(), &[0x00, 0xFD]);
+		assert_eq!(debugger.run(&mut emulator, 100), StopReason::Halted);
+
+		let mut emulator: Emulator = emulator_with(Profile::default(), &[0x60, 0x01, 0x50, 0x01]);
+		assert_eq!(debugger.run(&mut emulator, 100), StopReason::Error(EmulatorError::UnknownOpcode { pc: 0x202, opcode: 0x5001 }));
+		assert_eq!(emulator.get_pc(), 0x202);
+
+		// the VIP draws one sprite a frame
+		let mut emulator: Emulator = emulator_with(Platform::CosmacVip.profile(), &[0xD0, 0x01, 0x12, 0x00]);
+		assert_eq!(debugger.run(&mut emulator, 100), StopReason::WaitingForFrame);
+		emulator.tick_timers();
+		assert_eq!(debugger.run(&mut emulator, 100), StopReason::WaitingForFrame);
+
+		let mut emulator: Emulator = emulator_with(Profile::default(), &[0x12, 0x00]);
+		assert_eq!(debugger.run(&mut emulator, 50), StopReason::TickLimit);
+	}
+}
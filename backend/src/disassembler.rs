@@ -0,0 +1,122 @@
+// crates
+use std::fmt;
+
+use crate::Instruction;
+
+// one line of a disassembly listing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisassembledLine {
+	// where the bytes would be in RAM once loaded
+	pub address: u16,
+	// raw bytes - 2 for most instructions, 4 for LD I, LONG, 1 or 2 for data
+	pub bytes: Vec<u8>,
+	// None if the bytes aren't an instruction
+	pub instruction: Option<Instruction>,
+	// e.g. "LD V1, 0x2A", or "DW 0x1234" / "DB 0x12" for data
+	pub mnemonic: String,
+}
+
+// "0x0200  00 E0       CLS"
+impl fmt::Display for DisassembledLine {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+		write!(f, "{:#06X}  {:<12}{}", self.address, bytes.join(" "), self.mnemonic)
+	}
+}
+
+// list every instruction in a ROM loaded at base, one line each
+// Chip-8 can't tell code from data, so anything that decodes is shown as an
+// instruction and anything else as DW (or DB for a trailing odd byte)
+pub fn disassemble(rom: &[u8], base: u16) -> Vec<DisassembledLine> {
+	let mut lines: Vec<DisassembledLine> = Vec::new();
+	let mut offset: usize = 0;
+
+	while offset < rom.len() {
+		let address: u16 = base.wrapping_add(offset as u16);
+
+		// odd byte at the end
+		if offset + 1 == rom.len() {
+			lines.push(DisassembledLine {
+				address,
+				bytes: vec![rom[offset]],
+				instruction: None,
+				mnemonic: format!("DB {:#04X}", rom[offset]),
+			});
+			break;
+		}
+
+		let opcode: u16 = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+		let line: DisassembledLine = match Instruction::decode(opcode) {
+			// long I load needs the 2 bytes after it as well
+			Some(Instruction::LoadILong) if offset + 4 <= rom.len() => {
+				let long_address: u16 = u16::from_be_bytes([rom[offset + 2], rom[offset + 3]]);
+				DisassembledLine {
+					address,
+					bytes: rom[offset..offset + 4].to_vec(),
+					instruction: Some(Instruction::LoadILong),
+					mnemonic: format!("{} {:#06X}", Instruction::LoadILong, long_address),
+				}
+			},
+			Some(instruction) if instruction != Instruction::LoadILong => DisassembledLine {
+				address,
+				bytes: rom[offset..offset + 2].to_vec(),
+				instruction: Some(instruction),
+				mnemonic: instruction.to_string(),
+			},
+			// unknown opcode, or F000 cut off by the end of the ROM
+			_ => DisassembledLine {
+				address,
+				bytes: rom[offset..offset + 2].to_vec(),
+				instruction: None,
+				mnemonic: format!("DW {:#06X}", opcode),
+			},
+		};
+
+		offset += line.bytes.len();
+		lines.push(line);
+	}
+
+	lines
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn mnemonics(rom: &[u8]) -> Vec<String> {
+		disassemble(rom, 0x200).into_iter().map(|line| line.mnemonic).collect()
+	}
+
+	#[test]
+	fn lines_show_address_bytes_and_mnemonic() {
+		let lines: Vec<DisassembledLine> = disassemble(&[0x00, 0xE0, 0x12, 0x00], 0x200);
+		assert_eq!(lines[0].to_string(), "0x0200  00 E0       CLS");
+		assert_eq!(lines[1], DisassembledLine {
+			address: 0x202,
+			bytes: vec![0x12, 0x00],
+			instruction: Some(Instruction::Jump { address: 0x200 }),
+			mnemonic: String::from("JP 0x200"),
+		});
+	}
+
+	#[test]
+	fn unknown_opcodes_and_an_odd_last_byte_are_data() {
+		assert_eq!(mnemonics(&[0x51, 0x21, 0x00, 0xE0, 0xAB]), ["DW 0x5121", "CLS", "DB 0xAB"]);
+
+		let lines: Vec<DisassembledLine> = disassemble(&[0xAB], 0x300);
+		assert_eq!(lines.len(), 1);
+		assert_eq!((lines[0].address, lines[0].instruction), (0x300, None));
+	}
+
+	#[test]
+	fn long_i_takes_the_next_word_as_its_address() {
+		let lines: Vec<DisassembledLine> = disassemble(&[0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0], 0x200);
+		assert_eq!(lines[0].to_string(), "0x0200  F0 00 12 34 LD I, LONG 0x1234");
+		assert_eq!(lines[1].address, 0x204);
+		assert_eq!(lines[1].mnemonic, "CLS");
+
+		// cut off by the end of the ROM, it's just data
+		assert_eq!(mnemonics(&[0xF0, 0x00, 0x12]), ["DW 0xF000", "DB 0x12"]);
+		assert_eq!(mnemonics(&[0xF0, 0x00]), ["DW 0xF000"]);
+	}
+}
@@ -0,0 +1,160 @@
+// crates
+use std::error::Error;
+use std::fmt;
+
+// something a ROM did that the machine can't carry on from
+// pc is the address of the instruction that went wrong, and opcode is that instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmulatorError {
+	// program counter has run off the end of RAM, so there's no opcode to fetch
+	PcOutOfBounds { pc: u16 },
+	// opcode doesn't match any instruction
+	UnknownOpcode { pc: u16, opcode: u16 },
+	// CALL with every stack slot already in use
+	StackOverflow { pc: u16, opcode: u16 },
+	// RET with nothing on the stack to return to
+	StackUnderflow { pc: u16, opcode: u16 },
+	// read or write of an address past the end of RAM
+	MemoryOutOfBounds { pc: u16, opcode: u16, address: usize },
+	// key check with a key index above F
+	InvalidKey { pc: u16, opcode: u16, key: u8 },
+}
+
+impl EmulatorError {
+	// address of the instruction that went wrong
+	pub fn pc(&self) -> u16 {
+		match *self {
+			EmulatorError::PcOutOfBounds { pc } => pc,
+			EmulatorError::UnknownOpcode { pc, .. } => pc,
+			EmulatorError::StackOverflow { pc, .. } => pc,
+			EmulatorError::StackUnderflow { pc, .. } => pc,
+			EmulatorError::MemoryOutOfBounds { pc, .. } => pc,
+			EmulatorError::InvalidKey { pc, .. } => pc,
+		}
+	}
+
+	// instruction that went wrong, if it could be fetched
+	pub fn opcode(&self) -> Option<u16> {
+		match *self {
+			EmulatorError::PcOutOfBounds { .. } => None,
+			EmulatorError::UnknownOpcode { opcode, .. } => Some(opcode),
+			EmulatorError::StackOverflow { opcode, .. } => Some(opcode),
+			EmulatorError::StackUnderflow { opcode, .. } => Some(opcode),
+			EmulatorError::MemoryOutOfBounds { opcode, .. } => Some(opcode),
+			EmulatorError::InvalidKey { opcode, .. } => Some(opcode),
+		}
+	}
+}
+
+impl fmt::Display for EmulatorError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			EmulatorError::PcOutOfBounds { pc } => write!(f, "program counter {:#06x} is outside of RAM", pc),
+			EmulatorError::UnknownOpcode { pc, opcode } => write!(f, "unknown opcode {:#06x} at {:#06x}", opcode, pc),
+			EmulatorError::StackOverflow { pc, opcode } => write!(f, "stack overflow from opcode {:#06x} at {:#06x}", opcode, pc),
+			EmulatorError::StackUnderflow { pc, opcode } => write!(f, "stack underflow from opcode {:#06x} at {:#06x}", opcode, pc),
+			EmulatorError::MemoryOutOfBounds { pc, opcode, address } => write!(f, "address {:#06x} is outside of RAM for opcode {:#06x} at {:#06x}", address, opcode, pc),
+			EmulatorError::InvalidKey { pc, opcode, key } => write!(f, "key {:#04x} doesn't exist for opcode {:#06x} at {:#06x}", key, opcode, pc),
+		}
+	}
+}
+
+impl Error for EmulatorError {}
+
+// what went wrong inside an instruction, before tick adds where it happened
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Fault {
+	UnknownOpcode,
+	StackOverflow,
+	StackUnderflow,
+	MemoryOutOfBounds(usize),
+	InvalidKey(u8),
+}
+
+impl Fault {
+	// attach the instruction that caused this fault
+	pub(crate) fn at(self, pc: u16, opcode: u16) -> EmulatorError {
+		match self {
+			Fault::UnknownOpcode => EmulatorError::UnknownOpcode { pc, opcode },
+			Fault::StackOverflow => EmulatorError::StackOverflow { pc, opcode },
+			Fault::StackUnderflow => EmulatorError::StackUnderflow { pc, opcode },
+			Fault::MemoryOutOfBounds(address) => EmulatorError::MemoryOutOfBounds { pc, opcode, address },
+			Fault::InvalidKey(key) => EmulatorError::InvalidKey { pc, opcode, key },
+		}
+	}
+}
+
+// reason a machine can't be built from a profile
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileError {
+	// not enough RAM to hold the fonts
+	RamTooSmall { ram_size: usize, min_size: usize },
+	// programs would start inside the font area or past the end of RAM
+	InvalidStartAddress { start_address: u16 },
+}
+
+impl fmt::Display for ProfileError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ProfileError::RamTooSmall { ram_size, min_size } => write!(f, "{} bytes of RAM is too small, the fonts need {}", ram_size, min_size),
+			ProfileError::InvalidStartAddress { start_address } => write!(f, "programs can't start at {:#06x}", start_address),
+		}
+	}
+}
+
+impl Error for ProfileError {}
+
+// reason a ROM image couldn't be loaded into RAM
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RomError {
+	// ROM has no bytes in it
+	Empty,
+	// ROM doesn't fit between its load address and the end of RAM
+	TooLarge { size: usize, max_size: usize },
+	// load address is inside the font area or past the end of RAM
+	InvalidAddress { address: u16 },
+}
+
+impl fmt::Display for RomError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			RomError::Empty => write!(f, "ROM is empty"),
+			RomError::TooLarge { size, max_size } => write!(f, "ROM is {} bytes but only {} bytes of RAM are free", size, max_size),
+			RomError::InvalidAddress { address } => write!(f, "ROM can't be loaded at {:#06x}", address),
+		}
+	}
+}
+
+impl Error for RomError {}
+
+// reason a save state couldn't be restored - the machine is left as it was
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+	// doesn't start with the save state magic bytes
+	BadMagic,
+	// written by a version of the format this build doesn't read
+	UnsupportedVersion { version: u16 },
+	// checksum doesn't match, so the data has been damaged
+	ChecksumMismatch,
+	// ends before all the fields have been read, or has bytes left over
+	WrongLength,
+	// saved from a machine with a different amount of RAM or stack
+	IncompatibleProfile,
+	// saved from a machine with a different kind of random number generator
+	IncompatibleRandomSource,
+}
+
+impl fmt::Display for StateError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			StateError::BadMagic => write!(f, "not a save state"),
+			StateError::UnsupportedVersion { version } => write!(f, "save state version {} isn't supported", version),
+			StateError::ChecksumMismatch => write!(f, "save state is corrupted"),
+			StateError::WrongLength => write!(f, "save state is the wrong length"),
+			StateError::IncompatibleProfile => write!(f, "save state is from a machine with a different RAM or stack size"),
+			StateError::IncompatibleRandomSource => write!(f, "save state is from a machine with a different random number generator"),
+		}
+	}
+}
+
+impl Error for StateError {}
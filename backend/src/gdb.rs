@@ -0,0 +1,859 @@
+// GDB remote serial protocol server, so gdb (or anything else that speaks the
+// protocol) can debug a game over a localhost TCP port
+//
+// registers, in the order g and G use them, are V0-VF (1 byte each), I (2),
+// PC (2), SP (1), DT (1) and ST (1) - gdb gets these names and sizes from a
+// target description, and values wider than a byte are little endian, as gdb
+// assumes without an architecture to go on. memory is RAM, so anything past
+// the end of it can't be read or written
+//
+// supports ?, g, G, p, P, m, M, c, C, s, S, Z0-Z4 and z0-z4 (software and
+// hardware breakpoints are the same, 2-4 are watchpoints), D, k, qSupported,
+// qXfer:features:read, QStartNoAckMode, and ctrl-c to stop a running game
+
+// crates
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+
+use crate::{AccessKind, Debugger, Emulator, EmulatorError, StopReason, Watchpoint, NO_OF_REGISTERS};
+
+// gdb register numbers after V0-VF
+const I_REGISTER: usize = 16;
+const PC_REGISTER: usize = 17;
+const SP_REGISTER: usize = 18;
+const DT_REGISTER: usize = 19;
+const ST_REGISTER: usize = 20;
+const NO_OF_GDB_REGISTERS: usize = 21;
+// largest packet gdb is told it can send
+const PACKET_SIZE: usize = 0x1000;
+// byte gdb sends on its own when the user presses ctrl-c
+const INTERRUPT: u8 = 0x03;
+// signals stop replies report
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+// what arrived from gdb
+enum Incoming {
+	Interrupt,
+	Packet(String),
+}
+
+// one gdb connection at a time - the frontend calls update once a frame, and
+// the game only runs while gdb has said to continue
+// with nobody connected the game runs as normal
+pub struct GdbServer {
+	listener: TcpListener,
+	client: Option<TcpStream>,
+	// bytes received that aren't a whole packet yet
+	input: Vec<u8>,
+	// gdb asked for packets not to be acknowledged with + and -
+	no_ack: bool,
+	debugger: Debugger,
+	// gdb said to continue, and nothing has stopped the game since
+	running: bool,
+	// gdb said to step, but the instruction is waiting for the next frame
+	stepping: bool,
+	// stop reply for the last time the game stopped, for the ? packet
+	last_stop: String,
+}
+
+impl GdbServer {
+	// new constructor for GdbServer class - only listens on localhost, as
+	// anyone connected can read and write all of the machine
+	pub fn bind(port: u16) -> io::Result<Self> {
+		let listener: TcpListener = TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))?;
+		listener.set_nonblocking(true)?;
+
+		Ok(Self {
+			listener,
+			client: None,
+			input: Vec::new(),
+			no_ack: false,
+			debugger: Debugger::new(),
+			running: false,
+			stepping: false,
+			last_stop: stop_reply(StopReason::Step),
+		})
+	}
+
+	// address actually listened on, e.g. to find the port if 0 was asked for
+	pub fn local_addr(&self) -> io::Result<SocketAddr> {
+		self.listener.local_addr()
+	}
+
+	pub fn is_connected(&self) -> bool {
+		self.client.is_some()
+	}
+
+	// whether the game is stopped, waiting for gdb to say what to do next
+	pub fn is_stopped(&self) -> bool {
+		self.client.is_some() && !self.running && !self.stepping
+	}
+
+	// deal with anything gdb has sent, then run up to max_ticks instructions
+	// unless gdb has the game stopped
+	// returns whether a frame went by, so the frontend knows to tick the timers
+	// an error with nobody connected is returned, as there's no-one to tell
+	pub fn update(&mut self, emulator: &mut Emulator, max_ticks: usize) -> Result<bool, EmulatorError> {
+		self.accept();
+		self.receive(emulator);
+
+		if self.is_stopped() {
+			return Ok(false);
+		}
+
+		let stepping: bool = self.stepping;
+		let reason: StopReason = if stepping {
+			self.debugger.step(emulator)
+		} else {
+			self.debugger.run(emulator, max_ticks)
+		};
+
+		match reason {
+			// a step waiting for the next frame is tried again once it's come
+			StopReason::WaitingForFrame | StopReason::TickLimit => Ok(true),
+			StopReason::Error(error) if self.client.is_none() => Err(error),
+			_ => {
+				self.stop(reason);
+				// a single step doesn't take a whole frame
+				Ok(!stepping)
+			},
+		}
+	}
+
+	// take a new connection if there isn't one already - the game stops so
+	// gdb can look at it, as it expects
+	fn accept(&mut self) {
+		if self.client.is_some() {
+			return;
+		}
+
+		let Ok((stream, _)) = self.listener.accept() else {
+			return;
+		};
+		if stream.set_nonblocking(true).is_err() {
+			return;
+		}
+		// replies are small, so don't wait to fill a segment before sending them
+		let _ = stream.set_nodelay(true);
+
+		self.client = Some(stream);
+		self.input.clear();
+		self.no_ack = false;
+		self.debugger = Debugger::new();
+		self.running = false;
+		self.stepping = false;
+		self.last_stop = stop_reply(StopReason::Step);
+	}
+
+	// forget the connection, and let the game run as normal again
+	fn disconnect(&mut self) {
+		self.client = None;
+		self.input.clear();
+		self.debugger = Debugger::new();
+		self.running = false;
+		self.stepping = false;
+	}
+
+	// read everything waiting on the socket and handle every whole packet
+	fn receive(&mut self, emulator: &mut Emulator) {
+		let mut buffer: [u8; 1024] = [0; 1024];
+
+		while let Some(stream) = self.client.as_mut() {
+			match stream.read(&mut buffer) {
+				// gdb has gone
+				Ok(0) => self.disconnect(),
+				Ok(length) => self.input.extend_from_slice(&buffer[..length]),
+				Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+				Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+				Err(_) => self.disconnect(),
+			}
+		}
+
+		while let Some(incoming) = self.next_incoming() {
+			match incoming {
+				Incoming::Interrupt => {
+					if self.running || self.stepping {
+						self.stop_with(format!("S{:02x}", SIGINT));
+					}
+				},
+				Incoming::Packet(packet) => self.handle(&packet, emulator),
+			}
+		}
+	}
+
+	// take the next interrupt or whole packet out of the input, acknowledging
+	// packets unless gdb has turned that off
+	fn next_incoming(&mut self) -> Option<Incoming> {
+		loop {
+			match *self.input.first()? {
+				INTERRUPT => {
+					self.input.remove(0);
+					return Some(Incoming::Interrupt);
+				},
+				b'$' => {
+					// wait for the rest of the packet and its 2 checksum digits
+					let end: usize = match self.input.iter().position(|&byte| byte == b'#') {
+						Some(end) => end,
+						None if self.input.len() > PACKET_SIZE * 2 => {
+							// never going to end - throw it away
+							self.input.clear();
+							return None;
+						},
+						None => return None,
+					};
+					if self.input.len() < end + 3 {
+						return None;
+					}
+
+					let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+					let data: &[u8] = &packet[1..end];
+					let valid: bool = std::str::from_utf8(&packet[end + 1..])
+						.ok()
+						.and_then(|digits| u8::from_str_radix(digits, 16).ok())
+						== Some(checksum(data));
+
+					if !self.no_ack {
+						self.send_raw(if valid {b"+"} else {b"-"});
+					}
+					// gdb sends a bad packet again, so there's nothing else to do with it
+					if valid {
+						return Some(Incoming::Packet(String::from_utf8_lossy(data).into_owned()));
+					}
+				},
+				// acknowledgements of what was sent, or noise between packets
+				_ => {
+					self.input.remove(0);
+				},
+			}
+		}
+	}
+
+	// run one packet's command, and reply to it
+	fn handle(&mut self, packet: &str, emulator: &mut Emulator) {
+		let Some(command) = packet.chars().next() else {
+			self.send("");
+			return;
+		};
+		let arguments: &str = &packet[command.len_utf8()..];
+
+		let reply: String = match command {
+			'?' => self.last_stop.clone(),
+			'g' => read_registers(emulator),
+			'G' => ok_or_error(write_registers(emulator, arguments)),
+			'p' => match usize::from_str_radix(arguments, 16).ok().and_then(|number| read_register(emulator, number)) {
+				Some(value) => value,
+				None => error_reply(),
+			},
+			'P' => {
+				let written: Option<bool> = arguments.split_once('=').and_then(|(number, value)| {
+					let number: usize = usize::from_str_radix(number, 16).ok()?;
+					Some(write_register(emulator, number, &decode_hex(value)?))
+				});
+				ok_or_error(written == Some(true))
+			},
+			'm' => read_memory(emulator, arguments).unwrap_or_else(error_reply),
+			'M' => ok_or_error(write_memory(emulator, arguments)),
+			// continue and step, optionally from a new address - the signal
+			// C and S would pass to the game means nothing here
+			'c' | 'C' | 's' | 'S' => {
+				let address: &str = match command {
+					'c' | 's' => arguments,
+					_ => arguments.split_once(';').map_or("", |(_, address)| address),
+				};
+				if !address.is_empty() {
+					match parse_address(address) {
+						Some(address) => emulator.set_pc(address),
+						None => {
+							self.send(&error_reply());
+							return;
+						},
+					}
+				}
+
+				// the reply is sent once the game stops again
+				if command == 'c' || command == 'C' {
+					self.running = true;
+				} else {
+					self.stepping = true;
+				}
+				return;
+			},
+			'Z' => self.change_breakpoint(arguments, true),
+			'z' => self.change_breakpoint(arguments, false),
+			'q' => query(arguments),
+			'Q' if arguments == "StartNoAckMode" => {
+				// this reply is the last one gdb acknowledges
+				self.send("OK");
+				self.no_ack = true;
+				return;
+			},
+			// there's only ever the one thread
+			'H' | 'T' => String::from("OK"),
+			// detach and leave the game running
+			'D' => {
+				self.send("OK");
+				self.disconnect();
+				return;
+			},
+			'k' => {
+				self.disconnect();
+				return;
+			},
+			'v' if arguments.starts_with("Kill") => {
+				self.send("OK");
+				self.disconnect();
+				return;
+			},
+			// anything else isn't supported
+			_ => String::new(),
+		};
+
+		self.send(&reply);
+	}
+
+	// Z and z packets, e.g. "0,204,2" - type, address, then kind, which is a
+	// length for watchpoints
+	fn change_breakpoint(&mut self, arguments: &str, insert: bool) -> String {
+		// ignore any conditions gdb wants the stub to check itself
+		let arguments: &str = arguments.split(';').next().unwrap_or("");
+		let fields: Vec<&str> = arguments.split(',').collect();
+		let [kind, address, length] = fields.as_slice() else {
+			return error_reply();
+		};
+		let (Some(address), Some(length)) = (parse_address(address), parse_address(length)) else {
+			return error_reply();
+		};
+
+		match *kind {
+			// software and hardware breakpoints
+			"0" | "1" => {
+				if insert {
+					self.debugger.add_breakpoint(address);
+				} else {
+					self.debugger.remove_breakpoint(address);
+				}
+			},
+			// write, read and access watchpoints
+			"2" | "3" | "4" => {
+				let watchpoint: Watchpoint = Watchpoint {
+					address,
+					length,
+					read: *kind != "2",
+					write: *kind != "3",
+				};
+				if insert {
+					self.debugger.add_watchpoint(watchpoint);
+				} else {
+					self.debugger.remove_watchpoint(watchpoint);
+				}
+			},
+			_ => return String::new(),
+		}
+
+		String::from("OK")
+	}
+
+	// game has stopped, so tell gdb why
+	fn stop(&mut self, reason: StopReason) {
+		self.stop_with(stop_reply(reason));
+	}
+
+	fn stop_with(&mut self, reply: String) {
+		self.running = false;
+		self.stepping = false;
+		self.send(&reply);
+		self.last_stop = reply;
+	}
+
+	// send a packet, escaping the characters the protocol uses itself
+	fn send(&mut self, data: &str) {
+		let mut packet: Vec<u8> = Vec::with_capacity(data.len() + 4);
+		packet.push(b'$');
+		for &byte in data.as_bytes() {
+			if matches!(byte, b'#' | b'$' | b'}' | b'*') {
+				packet.push(b'}');
+				packet.push(byte ^ 0x20);
+			} else {
+				packet.push(byte);
+			}
+		}
+		let sum: u8 = checksum(&packet[1..]);
+		packet.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+
+		self.send_raw(&packet);
+	}
+
+	// the socket only blocks while writing, so nothing is ever half sent
+	fn send_raw(&mut self, bytes: &[u8]) {
+		let Some(stream) = self.client.as_mut() else {
+			return;
+		};
+
+		let sent: io::Result<()> = stream.set_nonblocking(false)
+			.and_then(|_| stream.write_all(bytes))
+			.and_then(|_| stream.set_nonblocking(true));
+		if sent.is_err() {
+			self.disconnect();
+		}
+	}
+}
+
+// sum of the bytes between $ and #
+fn checksum(data: &[u8]) -> u8 {
+	data.iter().fold(0, |sum: u8, &byte| sum.wrapping_add(byte))
+}
+
+fn error_reply() -> String {
+	String::from("E01")
+}
+
+fn ok_or_error(ok: bool) -> String {
+	if ok {String::from("OK")} else {error_reply()}
+}
+
+// what gdb is told about why the game stopped
+fn stop_reply(reason: StopReason) -> String {
+	match reason {
+		StopReason::Watchpoint { address, access, .. } => {
+			let kind: &str = match access {
+				AccessKind::Read => "rwatch",
+				AccessKind::Write => "watch",
+			};
+			format!("T{:02x}{}:{:x};", SIGTRAP, kind, address)
+		},
+		// as far as gdb knows, the program has exited
+		StopReason::Halted => String::from("W00"),
+		StopReason::Error(error) => {
+			let signal: u8 = match error {
+				EmulatorError::UnknownOpcode { .. } | EmulatorError::InvalidKey { .. } => SIGILL,
+				_ => SIGSEGV,
+			};
+			format!("S{:02x}", signal)
+		},
+		_ => format!("S{:02x}", SIGTRAP),
+	}
+}
+
+// a 16 bit value in hex, e.g. an address or a length
+fn parse_address(text: &str) -> Option<u16> {
+	u16::from_str_radix(text, 16).ok()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+	if !text.len().is_multiple_of(2) {
+		return None;
+	}
+
+	(0..text.len()).step_by(2)
+		.map(|index| text.get(index..index + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok()))
+		.collect()
+}
+
+// value of a gdb register, and how many bytes it takes up
+fn register_value(emulator: &Emulator, number: usize) -> Option<(u16, usize)> {
+	match number {
+		0..=15 => Some((emulator.get_v_registers()[number] as u16, 1)),
+		I_REGISTER => Some((emulator.get_i_register(), 2)),
+		PC_REGISTER => Some((emulator.get_pc(), 2)),
+		SP_REGISTER => Some((emulator.get_stack_pointer(), 1)),
+		DT_REGISTER => Some((emulator.get_delay_timer() as u16, 1)),
+		ST_REGISTER => Some((emulator.get_sound_timer() as u16, 1)),
+		_ => None,
+	}
+}
+
+fn read_register(emulator: &Emulator, number: usize) -> Option<String> {
+	let (value, size): (u16, usize) = register_value(emulator, number)?;
+	Some(encode_hex(&value.to_le_bytes()[..size]))
+}
+
+// value for a register from gdb's bytes, if they're exactly the register's
+// size and hold something the register can be set to
+fn fits(emulator: &Emulator, number: usize, bytes: &[u8]) -> Option<u16> {
+	let (_, size): (u16, usize) = register_value(emulator, number)?;
+	if bytes.len() != size {
+		return None;
+	}
+	let value: u16 = if size == 2 {u16::from_le_bytes([bytes[0], bytes[1]])} else {bytes[0] as u16};
+
+	// a stack pointer past the end of the stack would break the next CALL or RET
+	if number == SP_REGISTER && value as usize > emulator.get_stack().len() {
+		return None;
+	}
+	Some(value)
+}
+
+// put a value checked by fits into a register
+fn set_register(emulator: &mut Emulator, number: usize, value: u16) {
+	match number {
+		0..=15 => {
+			emulator.set_v_register(number, value as u8);
+		},
+		I_REGISTER => emulator.set_i_register(value),
+		PC_REGISTER => emulator.set_pc(value),
+		SP_REGISTER => {
+			emulator.set_stack_pointer(value);
+		},
+		DT_REGISTER => emulator.set_delay_timer(value as u8),
+		ST_REGISTER => emulator.set_sound_timer(value as u8),
+		_ => (),
+	}
+}
+
+// returns whether it was written
+fn write_register(emulator: &mut Emulator, number: usize, bytes: &[u8]) -> bool {
+	match fits(emulator, number, bytes) {
+		Some(value) => {
+			set_register(emulator, number, value);
+			true
+		},
+		None => false,
+	}
+}
+
+// every register, one after another
+fn read_registers(emulator: &Emulator) -> String {
+	(0..NO_OF_GDB_REGISTERS).filter_map(|number| read_register(emulator, number)).collect()
+}
+
+// every register from a g packet's layout - nothing is written unless it all fits
+fn write_registers(emulator: &mut Emulator, text: &str) -> bool {
+	let Some(bytes) = decode_hex(text) else {
+		return false;
+	};
+	let sizes: Vec<usize> = (0..NO_OF_GDB_REGISTERS).filter_map(|number| register_value(emulator, number)).map(|(_, size)| size).collect();
+	if bytes.len() != sizes.iter().sum::<usize>() {
+		return false;
+	}
+
+	// every value is checked before any register changes
+	let mut offset: usize = 0;
+	let mut values: Vec<u16> = Vec::with_capacity(sizes.len());
+	for (number, size) in sizes.into_iter().enumerate() {
+		let Some(value) = fits(emulator, number, &bytes[offset..offset + size]) else {
+			return false;
+		};
+		values.push(value);
+		offset += size;
+	}
+
+	for (number, value) in values.into_iter().enumerate() {
+		set_register(emulator, number, value);
+	}
+	true
+}
+
+// m packet, e.g. "200,10" - reads stop early at the end of RAM
+fn read_memory(emulator: &Emulator, arguments: &str) -> Option<String> {
+	let (address, length): (&str, &str) = arguments.split_once(',')?;
+	let address: u16 = parse_address(address)?;
+	let length: usize = usize::from_str_radix(length, 16).ok()?.min(PACKET_SIZE / 2);
+
+	let bytes: Vec<u8> = (0..length)
+		.map_while(|offset| u16::try_from(address as usize + offset).ok().and_then(|address| emulator.peek(address)))
+		.collect();
+	if bytes.is_empty() && length > 0 {
+		return None;
+	}
+
+	Some(encode_hex(&bytes))
+}
+
+// M packet, e.g. "200,2:00e0"
+fn write_memory(emulator: &mut Emulator, arguments: &str) -> bool {
+	let Some((range, data)) = arguments.split_once(':') else {
+		return false;
+	};
+	let Some((address, length)) = range.split_once(',') else {
+		return false;
+	};
+	let (Some(address), Some(length), Some(bytes)) = (parse_address(address), usize::from_str_radix(length, 16).ok(), decode_hex(data)) else {
+		return false;
+	};
+	if bytes.len() != length || address as usize + length > emulator.get_ram().len() {
+		return false;
+	}
+
+	for (offset, &byte) in bytes.iter().enumerate() {
+		emulator.poke(address + offset as u16, byte);
+	}
+
+	true
+}
+
+// q packets - what the server supports, and the target description
+fn query(arguments: &str) -> String {
+	if arguments.starts_with("Supported") {
+		return format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+", PACKET_SIZE);
+	}
+	if let Some(request) = arguments.strip_prefix("Xfer:features:read:target.xml:") {
+		return read_chunk(&target_description(), request).unwrap_or_else(error_reply);
+	}
+
+	match arguments {
+		// attached to a program that was already running, so it's left alone on quit
+		"Attached" => String::from("1"),
+		"C" => String::from("QC1"),
+		"fThreadInfo" => String::from("m1"),
+		"sThreadInfo" => String::from("l"),
+		"Symbol::" => String::from("OK"),
+		_ => String::new(),
+	}
+}
+
+// part of a qXfer document, e.g. "0,ffb" - starts with m if there's more to come
+fn read_chunk(document: &str, request: &str) -> Option<String> {
+	let (offset, length): (&str, &str) = request.split_once(',')?;
+	let offset: usize = usize::from_str_radix(offset, 16).ok()?;
+	let length: usize = usize::from_str_radix(length, 16).ok()?;
+
+	let start: usize = offset.min(document.len());
+	let end: usize = offset.saturating_add(length).min(document.len());
+	let marker: char = if end < document.len() {'m'} else {'l'};
+
+	Some(format!("{}{}", marker, &document[start..end]))
+}
+
+// names and sizes of the registers, in the order g uses
+fn target_description() -> String {
+	let mut registers: String = String::new();
+	for index in 0..NO_OF_REGISTERS {
+		registers.push_str(&format!("<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\"/>", index));
+	}
+	registers.push_str("<reg name=\"i\" bitsize=\"16\" type=\"uint16\"/>");
+	registers.push_str("<reg name=\"pc\" bitsize=\"16\" type=\"uint16\"/>");
+	registers.push_str("<reg name=\"sp\" bitsize=\"8\" type=\"uint8\"/>");
+	registers.push_str("<reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>");
+	registers.push_str("<reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>");
+
+	format!(
+		"<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\"><target version=\"1.0\"><feature name=\"org.chip8.core\">{}</feature></target>",
+		registers,
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::thread;
+	use std::time::Duration;
+
+	use crate::Profile;
+
+	// V0 = 5, then adds 1 to it forever
+	const LOOP_ROM: [u8; 6] = [
+		0x60, 0x05, // 0x200: V0 = 5
+		0x70, 0x01, // 0x202: V0 += 1
+		0x12, 0x02, // 0x204: jump 0x202
+	];
+
+	// gdb's end of the connection, with whatever the server has sent that
+	// hasn't been looked at yet
+	struct Client {
+		stream: TcpStream,
+		received: Vec<u8>,
+	}
+
+	impl Client {
+		fn send_raw(&mut self, bytes: &[u8]) {
+			self.stream.write_all(bytes).unwrap();
+		}
+
+		fn send(&mut self, data: &str) {
+			self.send_raw(format!("${}#{:02x}", data, checksum(data.as_bytes())).as_bytes());
+		}
+
+		// one update, then anything it sent
+		fn pump(&mut self, server: &mut GdbServer, emulator: &mut Emulator) {
+			server.update(emulator, 100).unwrap();
+			thread::sleep(Duration::from_millis(1));
+
+			let mut buffer: [u8; 1024] = [0; 1024];
+			loop {
+				match self.stream.read(&mut buffer) {
+					Ok(0) => break,
+					Ok(length) => self.received.extend_from_slice(&buffer[..length]),
+					Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+					Err(error) => panic!("{}", error),
+				}
+			}
+		}
+
+		// everything received up to the end of the next whole packet
+		fn wait_for_packet(&mut self, server: &mut GdbServer, emulator: &mut Emulator) -> String {
+			for _ in 0..1000 {
+				if let Some(end) = self.received.iter().position(|&byte| byte == b'#') {
+					if self.received.len() >= end + 3 {
+						let text: Vec<u8> = self.received.drain(..end + 3).collect();
+						return String::from_utf8(text).unwrap();
+					}
+				}
+				self.pump(server, emulator);
+			}
+			panic!("no reply, only {:?}", String::from_utf8_lossy(&self.received));
+		}
+
+		// send a packet and return what's in the reply, after checking it was
+		// acknowledged and the reply's checksum
+		fn command(&mut self, server: &mut GdbServer, emulator: &mut Emulator, data: &str) -> String {
+			self.send(data);
+			let reply: String = self.wait_for_packet(server, emulator);
+
+			let packet: &str = reply.strip_prefix("+$").unwrap_or_else(|| panic!("bad reply {:?} to {:?}", reply, data));
+			let (data, sum): (&str, &str) = packet.split_once('#').unwrap();
+			assert_eq!(u8::from_str_radix(sum, 16).unwrap(), checksum(data.as_bytes()), "{}", reply);
+			String::from(data)
+		}
+	}
+
+	// a server with gdb connected to it, and the game stopped at the start of LOOP_ROM
+	fn connect() -> (GdbServer, Emulator, Client) {
+		let mut server: GdbServer = GdbServer::bind(0).unwrap();
+		let mut emulator: Emulator = Emulator::with_seed(Profile::default(), 0).unwrap();
+		emulator.load_rom(&LOOP_ROM).unwrap();
+
+		let stream: TcpStream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+		stream.set_nonblocking(true).unwrap();
+		let mut client: Client = Client { stream, received: Vec::new() };
+		for _ in 0..100 {
+			if server.is_connected() {
+				break;
+			}
+			client.pump(&mut server, &mut emulator);
+		}
+		assert!(server.is_stopped());
+
+		(server, emulator, client)
+	}
+
+	#[test]
+	fn packets_are_checked_and_acknowledged() {
+		assert_eq!(checksum(b"OK"), 0x9a);
+
+		let (mut server, mut emulator, mut client) = connect();
+
+		// a bad checksum is refused and not answered
+		client.send_raw(b"$m200,2#00");
+		for _ in 0..10 {
+			client.pump(&mut server, &mut emulator);
+		}
+		assert_eq!(client.received, b"-");
+		client.received.clear();
+
+		// a packet split across reads waits for its checksum
+		client.send_raw(b"$m200,2#");
+		for _ in 0..10 {
+			client.pump(&mut server, &mut emulator);
+		}
+		assert!(client.received.is_empty());
+		client.send_raw(format!("{:02x}", checksum(b"m200,2")).as_bytes());
+		assert_eq!(client.wait_for_packet(&mut server, &mut emulator), format!("+$6005#{:02x}", checksum(b"6005")));
+
+		// unsupported packets get an empty reply
+		assert_eq!(client.command(&mut server, &mut emulator, "X300,1:a"), "");
+
+		// nothing is acknowledged once gdb asks not to be
+		assert_eq!(client.command(&mut server, &mut emulator, "QStartNoAckMode"), "OK");
+		client.send("m202,2");
+		assert_eq!(client.wait_for_packet(&mut server, &mut emulator), format!("$7001#{:02x}", checksum(b"7001")));
+	}
+
+	#[test]
+	fn registers_are_read_and_written() {
+		let (mut server, mut emulator, mut client) = connect();
+		emulator.set_v_register(0xF, 0x12);
+		emulator.set_i_register(0x0345);
+
+		// V0-VF, then I and PC little endian, then SP, DT and ST
+		let registers: String = client.command(&mut server, &mut emulator, "g");
+		assert_eq!(registers, format!("{}12{}", "00".repeat(15), "45030002000000"));
+		assert_eq!(client.command(&mut server, &mut emulator, "p11"), "0002");
+		assert_eq!(client.command(&mut server, &mut emulator, "p15"), "E01");
+
+		// a bad stack pointer means none of the registers change
+		let bad_stack_pointer: String = format!("aa{}ff0000", &registers[2..40]);
+		assert_eq!(client.command(&mut server, &mut emulator, &format!("G{}", bad_stack_pointer)), "E01");
+		assert_eq!(client.command(&mut server, &mut emulator, "g"), registers);
+		assert_eq!(client.command(&mut server, &mut emulator, &format!("G{}", &registers[2..])), "E01");
+
+		let changed: String = format!("aa{}0402010203", &registers[2..36]);
+		assert_eq!(client.command(&mut server, &mut emulator, &format!("G{}", changed)), "OK");
+		assert_eq!(client.command(&mut server, &mut emulator, "g"), changed);
+		assert_eq!(emulator.get_v_registers()[0], 0xAA);
+		assert_eq!(emulator.get_pc(), 0x0204);
+		assert_eq!(emulator.get_stack_pointer(), 1);
+
+		assert_eq!(client.command(&mut server, &mut emulator, "P10=0006"), "OK");
+		assert_eq!(emulator.get_i_register(), 0x0600);
+		assert_eq!(client.command(&mut server, &mut emulator, "P12=ff"), "E01");
+		assert_eq!(client.command(&mut server, &mut emulator, "P10=06"), "E01");
+	}
+
+	#[test]
+	fn memory_is_read_and_written() {
+		let (mut server, mut emulator, mut client) = connect();
+
+		assert_eq!(client.command(&mut server, &mut emulator, "m200,6"), "600570011202");
+		assert_eq!(client.command(&mut server, &mut emulator, "M300,3:abcdef"), "OK");
+		assert_eq!(client.command(&mut server, &mut emulator, "m300,3"), "abcdef");
+		assert_eq!(emulator.peek(0x302), Some(0xEF));
+
+		// reads stop at the end of RAM, and writes can't go past it
+		assert_eq!(emulator.get_ram().len(), 0x10000);
+		assert_eq!(client.command(&mut server, &mut emulator, "mfffe,4"), "0000");
+		assert_eq!(client.command(&mut server, &mut emulator, "mffff,0"), "");
+		assert_eq!(client.command(&mut server, &mut emulator, "Mffff,2:1234"), "E01");
+		assert_eq!(emulator.peek(0xFFFF), Some(0));
+
+		// the length has to match the data
+		assert_eq!(client.command(&mut server, &mut emulator, "M300,2:12"), "E01");
+		assert_eq!(client.command(&mut server, &mut emulator, "M300,1:xy"), "E01");
+		assert_eq!(client.command(&mut server, &mut emulator, "m300"), "E01");
+	}
+
+	#[test]
+	fn breakpoints_and_steps_stop_the_game() {
+		let (mut server, mut emulator, mut client) = connect();
+
+		assert_eq!(client.command(&mut server, &mut emulator, "Z0,204,2"), "OK");
+		assert_eq!(client.command(&mut server, &mut emulator, "c"), "S05");
+		assert!(server.is_stopped());
+		assert_eq!(emulator.get_pc(), 0x204);
+		assert_eq!(emulator.get_v_registers()[0], 6);
+
+		assert_eq!(client.command(&mut server, &mut emulator, "s"), "S05");
+		assert_eq!(emulator.get_pc(), 0x202);
+		assert_eq!(client.command(&mut server, &mut emulator, "?"), "S05");
+
+		// running on carries on from the breakpoint, and stops there next time round
+		assert_eq!(client.command(&mut server, &mut emulator, "c"), "S05");
+		assert_eq!(emulator.get_pc(), 0x204);
+		assert_eq!(emulator.get_v_registers()[0], 7);
+
+		// continue from a new address
+		assert_eq!(client.command(&mut server, &mut emulator, "s200"), "S05");
+		assert_eq!(emulator.get_pc(), 0x202);
+		assert_eq!(emulator.get_v_registers()[0], 5);
+
+		// with the breakpoint gone only ctrl-c stops the game
+		assert_eq!(client.command(&mut server, &mut emulator, "z0,204,2"), "OK");
+		client.send("c");
+		for _ in 0..10 {
+			client.pump(&mut server, &mut emulator);
+		}
+		assert!(!server.is_stopped());
+		assert_eq!(client.received, b"+");
+		client.received.clear();
+
+		client.send_raw(&[INTERRUPT]);
+		assert_eq!(client.wait_for_packet(&mut server, &mut emulator), format!("$S02#{:02x}", checksum(b"S02")));
+		assert!(server.is_stopped());
+	}
+}
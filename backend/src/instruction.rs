@@ -0,0 +1,379 @@
+// crates
+use std::fmt;
+
+// one decoded opcode - x and y are register numbers, n is the low nibble,
+// byte is the low byte and address is the low 12 bits
+// opcodes from http://devernay.free.fr/hacks/chip8/C8TECH10.HTM plus the
+// SUPER-CHIP and XO-CHIP extensions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+	// 0000 - do nothing
+	Nop,
+	// 00CN - scroll down N pixels (SUPER-CHIP)
+	ScrollDown { n: u8 },
+	// 00DN - scroll up N pixels (XO-CHIP)
+	ScrollUp { n: u8 },
+	// 00E0 - clear the screen
+	ClearScreen,
+	// 00EE - return from subroutine
+	Return,
+	// 00FB - scroll right 4 pixels (SUPER-CHIP)
+	ScrollRight,
+	// 00FC - scroll left 4 pixels (SUPER-CHIP)
+	ScrollLeft,
+	// 00FD - stop the interpreter (SUPER-CHIP)
+	Exit,
+	// 00FE - 64x32 mode (SUPER-CHIP)
+	LowRes,
+	// 00FF - 128x64 mode (SUPER-CHIP)
+	HighRes,
+	// 1NNN - jump to NNN
+	Jump { address: u16 },
+	// 2NNN - call subroutine at NNN
+	Call { address: u16 },
+	// 3XNN - skip if VX == NN
+	SkipEqualByte { x: u8, byte: u8 },
+	// 4XNN - skip if VX != NN
+	SkipNotEqualByte { x: u8, byte: u8 },
+	// 5XY0 - skip if VX == VY
+	SkipEqualRegister { x: u8, y: u8 },
+	// 5XY2 - store VX to VY at I (XO-CHIP)
+	SaveRange { x: u8, y: u8 },
+	// 5XY3 - load VX to VY from I (XO-CHIP)
+	LoadRange { x: u8, y: u8 },
+	// 6XNN - VX = NN
+	LoadByte { x: u8, byte: u8 },
+	// 7XNN - VX += NN
+	AddByte { x: u8, byte: u8 },
+	// 8XY0 - VX = VY
+	Move { x: u8, y: u8 },
+	// 8XY1 - VX |= VY
+	Or { x: u8, y: u8 },
+	// 8XY2 - VX &= VY
+	And { x: u8, y: u8 },
+	// 8XY3 - VX ^= VY
+	Xor { x: u8, y: u8 },
+	// 8XY4 - VX += VY, VF = carry
+	Add { x: u8, y: u8 },
+	// 8XY5 - VX -= VY, VF = not borrow
+	Sub { x: u8, y: u8 },
+	// 8XY6 - VX >>= 1, VF = bit shifted out
+	ShiftRight { x: u8, y: u8 },
+	// 8XY7 - VX = VY - VX, VF = not borrow
+	SubReverse { x: u8, y: u8 },
+	// 8XYE - VX <<= 1, VF = bit shifted out
+	ShiftLeft { x: u8, y: u8 },
+	// 9XY0 - skip if VX != VY
+	SkipNotEqualRegister { x: u8, y: u8 },
+	// ANNN - I = NNN
+	LoadI { address: u16 },
+	// BNNN - jump to NNN + V0
+	JumpOffset { address: u16 },
+	// CXNN - VX = random & NN
+	Random { x: u8, byte: u8 },
+	// DXYN - draw N rows of sprite at (VX, VY), 16x16 if N is 0
+	Draw { x: u8, y: u8, n: u8 },
+	// EX9E - skip if key VX is pressed
+	SkipKeyPressed { x: u8 },
+	// EXA1 - skip if key VX isn't pressed
+	SkipKeyNotPressed { x: u8 },
+	// F000 NNNN - I = the next 2 bytes (XO-CHIP)
+	LoadILong,
+	// FN01 - draw on bitplanes N (XO-CHIP)
+	SelectPlanes { planes: u8 },
+	// F002 - load 16 bytes at I into the audio pattern (XO-CHIP)
+	LoadAudioPattern,
+	// FX07 - VX = delay timer
+	LoadDelay { x: u8 },
+	// FX0A - wait for a key and put it in VX
+	WaitKey { x: u8 },
+	// FX15 - delay timer = VX
+	SetDelay { x: u8 },
+	// FX18 - sound timer = VX
+	SetSound { x: u8 },
+	// FX1E - I += VX
+	AddI { x: u8 },
+	// FX29 - I = small font character VX
+	LoadFont { x: u8 },
+	// FX30 - I = big font character VX (SUPER-CHIP)
+	LoadBigFont { x: u8 },
+	// FX33 - store BCD of VX at I
+	StoreBcd { x: u8 },
+	// FX3A - pitch = VX (XO-CHIP)
+	SetPitch { x: u8 },
+	// FX55 - store V0 to VX at I
+	StoreRegisters { x: u8 },
+	// FX65 - load V0 to VX from I
+	LoadRegisters { x: u8 },
+	// FX75 - store V0 to VX in the RPL flags (SUPER-CHIP)
+	StoreFlags { x: u8 },
+	// FX85 - load V0 to VX from the RPL flags (SUPER-CHIP)
+	LoadFlags { x: u8 },
+}
+
+impl Instruction {
+	// work out which instruction an opcode is, or None if it isn't one
+	pub fn decode(opcode: u16) -> Option<Instruction> {
+		// separate each hex "digit" for pattern matching
+		// bitwise AND and right-shift for this
+		let digit_1: u8 = ((opcode & 0xF000) >> 12) as u8;
+		let digit_2: u8 = ((opcode & 0x0F00) >> 8) as u8;
+		let digit_3: u8 = ((opcode & 0x00F0) >> 4) as u8;
+		let digit_4: u8 = (opcode & 0x000F) as u8;
+
+		// operands - not every instruction uses every one
+		let x: u8 = digit_2;
+		let y: u8 = digit_3;
+		let n: u8 = digit_4;
+		let byte: u8 = (opcode & 0xFF) as u8;
+		let address: u16 = opcode & 0xFFF;
+
+		let instruction: Instruction = match (digit_1, digit_2, digit_3, digit_4) {
+			(0, 0, 0, 0) => Instruction::Nop,
+			(0, 0, 0xC, _) => Instruction::ScrollDown { n },
+			(0, 0, 0xD, _) => Instruction::ScrollUp { n },
+			(0, 0, 0xE, 0) => Instruction::ClearScreen,
+			(0, 0, 0xE, 0xE) => Instruction::Return,
+			(0, 0, 0xF, 0xB) => Instruction::ScrollRight,
+			(0, 0, 0xF, 0xC) => Instruction::ScrollLeft,
+			(0, 0, 0xF, 0xD) => Instruction::Exit,
+			(0, 0, 0xF, 0xE) => Instruction::LowRes,
+			(0, 0, 0xF, 0xF) => Instruction::HighRes,
+			(1, _, _, _) => Instruction::Jump { address },
+			(2, _, _, _) => Instruction::Call { address },
+			(3, _, _, _) => Instruction::SkipEqualByte { x, byte },
+			(4, _, _, _) => Instruction::SkipNotEqualByte { x, byte },
+			(5, _, _, 0) => Instruction::SkipEqualRegister { x, y },
+			(5, _, _, 2) => Instruction::SaveRange { x, y },
+			(5, _, _, 3) => Instruction::LoadRange { x, y },
+			(6, _, _, _) => Instruction::LoadByte { x, byte },
+			(7, _, _, _) => Instruction::AddByte { x, byte },
+			(8, _, _, 0) => Instruction::Move { x, y },
+			(8, _, _, 1) => Instruction::Or { x, y },
+			(8, _, _, 2) => Instruction::And { x, y },
+			(8, _, _, 3) => Instruction::Xor { x, y },
+			(8, _, _, 4) => Instruction::Add { x, y },
+			(8, _, _, 5) => Instruction::Sub { x, y },
+			(8, _, _, 6) => Instruction::ShiftRight { x, y },
+			(8, _, _, 7) => Instruction::SubReverse { x, y },
+			(8, _, _, 0xE) => Instruction::ShiftLeft { x, y },
+			(9, _, _, 0) => Instruction::SkipNotEqualRegister { x, y },
+			(0xA, _, _, _) => Instruction::LoadI { address },
+			(0xB, _, _, _) => Instruction::JumpOffset { address },
+			(0xC, _, _, _) => Instruction::Random { x, byte },
+			(0xD, _, _, _) => Instruction::Draw { x, y, n },
+			(0xE, _, 9, 0xE) => Instruction::SkipKeyPressed { x },
+			(0xE, _, 0xA, 1) => Instruction::SkipKeyNotPressed { x },
+			(0xF, 0, 0, 0) => Instruction::LoadILong,
+			(0xF, _, 0, 1) => Instruction::SelectPlanes { planes: x },
+			(0xF, 0, 0, 2) => Instruction::LoadAudioPattern,
+			(0xF, _, 0, 7) => Instruction::LoadDelay { x },
+			(0xF, _, 0, 0xA) => Instruction::WaitKey { x },
+			(0xF, _, 1, 5) => Instruction::SetDelay { x },
+			(0xF, _, 1, 8) => Instruction::SetSound { x },
+			(0xF, _, 1, 0xE) => Instruction::AddI { x },
+			(0xF, _, 2, 9) => Instruction::LoadFont { x },
+			(0xF, _, 3, 0) => Instruction::LoadBigFont { x },
+			(0xF, _, 3, 3) => Instruction::StoreBcd { x },
+			(0xF, _, 3, 0xA) => Instruction::SetPitch { x },
+			(0xF, _, 5, 5) => Instruction::StoreRegisters { x },
+			(0xF, _, 6, 5) => Instruction::LoadRegisters { x },
+			(0xF, _, 7, 5) => Instruction::StoreFlags { x },
+			(0xF, _, 8, 5) => Instruction::LoadFlags { x },
+			// anything left isn't a real opcode
+			(_, _, _, _) => return None,
+		};
+
+		Some(instruction)
+	}
+
+	// opcode for this instruction - the reverse of decode
+	// LD I, LONG's address goes in the 2 bytes after this
+	pub fn encode(&self) -> u16 {
+		// put register numbers and nibbles back in their "digits"
+		let xy = |opcode: u16, x: u8, y: u8| opcode | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4;
+		let xb = |opcode: u16, x: u8, byte: u8| opcode | (x as u16 & 0xF) << 8 | byte as u16;
+
+		match *self {
+			Instruction::Nop => 0x0000,
+			Instruction::ScrollDown { n } => 0x00C0 | (n as u16 & 0xF),
+			Instruction::ScrollUp { n } => 0x00D0 | (n as u16 & 0xF),
+			Instruction::ClearScreen => 0x00E0,
+			Instruction::Return => 0x00EE,
+			Instruction::ScrollRight => 0x00FB,
+			Instruction::ScrollLeft => 0x00FC,
+			Instruction::Exit => 0x00FD,
+			Instruction::LowRes => 0x00FE,
+			Instruction::HighRes => 0x00FF,
+			Instruction::Jump { address } => 0x1000 | (address & 0xFFF),
+			Instruction::Call { address } => 0x2000 | (address & 0xFFF),
+			Instruction::SkipEqualByte { x, byte } => xb(0x3000, x, byte),
+			Instruction::SkipNotEqualByte { x, byte } => xb(0x4000, x, byte),
+			Instruction::SkipEqualRegister { x, y } => xy(0x5000, x, y),
+			Instruction::SaveRange { x, y } => xy(0x5002, x, y),
+			Instruction::LoadRange { x, y } => xy(0x5003, x, y),
+			Instruction::LoadByte { x, byte } => xb(0x6000, x, byte),
+			Instruction::AddByte { x, byte } => xb(0x7000, x, byte),
+			Instruction::Move { x, y } => xy(0x8000, x, y),
+			Instruction::Or { x, y } => xy(0x8001, x, y),
+			Instruction::And { x, y } => xy(0x8002, x, y),
+			Instruction::Xor { x, y } => xy(0x8003, x, y),
+			Instruction::Add { x, y } => xy(0x8004, x, y),
+			Instruction::Sub { x, y } => xy(0x8005, x, y),
+			Instruction::ShiftRight { x, y } => xy(0x8006, x, y),
+			Instruction::SubReverse { x, y } => xy(0x8007, x, y),
+			Instruction::ShiftLeft { x, y } => xy(0x800E, x, y),
+			Instruction::SkipNotEqualRegister { x, y } => xy(0x9000, x, y),
+			Instruction::LoadI { address } => 0xA000 | (address & 0xFFF),
+			Instruction::JumpOffset { address } => 0xB000 | (address & 0xFFF),
+			Instruction::Random { x, byte } => xb(0xC000, x, byte),
+			Instruction::Draw { x, y, n } => xy(0xD000, x, y) | (n as u16 & 0xF),
+			Instruction::SkipKeyPressed { x } => xy(0xE09E, x, 0),
+			Instruction::SkipKeyNotPressed { x } => xy(0xE0A1, x, 0),
+			Instruction::LoadILong => 0xF000,
+			Instruction::SelectPlanes { planes } => xy(0xF001, planes, 0),
+			Instruction::LoadAudioPattern => 0xF002,
+			Instruction::LoadDelay { x } => xy(0xF007, x, 0),
+			Instruction::WaitKey { x } => xy(0xF00A, x, 0),
+			Instruction::SetDelay { x } => xy(0xF015, x, 0),
+			Instruction::SetSound { x } => xy(0xF018, x, 0),
+			Instruction::AddI { x } => xy(0xF01E, x, 0),
+			Instruction::LoadFont { x } => xy(0xF029, x, 0),
+			Instruction::LoadBigFont { x } => xy(0xF030, x, 0),
+			Instruction::StoreBcd { x } => xy(0xF033, x, 0),
+			Instruction::SetPitch { x } => xy(0xF03A, x, 0),
+			Instruction::StoreRegisters { x } => xy(0xF055, x, 0),
+			Instruction::LoadRegisters { x } => xy(0xF065, x, 0),
+			Instruction::StoreFlags { x } => xy(0xF075, x, 0),
+			Instruction::LoadFlags { x } => xy(0xF085, x, 0),
+		}
+	}
+
+	// bytes taken up in RAM - F000 NNNN is followed by its address
+	pub fn length(&self) -> u16 {
+		match self {
+			Instruction::LoadILong => 4,
+			_ => 2,
+		}
+	}
+}
+
+// standard mnemonics, e.g. "LD V1, 0x2A" or "DRW V0, V1, 5"
+// LD I, LONG is followed by its address in the next 2 bytes, which the
+// disassembler adds on
+impl fmt::Display for Instruction {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Instruction::Nop => write!(f, "NOP"),
+			Instruction::ScrollDown { n } => write!(f, "SCD {}", n),
+			Instruction::ScrollUp { n } => write!(f, "SCU {}", n),
+			Instruction::ClearScreen => write!(f, "CLS"),
+			Instruction::Return => write!(f, "RET"),
+			Instruction::ScrollRight => write!(f, "SCR"),
+			Instruction::ScrollLeft => write!(f, "SCL"),
+			Instruction::Exit => write!(f, "EXIT"),
+			Instruction::LowRes => write!(f, "LOW"),
+			Instruction::HighRes => write!(f, "HIGH"),
+			Instruction::Jump { address } => write!(f, "JP {:#05X}", address),
+			Instruction::Call { address } => write!(f, "CALL {:#05X}", address),
+			Instruction::SkipEqualByte { x, byte } => write!(f, "SE V{:X}, {:#04X}", x, byte),
+			Instruction::SkipNotEqualByte { x, byte } => write!(f, "SNE V{:X}, {:#04X}", x, byte),
+			Instruction::SkipEqualRegister { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+			Instruction::SaveRange { x, y } => write!(f, "SAVE V{:X}, V{:X}", x, y),
+			Instruction::LoadRange { x, y } => write!(f, "LOAD V{:X}, V{:X}", x, y),
+			Instruction::LoadByte { x, byte } => write!(f, "LD V{:X}, {:#04X}", x, byte),
+			Instruction::AddByte { x, byte } => write!(f, "ADD V{:X}, {:#04X}", x, byte),
+			Instruction::Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+			Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+			Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+			Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+			Instruction::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+			Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+			Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+			Instruction::SubReverse { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+			Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+			Instruction::SkipNotEqualRegister { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+			Instruction::LoadI { address } => write!(f, "LD I, {:#05X}", address),
+			Instruction::JumpOffset { address } => write!(f, "JP V0, {:#05X}", address),
+			Instruction::Random { x, byte } => write!(f, "RND V{:X}, {:#04X}", x, byte),
+			Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+			Instruction::SkipKeyPressed { x } => write!(f, "SKP V{:X}", x),
+			Instruction::SkipKeyNotPressed { x } => write!(f, "SKNP V{:X}", x),
+			Instruction::LoadILong => write!(f, "LD I, LONG"),
+			Instruction::SelectPlanes { planes } => write!(f, "PLANE {}", planes),
+			Instruction::LoadAudioPattern => write!(f, "AUDIO"),
+			Instruction::LoadDelay { x } => write!(f, "LD V{:X}, DT", x),
+			Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+			Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+			Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+			Instruction::AddI { x } => write!(f, "ADD I, V{:X}", x),
+			Instruction::LoadFont { x } => write!(f, "LD F, V{:X}", x),
+			Instruction::LoadBigFont { x } => write!(f, "LD HF, V{:X}", x),
+			Instruction::StoreBcd { x } => write!(f, "LD B, V{:X}", x),
+			Instruction::SetPitch { x } => write!(f, "PITCH V{:X}", x),
+			Instruction::StoreRegisters { x } => write!(f, "LD [I], V{:X}", x),
+			Instruction::LoadRegisters { x } => write!(f, "LD V{:X}, [I]", x),
+			Instruction::StoreFlags { x } => write!(f, "LD R, V{:X}", x),
+			Instruction::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn undefined_opcodes_decode_to_none() {
+		for opcode in [0x0123, 0x00E1, 0x5121, 0x5AB7, 0x8ABF, 0x9AB1, 0xE000, 0xE19F, 0xF0FF, 0xF103, 0xF1A5] {
+			assert_eq!(Instruction::decode(opcode), None, "{:#06X}", opcode);
+		}
+	}
+
+	// encode is the reverse of decode for every opcode that decodes
+	#[test]
+	fn decoded_opcodes_encode_back() {
+		for opcode in 0..=u16::MAX {
+			if let Some(instruction) = Instruction::decode(opcode) {
+				assert_eq!(instruction.encode(), opcode, "{}", instruction);
+			}
+		}
+	}
+
+	#[test]
+	fn mnemonics_for_each_family() {
+		let cases: [(u16, &str); 22] = [
+			(0x00C3, "SCD 3"),
+			(0x00E0, "CLS"),
+			(0x00FD, "EXIT"),
+			(0x1ABC, "JP 0xABC"),
+			(0x2204, "CALL 0x204"),
+			(0x3A2F, "SE VA, 0x2F"),
+			(0x5AB0, "SE VA, VB"),
+			(0x5123, "LOAD V1, V2"),
+			(0x6F00, "LD VF, 0x00"),
+			(0x7105, "ADD V1, 0x05"),
+			(0x8127, "SUBN V1, V2"),
+			(0x812E, "SHL V1, V2"),
+			(0x9CD0, "SNE VC, VD"),
+			(0xA123, "LD I, 0x123"),
+			(0xB300, "JP V0, 0x300"),
+			(0xC7FF, "RND V7, 0xFF"),
+			(0xD120, "DRW V1, V2, 0"),
+			(0xE4A1, "SKNP V4"),
+			(0xF000, "LD I, LONG"),
+			(0xF201, "PLANE 2"),
+			(0xF30A, "LD V3, K"),
+			(0xFE65, "LD VE, [I]"),
+		];
+		for (opcode, mnemonic) in cases {
+			assert_eq!(Instruction::decode(opcode).unwrap().to_string(), mnemonic, "{:#06X}", opcode);
+		}
+	}
+
+	#[test]
+	fn only_long_i_takes_four_bytes() {
+		assert_eq!(Instruction::LoadILong.length(), 4);
+		assert_eq!(Instruction::LoadI { address: 0x200 }.length(), 2);
+	}
+}
@@ -2,53 +2,90 @@
 
 // crates
 use rand::random;
+use std::ops::Range;
+
+// modules
+mod audio;
+mod debugger;
+mod disassembler;
+mod error;
+mod font;
+mod gdb;
+mod instruction;
+mod platform;
+mod quirks;
+mod rewind;
+mod rng;
+mod save_state;
+mod tracer;
+
+pub use audio::{AudioSettings, ToneGenerator, Waveform};
+pub use debugger::{AccessKind, Comparison, Condition, Debugger, Register, StopReason, Watchpoint};
+pub use disassembler::{disassemble, DisassembledLine};
+pub use error::{EmulatorError, ProfileError, RomError, StateError};
+pub use font::Font;
+pub use gdb::GdbServer;
+pub use instruction::Instruction;
+pub use platform::{Platform, Profile};
+pub use quirks::Quirks;
+pub use rewind::RewindBuffer;
+pub use rng::{RandomSource, XorShiftRng};
+pub use tracer::{compare_traces, Divergence, Tracer};
+
+use debugger::MemoryAccess;
+use error::Fault;
+use font::{BIG_CHAR_SPRITE_ADDRESS, BIG_CHAR_SPRITE_ARR_SIZE, CHAR_SPRITE_ARR_SIZE, FONT_AREA_SIZE};
 
 // screen size will need to be accessed by frontend
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
+// SUPER-CHIP high resolution mode doubles both dimensions
+pub const HIRES_SCREEN_WIDTH: usize = 128;
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
 
-// start address - ROM loaded in from 512th byte in RAM
+// start address - ROM loaded in from 512th byte in RAM, unless the profile says otherwise
 const START_ADDRESS: u16 = 0x200;
-// 4 KB of RAM
-const RAM_SIZE: usize = 4096;
+// at most 64 KB of RAM - 4 KB for a Chip-8, but XO-CHIP can address all of it
+const RAM_SIZE: usize = 0x10000;
 // 16 registers
 const NO_OF_REGISTERS: usize = 16;
-// 32 bytes of stack - paired in 2 bytes so 16
+// 32 bytes of stack - paired in 2 bytes so 16, unless the profile says otherwise
 const STACK_SIZE: usize = 16;
 // 16 keys for a Chip-8
 const NO_OF_KEYS: usize = 16;
-
-// 5 bytes per character, 16 characters, 5 * 16 = 80
-const CHAR_SPRITE_ARR_SIZE: usize = 80;
-// array of each character's font display values in hex
-const CHAR_SPRITE_ARR: [u8; CHAR_SPRITE_ARR_SIZE] = [
-	0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-	0x20, 0x60, 0x20, 0x20, 0x70, // 1
-	0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-	0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-	0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-	0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-	0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-	0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-	0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-	0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-	0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-	0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-	0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-	0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-	0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-	0xF0, 0x80, 0xF0, 0x80, 0x80 // F
+// XO-CHIP has 2 bitplanes, so each pixel is one of 4 colours
+pub const NO_OF_BITPLANES: usize = 2;
+// colour for each combination of XO-CHIP bitplanes - off, plane 1, plane 2, both
+pub type Palette = [(u8, u8, u8); 1 << NO_OF_BITPLANES];
+// black and white, with greys for the other planes - what every frontend shows by default
+pub const MONO_PALETTE: Palette = [
+	(0, 0, 0),
+	(255, 255, 255),
+	(170, 170, 170),
+	(85, 85, 85),
 ];
+// XO-CHIP audio pattern buffer - 16 bytes, 128 1-bit samples
+const AUDIO_PATTERN_SIZE: usize = 16;
+// XO-CHIP pitch register starts at 64, which plays the pattern at 4000 Hz
+const DEFAULT_PITCH: u8 = 64;
+// HP48 RPL user flags - SUPER-CHIP only allows V0-V7, but keep room for V0-VF
+const NO_OF_RPL_FLAGS: usize = 16;
 
 // class to manage emulator (main object - handles running the game + passes information back and forth from frontend)
 #[allow(dead_code)]
 pub struct Emulator {
 	// program counter - keeps track of the index of the current instruction
 	pc: u16,
-	// Random Access Memory
-	ram: [u8; RAM_SIZE],
-	// display is monochromatic so can use a 1-bit display, so we're using bool
-	screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+	// Random Access Memory - sized by the profile
+	ram: Vec<u8>,
+	// each pixel is a bitmask of which XO-CHIP bitplanes are lit (bit 0 is
+	// plane 1), so a plain Chip-8 game only ever uses 0 and 1
+	// sized for hi-res mode - in lo-res mode only the first 64 * 32 are used
+	screen: [u8; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+	// SUPER-CHIP 128x64 mode - switched on and off with 00FF and 00FE
+	hires: bool,
+	// XO-CHIP bitplanes affected by drawing, clearing and scrolling (FN01)
+	selected_planes: u8,
 	
 	// V registers - V0 through VF
 	v_registers: [u8; NO_OF_REGISTERS],
@@ -59,7 +96,8 @@ pub struct Emulator {
 	// actual stack from the std lib as WebAssembly doesn't fully support std)
 	stack_pointer: u16,
 	// stack - LIFO, not general purpose, used when entering/exiting subroutine
-	stack: [u16; STACK_SIZE],
+	// sized by the profile
+	stack: Vec<u16>,
 	
 	// keys/buttons of the chip-8 emulator
 	keys: [bool; NO_OF_KEYS],
@@ -68,252 +106,739 @@ pub struct Emulator {
 	delay_timer: u8,
 	// sound timer - counts down every frame (-1) and emits sound at 0
 	sound_timer: u8,
+	// XO-CHIP 1-bit sample pattern played while the sound timer is active (F002)
+	audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+	// whether F002 has been run, so the pattern plays instead of a plain beep
+	audio_pattern_loaded: bool,
+	// XO-CHIP playback rate of the audio pattern (FX3A)
+	pitch: u8,
+
+	// HP48 RPL user flags - saved and loaded with FX75 and FX85
+	rpl_flags: [u8; NO_OF_RPL_FLAGS],
+	// set by the SUPER-CHIP exit opcode - no more instructions are run
+	halted: bool,
+
+	// machine being emulated - quirks, memory sizes and font
+	profile: Profile,
+	// set after drawing with the display wait quirk - no more instructions
+	// are run until the next frame
+	waiting_for_vblank: bool,
+	// random numbers for CXNN
+	rng: Box<dyn RandomSource>,
+
+	// RAM read and written by the last instruction, for debugger watchpoints
+	// only recorded while watching_memory is set, as it costs time every instruction
+	memory_accesses: Vec<MemoryAccess>,
+	watching_memory: bool,
+	// writes a line for every instruction run, if there is one
+	tracer: Option<Tracer>,
+}
+
+// Default just calls new with the default profile, which is always valid
+impl Default for Emulator {
+	fn default() -> Self {
+		Self::new(Profile::default()).expect("default profile is valid")
+	}
 }
 
 impl Emulator {
-	// new constructor for Emulator class    
-	pub fn new() -> Self {
+	// new constructor for Emulator class - pass a Platform's profile to
+	// emulate that machine, e.g. Emulator::new(Platform::XoChip.profile())
+	// random numbers are different every run
+	// fails if the profile doesn't describe a machine that could exist - see Profile::validate
+	pub fn new(profile: Profile) -> Result<Self, ProfileError> {
+		Self::with_seed(profile, random())
+	}
+
+	// emulator whose random numbers come from a seed - two emulators with the
+	// same seed and the same key presses end up in exactly the same state
+	pub fn with_seed(profile: Profile, seed: u64) -> Result<Self, ProfileError> {
+		Self::with_rng(profile, Box::new(XorShiftRng::new(seed)))
+	}
+
+	// emulator that uses any random number generator for CXNN
+	pub fn with_rng(profile: Profile, rng: Box<dyn RandomSource>) -> Result<Self, ProfileError> {
+		profile.validate()?;
+
 		let mut new_emulator = Self {
-			pc: START_ADDRESS,
-			ram: [0; RAM_SIZE],
-			screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+			pc: profile.start_address,
+			ram: vec![0; profile.ram_size],
+			screen: [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+			hires: false,
+			selected_planes: 1,
 
 			v_registers: [0; NO_OF_REGISTERS],
 			i_register: 0,
 			
 			stack_pointer: 0,
-			stack: [0; STACK_SIZE],
+			stack: vec![0; profile.stack_size],
 			
 			keys: [false; NO_OF_KEYS],
 			
 			delay_timer: 0,
 			sound_timer: 0,
+			audio_pattern: [0; AUDIO_PATTERN_SIZE],
+			audio_pattern_loaded: false,
+			pitch: DEFAULT_PITCH,
+
+			rpl_flags: [0; NO_OF_RPL_FLAGS],
+			halted: false,
+
+			profile,
+			waiting_for_vblank: false,
+			rng,
+
+			memory_accesses: Vec::new(),
+			watching_memory: false,
+			tracer: None,
 		};
 
-		new_emulator.ram[..CHAR_SPRITE_ARR_SIZE].copy_from_slice(&CHAR_SPRITE_ARR);
+		new_emulator.load_fonts();
 
-		new_emulator
+		Ok(new_emulator)
 	}
 
 	// reset emulator without having to create a new object
 	pub fn reset(&mut self) {
-		self.pc = START_ADDRESS;
-		self.ram = [0; RAM_SIZE];
-		self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+		self.pc = self.profile.start_address;
+		self.ram.fill(0);
+		self.screen = [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+		self.hires = false;
+		self.selected_planes = 1;
 		
 		self.v_registers = [0; NO_OF_REGISTERS];
 		self.i_register = 0;
 		
 		self.stack_pointer = 0;
-		self.stack = [0; STACK_SIZE];
+		self.stack.fill(0);
 		
 		self.keys = [false; NO_OF_KEYS];
 		
 		self.delay_timer = 0;
 		self.sound_timer = 0;
+		self.audio_pattern = [0; AUDIO_PATTERN_SIZE];
+		self.audio_pattern_loaded = false;
+		self.pitch = DEFAULT_PITCH;
+
+		// RPL flags are kept - on the HP48 they survive between programs
+		self.halted = false;
+
+		// profile is kept - it belongs to the interpreter, not the game
+		self.waiting_for_vblank = false;
+		// so is the tracer, which carries on counting cycles
 		
-		self.ram[..CHAR_SPRITE_ARR_SIZE].copy_from_slice(&CHAR_SPRITE_ARR);
+		self.load_fonts();
 	}
 
 	// tick - 1 F-D-E cycle
-	pub fn tick(&mut self) {
+	// if the instruction can't be run, the PC is left pointing at it and the
+	// error says what went wrong
+	pub fn tick(&mut self) -> Result<(), EmulatorError> {
+		self.memory_accesses.clear();
+
+		// exit opcode has been run so nothing left to do, or the last
+		// sprite drawn is waiting for the display to refresh
+		if self.halted || self.waiting_for_vblank {
+			return Ok(());
+		}
+
 		// fetch
-		let opcode: u16 = self.fetch();
+		let instruction_pc: u16 = self.pc;
+		let opcode: u16 = self.fetch().map_err(|_| EmulatorError::PcOutOfBounds { pc: instruction_pc })?;
+
+		// traced before it runs, so the line shows what the instruction started with
+		if let Some(mut tracer) = self.tracer.take() {
+			tracer.trace(self, instruction_pc, opcode);
+			self.tracer = Some(tracer);
+		}
 
 		// decode & execute
-		self.execute_opcode(opcode);
+		self.execute_opcode(opcode).map_err(|fault| {
+			self.pc = instruction_pc;
+			fault.at(instruction_pc, opcode)
+		})
 	}
 
 	// modify timers every frame
 	pub fn tick_timers(&mut self) {
+		// a new frame has started, so any sprite that was drawn is now on screen
+		self.waiting_for_vblank = false;
+
 		if self.delay_timer > 0 {
 			self.delay_timer -= 1;
 		}
+		// the frontend beeps while this is above 0 - see is_sound_active
 		if self.sound_timer > 0 {
-			if self.sound_timer == 1 {
-				// TODO: BEEP audio - research on your own
-			}
 			self.sound_timer -= 1;
 		}
 	}
 
-	// passess a pointer to the screen buffer array
-	pub fn get_display(&self) -> &[bool] {
-		&self.screen
+	// passess a pointer to the screen buffer array - only the part used by
+	// the current resolution, so its length is width * height
+	// each value is a colour index made from the lit bitplanes (0 is off)
+	pub fn get_display(&self) -> &[u8] {
+		let (width, height) = self.get_resolution();
+		&self.screen[..width * height]
+	}
+
+	// (width, height) of the current display mode
+	pub fn get_resolution(&self) -> (usize, usize) {
+		if self.hires {
+			(HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT)
+		} else {
+			(SCREEN_WIDTH, SCREEN_HEIGHT)
+		}
+	}
+
+	// machine being emulated
+	pub fn get_profile(&self) -> &Profile {
+		&self.profile
+	}
+
+	// whether the program has run the SUPER-CHIP exit opcode
+	pub fn is_halted(&self) -> bool {
+		self.halted
+	}
+
+	// whether a sprite is waiting for the display to refresh - tick runs
+	// nothing until tick_timers starts the next frame
+	pub fn is_waiting_for_vblank(&self) -> bool {
+		self.waiting_for_vblank
+	}
+
+	// address of the next instruction
+	pub fn get_pc(&self) -> u16 {
+		self.pc
+	}
+
+	// jump somewhere else, e.g. from a debugger
+	pub fn set_pc(&mut self, pc: u16) {
+		self.pc = pc;
+	}
+
+	// V0 through VF
+	pub fn get_v_registers(&self) -> &[u8; NO_OF_REGISTERS] {
+		&self.v_registers
+	}
+
+	// change one of V0 through VF, returning false if index is above F
+	pub fn set_v_register(&mut self, index: usize, value: u8) -> bool {
+		match self.v_registers.get_mut(index) {
+			Some(register) => {
+				*register = value;
+				true
+			},
+			None => false,
+		}
+	}
+
+	pub fn get_i_register(&self) -> u16 {
+		self.i_register
+	}
+
+	pub fn set_i_register(&mut self, value: u16) {
+		self.i_register = value;
+	}
+
+	// every stack slot, including the unused ones above the stack pointer
+	pub fn get_stack(&self) -> &[u16] {
+		&self.stack
+	}
+
+	// change a stack slot, returning false if index is past the profile's stack size
+	pub fn set_stack(&mut self, index: usize, value: u16) -> bool {
+		match self.stack.get_mut(index) {
+			Some(slot) => {
+				*slot = value;
+				true
+			},
+			None => false,
+		}
+	}
+
+	// number of stack slots in use
+	pub fn get_stack_pointer(&self) -> u16 {
+		self.stack_pointer
 	}
 
-	// press_key and release_key function in one
-	pub fn key_press(&mut self, index: usize, pressed: bool) {
-		self.keys[index] = pressed;
+	// returns false, leaving it as it was, if it's past the profile's stack size
+	// - the next CALL or RET would go off the end of the stack
+	pub fn set_stack_pointer(&mut self, stack_pointer: u16) -> bool {
+		if stack_pointer as usize > self.stack.len() {
+			return false;
+		}
+		self.stack_pointer = stack_pointer;
+		true
 	}
 
-	// copy all values from game rom data to RAM starting at 0x200
-	pub fn load_rom(&mut self, data: &[u8]) {
-		let start_address: usize = START_ADDRESS as usize;
-		let end_address = (START_ADDRESS as usize) + data.len();
+	pub fn get_delay_timer(&self) -> u8 {
+		self.delay_timer
+	}
 
-		self.ram[start_address..end_address].copy_from_slice(data);
+	pub fn set_delay_timer(&mut self, value: u8) {
+		self.delay_timer = value;
+	}
+
+	pub fn get_sound_timer(&self) -> u8 {
+		self.sound_timer
+	}
+
+	pub fn set_sound_timer(&mut self, value: u8) {
+		self.sound_timer = value;
+	}
+
+	// which keys are held down - change them with key_press
+	pub fn get_keys(&self) -> &[bool; NO_OF_KEYS] {
+		&self.keys
+	}
+
+	// all of RAM, fonts included - change it with poke
+	pub fn get_ram(&self) -> &[u8] {
+		&self.ram
+	}
+
+	// byte at address, or None if it's past the end of RAM
+	pub fn peek(&self, address: u16) -> Option<u8> {
+		self.ram.get(address as usize).copied()
+	}
+
+	// change the byte at address, returning false if it's past the end of RAM
+	pub fn poke(&mut self, address: u16, value: u8) -> bool {
+		match self.ram.get_mut(address as usize) {
+			Some(byte) => {
+				*byte = value;
+				true
+			},
+			None => false,
+		}
+	}
+
+	// write a line for every instruction from now on - see tracer.rs
+	pub fn set_tracer(&mut self, tracer: Tracer) {
+		self.tracer = Some(tracer);
+	}
+
+	// stop tracing, and get the tracer back, e.g. to flush it or check for errors
+	pub fn take_tracer(&mut self) -> Option<Tracer> {
+		self.tracer.take()
+	}
+
+	// press_key and release_key function in one, returning false if there's
+	// no such key - there are only 16, 0 to F
+	pub fn key_press(&mut self, index: usize, pressed: bool) -> bool {
+		match self.keys.get_mut(index) {
+			Some(key) => {
+				*key = pressed;
+				true
+			},
+			None => false,
+		}
+	}
+
+	// copy all values from game rom data to RAM starting at the profile's start address (usually 0x200)
+	pub fn load_rom(&mut self, data: &[u8]) -> Result<(), RomError> {
+		self.load_rom_at(self.profile.start_address, data)
+	}
+
+	// copy all values from game rom data to RAM starting at any address, and
+	// start running from there - e.g. 0x600 for ETI-660 games
+	pub fn load_rom_at(&mut self, address: u16, data: &[u8]) -> Result<(), RomError> {
+		let start_address: usize = address as usize;
+
+		// the fonts live below the program area
+		if start_address < FONT_AREA_SIZE || start_address >= self.ram.len() {
+			return Err(RomError::InvalidAddress { address });
+		}
+		if data.is_empty() {
+			return Err(RomError::Empty);
+		}
+		let max_size: usize = self.ram.len() - start_address;
+		if data.len() > max_size {
+			return Err(RomError::TooLarge { size: data.len(), max_size });
+		}
+
+		// clear out anything left by a previous ROM, even below this one's
+		// start address, which may have been loaded somewhere else
+		self.ram[FONT_AREA_SIZE..].fill(0);
+		self.ram[start_address..start_address + data.len()].copy_from_slice(data);
+		self.pc = address;
+
+		Ok(())
+	}
+
+	// copy both fonts into the start of RAM (the interpreter area)
+	fn load_fonts(&mut self) {
+		let font: Font = self.profile.font;
+		self.ram[..CHAR_SPRITE_ARR_SIZE].copy_from_slice(font.small());
+		self.ram[BIG_CHAR_SPRITE_ADDRESS..BIG_CHAR_SPRITE_ADDRESS + BIG_CHAR_SPRITE_ARR_SIZE].copy_from_slice(font.big());
+	}
+
+	// switch between lo-res and hi-res - the screen is cleared either way
+	fn set_hires(&mut self, hires: bool) {
+		self.hires = hires;
+		self.screen = [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+	}
+
+	// XOR a sprite from RAM at I onto each selected plane, returning whether any pixel was erased
+	// sprite_width is 8 for normal sprites or 16 for SUPER-CHIP DXY0 sprites
+	fn draw_sprite(&mut self, x_coord: usize, y_coord: usize, sprite_width: usize, sprite_height: usize) -> Result<bool, Fault> {
+		let (width, height) = self.get_resolution();
+		// each row is 1 byte wide, or 2 for 16 pixel wide sprites
+		let bytes_per_row: usize = sprite_width / 8;
+		// with more than one plane selected, each plane's sprite data follows the last
+		let mut address: usize = self.i_register as usize;
+
+		// make sure every plane's sprite is in RAM before drawing any of it
+		let no_of_planes: usize = (self.selected_planes as usize & ((1 << NO_OF_BITPLANES) - 1)).count_ones() as usize;
+		self.read_range(address, no_of_planes * sprite_height * bytes_per_row)?;
+
+		// keep track of flipped pixels
+		let mut flipped_pixel: bool = false;
+
+		for plane in 0..NO_OF_BITPLANES {
+			let plane_mask: u8 = 1 << plane;
+			if self.selected_planes & plane_mask == 0 {
+				continue;
+			}
+
+			// iterate over each row in sprite
+			for each_row in 0..sprite_height {
+				// iterate over each column in the row
+				for each_col in 0..sprite_width {
+					// determine memory address of the byte holding this pixel
+					let pixel: u8 = self.ram[address + each_row * bytes_per_row + each_col / 8];
+
+					// use mask to fetch current pixel's bit
+					// only flip if a 1
+					if (pixel & (0b10000000 >> (each_col % 8))) != 0 {
+						// sprites either get cut off at the edges or wrap
+						// around screen with %
+						if self.profile.quirks.clipping && (x_coord + each_col >= width || y_coord + each_row >= height) {
+							continue;
+						}
+						let x_index: usize = (x_coord + each_col) % width;
+						let y_index: usize = (y_coord + each_row) % height;
+
+						// get pixel's index for 1D screen array
+						let pixel_index: usize = x_index + width * y_index;
+
+						// check to flip pixel and set
+						flipped_pixel |= self.screen[pixel_index] & plane_mask != 0;
+						self.screen[pixel_index] ^= plane_mask;
+					}
+				}
+			}
+
+			address += sprite_height * bytes_per_row;
+		}
+
+		Ok(flipped_pixel)
+	}
+
+	// move the selected planes by (x_offset, y_offset) pixels, filling the gap with blanks
+	fn scroll(&mut self, x_offset: isize, y_offset: isize) {
+		let (width, height) = self.get_resolution();
+		let planes: u8 = self.selected_planes;
+		// copy so pixels aren't overwritten before being moved
+		let old_screen = self.screen;
+
+		for y_index in 0..height {
+			for x_index in 0..width {
+				// where this pixel's new value comes from
+				let source_x: isize = x_index as isize - x_offset;
+				let source_y: isize = y_index as isize - y_offset;
+
+				let moved: u8 = if (0..width as isize).contains(&source_x) && (0..height as isize).contains(&source_y) {
+					old_screen[source_x as usize + width * source_y as usize]
+				} else {
+					0
+				};
+
+				// only the selected planes move, the others stay as they are
+				let pixel_index: usize = x_index + width * y_index;
+				self.screen[pixel_index] = (old_screen[pixel_index] & !planes) | (moved & planes);
+			}
+		}
+	}
+
+	// skip the next instruction, which is 4 bytes long if it's XO-CHIP's F000 NNNN
+	fn skip_instruction(&mut self) {
+		// if the next instruction is outside of RAM, fetching it will fail later
+		let pc: usize = self.pc as usize;
+		let is_long: bool = self.ram.get(pc..pc + 2) == Some(&[0xF0, 0x00]);
+		let length: u16 = if is_long {4} else {2};
+		self.pc = self.pc.wrapping_add(length);
+	}
+
+	// range of RAM covering length bytes from address, or the first address that doesn't exist
+	fn ram_range(&self, address: usize, length: usize) -> Result<Range<usize>, Fault> {
+		if address + length > self.ram.len() {
+			return Err(Fault::MemoryOutOfBounds(address.max(self.ram.len())));
+		}
+
+		Ok(address..address + length)
+	}
+
+	// ram_range for an instruction reading RAM, noted for watchpoints
+	fn read_range(&mut self, address: usize, length: usize) -> Result<Range<usize>, Fault> {
+		let range: Range<usize> = self.ram_range(address, length)?;
+		if self.watching_memory {
+			self.memory_accesses.push(MemoryAccess { range: range.clone(), kind: AccessKind::Read });
+		}
+		Ok(range)
+	}
+
+	// ram_range for an instruction writing RAM, noted for watchpoints
+	fn write_range(&mut self, address: usize, length: usize) -> Result<Range<usize>, Fault> {
+		let range: Range<usize> = self.ram_range(address, length)?;
+		if self.watching_memory {
+			self.memory_accesses.push(MemoryAccess { range: range.clone(), kind: AccessKind::Write });
+		}
+		Ok(range)
 	}
 
 	// pushes a value to the stack and sets pointer to new element
-	fn stack_push(&mut self, value_to_push: u16) {
+	fn stack_push(&mut self, value_to_push: u16) -> Result<(), Fault> {
+		if self.stack_pointer as usize >= self.stack.len() {
+			return Err(Fault::StackOverflow);
+		}
+
 		self.stack[self.stack_pointer as usize] = value_to_push;
 		self.stack_pointer += 1;
+		Ok(())
 	}
 
 	// pops a value from the stack and sets pointer to previous element
-	fn stack_pop(&mut self) -> u16 {
+	fn stack_pop(&mut self) -> Result<u16, Fault> {
+		if self.stack_pointer == 0 {
+			return Err(Fault::StackUnderflow);
+		}
+
 		self.stack_pointer -= 1;
-		self.stack[self.stack_pointer as usize]
+		Ok(self.stack[self.stack_pointer as usize])
+	}
+
+	// whether key number VX is held down
+	fn is_key_pressed(&self, register_vx: u8) -> Result<bool, Fault> {
+		self.keys.get(register_vx as usize).copied().ok_or(Fault::InvalidKey(register_vx))
 	}
 
 	// fetch instruction / opcode we need to format - operands included in opcode for Chip-8
-	fn fetch(&mut self) -> u16 {
+	fn fetch(&mut self) -> Result<u16, Fault> {
+		let range: Range<usize> = self.ram_range(self.pc as usize, 2)?;
+
 		// Big Endian, so most significant bit is stored first
-		let first_byte: u16 = self.ram[self.pc as usize] as u16;
-		let second_byte: u16 = self.ram[(self.pc + 1) as usize] as u16;
+		let first_byte: u16 = self.ram[range.start] as u16;
+		let second_byte: u16 = self.ram[range.start + 1] as u16;
 
 		// left-shift by a byte, and | is the same as +
 		let opcode: u16 = (first_byte << 8) | second_byte;
-		self.pc += 2;
+		self.pc = self.pc.wrapping_add(2);
 
-		opcode
+		Ok(opcode)
 	}
 
 	// decode and execute each opcode / instruction
-	fn execute_opcode(&mut self, opcode: u16) {
-		// separate each hex "digit" for pattern matching
-		// bitwise AND and right-shift for this
-		let digit_1: u16 = (opcode & 0xF000) >> 12;
-		let digit_2: u16 = (opcode & 0x0F00) >> 8;
-		let digit_3: u16 = (opcode & 0x00F0) >> 4;
-		let digit_4: u16 = opcode & 0x000F;
-
-		// match all opcodes - http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
-		match (digit_1, digit_2, digit_3, digit_4) {
+	fn execute_opcode(&mut self, opcode: u16) -> Result<(), Fault> {
+		// anything that doesn't decode isn't a real opcode
+		let instruction: Instruction = Instruction::decode(opcode).ok_or(Fault::UnknownOpcode)?;
+
+		// match all instructions - see instruction.rs for the opcodes
+		match instruction {
 			
 			// NOP
-			(0, 0, 0, 0) => return,
+			Instruction::Nop => {},
+
+			// SCROLL DOWN N (SUPER-CHIP)
+			Instruction::ScrollDown { n } => {
+				self.scroll(0, n as isize);
+			},
+
+			// SCROLL UP N (XO-CHIP)
+			Instruction::ScrollUp { n } => {
+				self.scroll(0, -(n as isize));
+			},
 			
 			// CLS
-			(0, 0, 0xE, 0) => {
-				// reset screen to be empty
-				self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+			Instruction::ClearScreen => {
+				// reset selected planes of the screen to be empty
+				let planes: u8 = self.selected_planes;
+				for pixel in self.screen.iter_mut() {
+					*pixel &= !planes;
+				}
 			},
 
 			// RET
-			(0, 0, 0xE, 0xE) => {
+			Instruction::Return => {
 				// return to previous function so pop from stack
-				let return_address: u16 = self.stack_pop();
+				let return_address: u16 = self.stack_pop()?;
 				self.pc = return_address;
 			},
 
+			// SCROLL RIGHT 4 (SUPER-CHIP)
+			Instruction::ScrollRight => {
+				self.scroll(4, 0);
+			},
+
+			// SCROLL LEFT 4 (SUPER-CHIP)
+			Instruction::ScrollLeft => {
+				self.scroll(-4, 0);
+			},
+
+			// EXIT (SUPER-CHIP)
+			Instruction::Exit => {
+				// stop on this opcode so the frontend can quit
+				self.pc = self.pc.wrapping_sub(2);
+				self.halted = true;
+			},
+
+			// LOW RES (SUPER-CHIP)
+			Instruction::LowRes => {
+				self.set_hires(false);
+			},
+
+			// HIGH RES (SUPER-CHIP)
+			Instruction::HighRes => {
+				self.set_hires(true);
+			},
+
 			// JMP NNN
-			(1, _, _, _) => {
-				let new_address: u16 = opcode & 0xFFF;
+			Instruction::Jump { address: new_address } => {
 				self.pc = new_address;
 			},
 
 			// CALL NNN
-			(2, _, _, _) => {
-				let new_address: u16 = opcode & 0xFFF;
+			Instruction::Call { address: new_address } => {
 
 				// add current pc to stack
-				self.stack_push(self.pc);
+				self.stack_push(self.pc)?;
 				// jump to given address
 				self.pc = new_address;
 			},
 
 			// SKIP VX == NN
-			(3, _, _, _) => {
+			Instruction::SkipEqualByte { x, byte: new_address } => {
 				// Rust requires array indexing to be done with usize
-				let register_no: usize = digit_2 as usize;
-				let new_address: u8 = (opcode & 0xFF) as u8;
+				let register_no: usize = x as usize;
 
 				if self.v_registers[register_no] == new_address {
 					// skip the next opcode
-					self.pc += 2;
+					self.skip_instruction();
 				}
 			},
 
 			// SKIP VX != NN
-			(4, _, _, _) => {
+			Instruction::SkipNotEqualByte { x, byte: new_address } => {
 				// Rust requires array indexing to be done with usize
-				let register_no: usize = digit_2 as usize;
-				let new_address: u8 = (opcode & 0xFF) as u8;
+				let register_no: usize = x as usize;
 
 				if self.v_registers[register_no] != new_address {
 					// skip to next opcode
-					self.pc += 2;
+					self.skip_instruction();
 				}
 			},
 
 			// SKIP VX == VY
-			(5, _, _, 0) => {
+			Instruction::SkipEqualRegister { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let register_y: usize = digit_3 as usize;
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
 
 				if self.v_registers[register_x] == self.v_registers[register_y] {
 					// skip to next opcode
-					self.pc += 2;
+					self.skip_instruction();
+				}
+			},
+
+			// SAVE VX-VY (INCLUSIVE) (XO-CHIP)
+			Instruction::SaveRange { x, y } => {
+				// Rust requires array indexing to be done with usize
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
+				let range: Range<usize> = self.write_range(self.i_register as usize, register_x.abs_diff(register_y) + 1)?;
+
+				// the range can go either way, but is stored in the order given
+				for (offset, register_no) in register_range(register_x, register_y).enumerate() {
+					self.ram[range.start + offset] = self.v_registers[register_no];
+				}
+			},
+
+			// LOAD VX-VY (INCLUSIVE) (XO-CHIP)
+			Instruction::LoadRange { x, y } => {
+				// Rust requires array indexing to be done with usize
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
+				let range: Range<usize> = self.read_range(self.i_register as usize, register_x.abs_diff(register_y) + 1)?;
+
+				for (offset, register_no) in register_range(register_x, register_y).enumerate() {
+					self.v_registers[register_no] = self.ram[range.start + offset];
 				}
 			},
 
 			// VX = NN
-			(6, _, _, _) => {
+			Instruction::LoadByte { x, byte: new_address } => {
 				// Rust requires array indexing to be done with usize
-				let register_no: usize = digit_2 as usize;
-				let new_address: u8 = (opcode & 0xFF) as u8;
+				let register_no: usize = x as usize;
 				self.v_registers[register_no] = new_address;
 			},
 
 			// VX += NN
-			(7, _, _, _) => {
+			Instruction::AddByte { x, byte: new_address } => {
 				// Rust requires array indexing to be done with usize
-				let register_no: usize = digit_2 as usize;
-				let new_address: u8 = (opcode & 0xFF) as u8;
+				let register_no: usize = x as usize;
 				// use wrapping_add incase of overflow
 				self.v_registers[register_no] = self.v_registers[register_no].wrapping_add(new_address);
 			},
 
 			// VX = VY
-			(8, _, _, 0) => {
+			Instruction::Move { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let register_y: usize = digit_3 as usize;
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
 				self.v_registers[register_x] = self.v_registers[register_y];
 			},
 
 			// VX |= VY
-			(8, _, _, 1) => {
+			Instruction::Or { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let register_y: usize = digit_3 as usize;
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
 				self.v_registers[register_x] |= self.v_registers[register_y];
+
+				if self.profile.quirks.vf_reset {
+					self.v_registers[0xF] = 0;
+				}
 			},
 
 			// VX &= VY
-			(8, _, _, 2) => {
+			Instruction::And { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let register_y: usize = digit_3 as usize;
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
 				self.v_registers[register_x] &= self.v_registers[register_y];
+
+				if self.profile.quirks.vf_reset {
+					self.v_registers[0xF] = 0;
+				}
 			},
 
 			// VX ^= VY
-			(8, _, _, 3) => {
+			Instruction::Xor { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let register_y: usize = digit_3 as usize;
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
 				self.v_registers[register_x] ^= self.v_registers[register_y];
+
+				if self.profile.quirks.vf_reset {
+					self.v_registers[0xF] = 0;
+				}
 			},
 
 			// VX += VY
-			(8, _, _, 4) => {
+			Instruction::Add { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let register_y: usize = digit_3 as usize;
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
 				
 				// use overflowing_add to get tuple of addition and bool of
 				// whether carrying a bit or not
@@ -325,10 +850,10 @@ impl Emulator {
 			},
 
 			// VX -= VY
-			(8, _, _, 5) => {
+			Instruction::Sub { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let register_y: usize = digit_3 as usize;
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
 				
 				// use overflowing_add to get tuple of addition and bool of
 				// whether carrying a bit or not
@@ -339,21 +864,23 @@ impl Emulator {
 				self.v_registers[0xF] = new_register_f;
 			},
 
-			// VX >>= 1
-			(8, _, _, 6) => {
+			// VX >>= 1 (or VX = VY >> 1)
+			Instruction::ShiftRight { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let bit_to_drop: u8 = self.v_registers[register_x] & 1;
+				let register_x: usize = x as usize;
+				// original interpreter shifts VY rather than VX
+				let register_source: usize = if self.profile.quirks.shift {register_x} else {y as usize};
+				let bit_to_drop: u8 = self.v_registers[register_source] & 1;
 				
-				self.v_registers[register_x] >>= 1;
+				self.v_registers[register_x] = self.v_registers[register_source] >> 1;
 				self.v_registers[0xF] = bit_to_drop;
 			},
 
 			// VX = VY - VX
-			(8, _, _, 7) => {
+			Instruction::SubReverse { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let register_y: usize = digit_3 as usize;
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
 				
 				// use overflowing_add to get tuple of addition and bool of
 				// whether carrying a bit or not
@@ -364,130 +891,133 @@ impl Emulator {
 				self.v_registers[0xF] = new_register_f;
 			},
 
-			// VX <<= 1
-			(8, _, _, 0xE) => {
+			// VX <<= 1 (or VX = VY << 1)
+			Instruction::ShiftLeft { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let bit_to_drop: u8 = (self.v_registers[register_x] >> 7) & 1;
+				let register_x: usize = x as usize;
+				// original interpreter shifts VY rather than VX
+				let register_source: usize = if self.profile.quirks.shift {register_x} else {y as usize};
+				let bit_to_drop: u8 = (self.v_registers[register_source] >> 7) & 1;
 				
-				self.v_registers[register_x] <<= 1;
+				self.v_registers[register_x] = self.v_registers[register_source] << 1;
 				self.v_registers[0xF] = bit_to_drop;
 			},
 
 			// SKIP VX != VY
-			(9, _, _, 0) => {
+			Instruction::SkipNotEqualRegister { x, y } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let register_y: usize = digit_3 as usize;
+				let register_x: usize = x as usize;
+				let register_y: usize = y as usize;
 
 				if self.v_registers[register_x] != self.v_registers[register_y] {
 					// skip to next opcode
-					self.pc += 2;
+					self.skip_instruction();
 				}
 			},
 
 			// I = NNN
-			(0xA, _, _, _) => {
-				let new_address: u16 = opcode & 0xFFF;
+			Instruction::LoadI { address: new_address } => {
 				self.i_register = new_address;
 			},
 
-			// JMP V0 + NNN
-			(0xB, _, _, _) => {
-				let new_address: u16 = opcode & 0xFFF;
-				self.pc = (self.v_registers[0] as u16) + new_address;
+			// JMP V0 + NNN (or VX + XNN)
+			Instruction::JumpOffset { address: new_address } => {
+				// CHIP-48 misread this as using the register in the first digit
+				let register_no: usize = if self.profile.quirks.jump_with_vx {(new_address >> 8) as usize} else {0};
+				self.pc = (self.v_registers[register_no] as u16) + new_address;
 			},
 
 			// VX = rand() & NN
-			(0xC, _, _, _) => {
+			Instruction::Random { x, byte: new_address } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let new_address: u8 = (opcode & 0xFF) as u8;
+				let register_x: usize = x as usize;
 
-				let random_number: u8 = random();
+				let random_number: u8 = self.rng.next_byte();
 
 				self.v_registers[register_x] = random_number & new_address;
 			},
 
 			// DRAW
-			(0xD, _, _, _) => {
-				// get (x, y) co-ordinates for sprite
-				let x_coord: u16 = self.v_registers[digit_2 as usize] as u16;
-				let y_coord: u16 = self.v_registers[digit_3 as usize] as u16;
-				// last digit is how many pixels tall the sprite is
-				let col_height: u16 = digit_4;
-
-				// keep track of flipped pixels
-				let mut flipped_pixel: bool = false;
-
-				// iterate over each row in sprite
-				for each_row in 0..col_height {
-					// determine memory address of row's stored data
-					let address: u16 = self.i_register + each_row as u16;
-					let pixel: u8 = self.ram[address as usize];
-
-					// iterate over each column in the row
-					for each_col in 0..8 {
-						// use mask to fetch current pixel's bit
-						// only flip if a 1
-						if (pixel & (0b10000000) >> each_col) != 0 {
-							// sprites wrap around screen so %
-							let x_index: usize = (x_coord + each_col) as usize % SCREEN_WIDTH;
-							let y_index: usize = (y_coord + each_row) as usize % SCREEN_HEIGHT;
-
-							// get pixel's index for 1D screen array
-							let pixel_index: usize = x_index + SCREEN_WIDTH * y_index;
-
-							// check to flip pixel and set
-							flipped_pixel |= self.screen[pixel_index];
-							self.screen[pixel_index] ^= true;
-						}
-					}
-				}
+			Instruction::Draw { x, y, n } => {
+				// get (x, y) co-ordinates for sprite, starting point always wraps
+				let (width, height) = self.get_resolution();
+				let x_coord: usize = self.v_registers[x as usize] as usize % width;
+				let y_coord: usize = self.v_registers[y as usize] as usize % height;
+
+				// last digit is how many pixels tall the sprite is, with 0
+				// meaning a 16x16 SUPER-CHIP sprite
+				let flipped_pixel: bool = if n == 0 {
+					self.draw_sprite(x_coord, y_coord, 16, 16)?
+				} else {
+					self.draw_sprite(x_coord, y_coord, 8, n as usize)?
+				};
 
 				// put necessary in VF register
 				self.v_registers[0xF] = if flipped_pixel {1} else {0};
+
+				// stop until the frontend draws this frame
+				if self.profile.quirks.display_wait {
+					self.waiting_for_vblank = true;
+				}
 			},
 
 			// SKIP KEY PRESS
-			(0xE, _, 9, 0xE) => {
+			Instruction::SkipKeyPressed { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
+				let register_x: usize = x as usize;
 				let register_vx: u8 = self.v_registers[register_x];
 				
 				// if index stored in VX is pressed
-				let key: bool = self.keys[register_vx as usize];
+				let key: bool = self.is_key_pressed(register_vx)?;
 				if key {
 					// skip the next opcode
-					self.pc += 2;
+					self.skip_instruction();
 				}
 			},
 
 			// SKIP KEY RELEASE
-			(0xE, _, 0xA, 1) => {
+			Instruction::SkipKeyNotPressed { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
+				let register_x: usize = x as usize;
 				let register_vx: u8 = self.v_registers[register_x];
 				
 				// if index stored in VX is not pressed
-				let key: bool = self.keys[register_vx as usize];
+				let key: bool = self.is_key_pressed(register_vx)?;
 				if !key {
 					// skip the next opcode
-					self.pc += 2;
+					self.skip_instruction();
 				}
 			},
 
+			// I = NNNN (XO-CHIP)
+			Instruction::LoadILong => {
+				// the address is the whole of the next 2 bytes
+				self.i_register = self.fetch()?;
+			},
+
+			// SELECT PLANES N (XO-CHIP)
+			Instruction::SelectPlanes { planes } => {
+				self.selected_planes = planes;
+			},
+
+			// LOAD AUDIO PATTERN (XO-CHIP)
+			Instruction::LoadAudioPattern => {
+				let range: Range<usize> = self.read_range(self.i_register as usize, AUDIO_PATTERN_SIZE)?;
+				self.audio_pattern.copy_from_slice(&self.ram[range]);
+				self.audio_pattern_loaded = true;
+			},
+
 			// VX = DT
-			(0xF, _, 0, 7) => {
+			Instruction::LoadDelay { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
+				let register_x: usize = x as usize;
 				self.v_registers[register_x] = self.delay_timer;
 			},
 		
 			// WAIT KEY
-			(0xF, _, 0, 0xA) => {
+			Instruction::WaitKey { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
+				let register_x: usize = x as usize;
 				let mut pressed: bool = false;
 
 				// go through all keys
@@ -503,7 +1033,7 @@ impl Emulator {
 				// do this until a key is pressed
 				if !pressed {
 					// redo opcode
-					self.pc -= 2;
+					self.pc = self.pc.wrapping_sub(2);
 				}
 
 				// this wasn't implemented in a loop because by being in a
@@ -514,41 +1044,57 @@ impl Emulator {
 			},
 
 			// DT = VX
-			(0xF, _, 1, 5) => {
+			Instruction::SetDelay { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
+				let register_x: usize = x as usize;
 				self.delay_timer = self.v_registers[register_x];
 			},
 
 			// ST = VX
-			(0xF, _, 1, 8) => {
+			Instruction::SetSound { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
+				let register_x: usize = x as usize;
 				self.sound_timer = self.v_registers[register_x];
 			},
 
 			// I += VX
-			(0xF, _, 1, 0xE) => {
+			Instruction::AddI { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
+				let register_x: usize = x as usize;
 				let register_vx: u16 = self.v_registers[register_x] as u16;
 				
 				self.i_register = self.i_register.wrapping_add(register_vx);
 			},
 
 			// I = CHAR SPRITE
-			(0xF, _, 2, 9) => {
+			Instruction::LoadFont { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
+				let register_x: usize = x as usize;
 				let current: u16 = self.v_registers[register_x] as u16;
 
 				self.i_register = current * 5;
 			},
 
+			// I = BIG CHAR SPRITE (SUPER-CHIP)
+			Instruction::LoadBigFont { x } => {
+				// Rust requires array indexing to be done with usize
+				let register_x: usize = x as usize;
+				let current: u16 = self.v_registers[register_x] as u16;
+
+				self.i_register = BIG_CHAR_SPRITE_ADDRESS as u16 + current * 10;
+			},
+
+			// PITCH = VX (XO-CHIP)
+			Instruction::SetPitch { x } => {
+				// Rust requires array indexing to be done with usize
+				let register_x: usize = x as usize;
+				self.pitch = self.v_registers[register_x];
+			},
+
 			// BCD
-			(0xF, _, 3, 3) => {
+			Instruction::StoreBcd { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
+				let register_x: usize = x as usize;
 				let register_vx: f32 = self.v_registers[register_x] as f32;
 
 				// get 100s digit by floor dividing by 100
@@ -560,35 +1106,204 @@ impl Emulator {
 				let ones_digit: u8 = (register_vx % 10.0) as u8;
 
 				// load into RAM at i_register's current location
-				self.ram[self.i_register as usize] = hundreds_digit;
-				self.ram[(self.i_register + 1) as usize] = tens_digit;
-				self.ram[(self.i_register + 2) as usize] = ones_digit;
+				let range: Range<usize> = self.write_range(self.i_register as usize, 3)?;
+				self.ram[range].copy_from_slice(&[hundreds_digit, tens_digit, ones_digit]);
 			},
 
 			// STORE V0-VX (INCLUSIVE)
-			(0xF, _, 5, 5) => {
+			Instruction::StoreRegisters { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let index: usize = self.i_register as usize;
+				let register_x: usize = x as usize;
+				let index: usize = self.write_range(self.i_register as usize, register_x + 1)?.start;
 
 				for ram_location in 0..=register_x {
 					self.ram[index + ram_location] = self.v_registers[ram_location];
 				}
+
+				if self.profile.quirks.load_store_increment {
+					self.i_register = self.i_register.wrapping_add(register_x as u16 + 1);
+				}
 			},
 
 			// LOAD V0-VX (INCLUSIVE)
-			(0xF, _, 6, 5) => {
+			Instruction::LoadRegisters { x } => {
 				// Rust requires array indexing to be done with usize
-				let register_x: usize = digit_2 as usize;
-				let index: usize = self.i_register as usize;
+				let register_x: usize = x as usize;
+				let index: usize = self.read_range(self.i_register as usize, register_x + 1)?.start;
 
 				for ram_location in 0..=register_x {
 					self.v_registers[ram_location] = self.ram[index + ram_location];
 				}
+
+				if self.profile.quirks.load_store_increment {
+					self.i_register = self.i_register.wrapping_add(register_x as u16 + 1);
+				}
 			},
 
-			// _ is a wildcard - won't run into this, but Rust requires it
-			(_, _, _, _) => unimplemented!("{:#04x} opcode unimplemented", opcode),
+			// STORE V0-VX IN RPL FLAGS (SUPER-CHIP)
+			Instruction::StoreFlags { x } => {
+				// Rust requires array indexing to be done with usize
+				let register_x: usize = x as usize;
+				self.rpl_flags[..=register_x].copy_from_slice(&self.v_registers[..=register_x]);
+			},
+
+			// LOAD V0-VX FROM RPL FLAGS (SUPER-CHIP)
+			Instruction::LoadFlags { x } => {
+				// Rust requires array indexing to be done with usize
+				let register_x: usize = x as usize;
+				self.v_registers[..=register_x].copy_from_slice(&self.rpl_flags[..=register_x]);
+			},
+		}
+
+		Ok(())
+	}
+}
+
+// registers from X to Y inclusive, counting down if Y is before X
+fn register_range(register_x: usize, register_y: usize) -> Box<dyn Iterator<Item = usize>> {
+	if register_x <= register_y {
+		Box::new(register_x..=register_y)
+	} else {
+		Box::new((register_y..=register_x).rev())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// a profile with nowhere to put the fonts, or nowhere to put the program, is refused
+	#[test]
+	fn invalid_profiles_are_rejected() {
+		let small_ram: Profile = Profile { ram_size: FONT_AREA_SIZE - 1, ..Profile::default() };
+		assert_eq!(Emulator::with_seed(small_ram, 0).err(), Some(ProfileError::RamTooSmall { ram_size: FONT_AREA_SIZE - 1, min_size: FONT_AREA_SIZE }));
+
+		let start_in_fonts: Profile = Profile { start_address: 0x10, ..Profile::default() };
+		assert_eq!(Emulator::with_seed(start_in_fonts, 0).err(), Some(ProfileError::InvalidStartAddress { start_address: 0x10 }));
+
+		let start_past_ram: Profile = Profile { ram_size: 0x1000, start_address: 0x1000, ..Profile::default() };
+		assert_eq!(Emulator::with_seed(start_past_ram, 0).err(), Some(ProfileError::InvalidStartAddress { start_address: 0x1000 }));
+
+		for platform in Platform::ALL {
+			assert!(Emulator::with_seed(platform.profile(), 0).is_ok());
+		}
+	}
+
+	// keys past F don't exist, and pressing one changes nothing
+	#[test]
+	fn key_press_ignores_missing_keys() {
+		let mut emulator: Emulator = Emulator::default();
+
+		assert!(emulator.key_press(0xF, true));
+		assert!(!emulator.key_press(16, true));
+		assert!(!emulator.key_press(usize::MAX, false));
+
+		let mut expected: [bool; NO_OF_KEYS] = [false; NO_OF_KEYS];
+		expected[0xF] = true;
+		assert_eq!(emulator.get_keys(), &expected);
+	}
+
+	// setters refuse anything outside the machine, e.g. from a debugger client, rather than panicking
+	#[test]
+	fn setters_reject_values_outside_the_machine() {
+		let mut emulator: Emulator = Emulator::with_seed(Platform::CosmacVip.profile(), 0).unwrap();
+		let stack_size: usize = emulator.get_stack().len();
+
+		assert!(emulator.set_v_register(0xF, 7));
+		assert!(!emulator.set_v_register(16, 7));
+		assert_eq!(emulator.get_v_registers()[0xF], 7);
+
+		assert!(emulator.set_stack(stack_size - 1, 0x345));
+		assert!(!emulator.set_stack(stack_size, 0x345));
+		assert_eq!(emulator.get_stack()[stack_size - 1], 0x345);
+
+		assert!(emulator.set_stack_pointer(stack_size as u16));
+		assert!(!emulator.set_stack_pointer(stack_size as u16 + 1));
+		assert_eq!(emulator.get_stack_pointer(), stack_size as u16);
+	}
+
+	// loading a second, shorter ROM leaves nothing of the first behind, wherever either was loaded
+	#[test]
+	fn load_rom_clears_old_rom() {
+		let mut emulator: Emulator = Emulator::default();
+		let fonts: Vec<u8> = emulator.get_ram()[..FONT_AREA_SIZE].to_vec();
+
+		emulator.load_rom(&[0xAA; 0x500]).unwrap();
+		emulator.load_rom_at(0x600, &[0x12, 0x34]).unwrap();
+
+		let ram: &[u8] = emulator.get_ram();
+		assert_eq!(&ram[..FONT_AREA_SIZE], &fonts[..]);
+		assert_eq!(&ram[0x600..0x602], &[0x12, 0x34]);
+		assert!(ram[FONT_AREA_SIZE..0x600].iter().all(|byte| *byte == 0));
+		assert!(ram[0x602..].iter().all(|byte| *byte == 0));
+		assert_eq!(emulator.get_pc(), 0x600);
+
+		emulator.load_rom(&[0x56]).unwrap();
+		let ram: &[u8] = emulator.get_ram();
+		assert_eq!(ram[0x200], 0x56);
+		assert!(ram[0x201..].iter().all(|byte| *byte == 0));
+	}
+
+	// an opcode that runs again from the last address in RAM goes back there,
+	// even though the pc has wrapped round to 0 after fetching it
+	#[test]
+	fn repeated_opcodes_at_the_end_of_ram_wrap() {
+		for opcode in [[0x00, 0xFD], [0xF0, 0x0A]] {
+			let mut emulator: Emulator = Emulator::with_seed(Platform::XoChip.profile(), 0).unwrap();
+			emulator.load_rom_at(0xFFFE, &opcode).unwrap();
+
+			assert_eq!(emulator.tick(), Ok(()));
+			assert_eq!(emulator.get_pc(), 0xFFFE);
+			assert_eq!(emulator.tick(), Ok(()));
+			assert_eq!(emulator.get_pc(), 0xFFFE);
+		}
+	}
+
+	// draws a sprite at a random place, forever
+	const RANDOM_SPRITES_ROM: [u8; 14] = [
+		0xC0, 0x3F, // V0 = random & 3F
+		0xC1, 0x1F, // V1 = random & 1F
+		0xA2, 0x0A, // I = 0x20A
+		0xD0, 0x14, // draw 4 rows at V0, V1
+		0x12, 0x00, // jump 0x200
+		0xF0, 0x90, 0x90, 0xF0, // sprite
+	];
+
+	// emulator running RANDOM_SPRITES_ROM for a number of frames
+	fn run_random_sprites(seed: u64, frames: usize) -> Emulator {
+		let mut emulator: Emulator = Emulator::with_seed(Profile::default(), seed).unwrap();
+		emulator.load_rom(&RANDOM_SPRITES_ROM).unwrap();
+		for _ in 0..frames {
+			for _ in 0..emulator.get_profile().ticks_per_frame {
+				emulator.tick().unwrap();
+			}
+			emulator.tick_timers();
+		}
+		emulator
+	}
+
+	#[test]
+	fn same_seed_gives_identical_screens() {
+		let first: Emulator = run_random_sprites(1234, 60);
+		let second: Emulator = run_random_sprites(1234, 60);
+		assert_eq!(first.get_display(), second.get_display());
+		assert_eq!(first.rng.save(), second.rng.save());
+		assert_eq!(first.save_state(), second.save_state());
+
+		// and a different seed doesn't
+		let other: Emulator = run_random_sprites(4321, 60);
+		assert_ne!(first.get_display(), other.get_display());
+	}
+
+	#[test]
+	fn random_numbers_carry_on_after_loading_a_state() {
+		let mut original: Emulator = run_random_sprites(99, 10);
+		let mut restored: Emulator = Emulator::with_seed(Profile::default(), 1).unwrap();
+		restored.load_state(&original.save_state()).unwrap();
+		assert_eq!(restored.rng.save(), original.rng.save());
+
+		for _ in 0..100 {
+			assert_eq!(restored.rng.next_byte(), original.rng.next_byte());
 		}
 	}
 }
@@ -0,0 +1,244 @@
+// crates
+use std::fmt;
+use std::str::FromStr;
+
+use crate::disassembler::disassemble;
+use crate::error::ProfileError;
+use crate::font::FONT_AREA_SIZE;
+use crate::font::Font;
+use crate::quirks::Quirks;
+use crate::{Instruction, RAM_SIZE, STACK_SIZE, START_ADDRESS};
+
+// 4 KB of RAM for everything before XO-CHIP
+const CLASSIC_RAM_SIZE: usize = 4096;
+
+// everything about the machine a ROM was written for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Profile {
+	// interpretation of the ambiguous opcodes
+	pub quirks: Quirks,
+	// bytes of RAM - I and the PC can't go past this
+	pub ram_size: usize,
+	// where the ROM is loaded and the PC starts
+	pub start_address: u16,
+	// how many return addresses can be on the stack at once
+	pub stack_size: usize,
+	// instructions to run for every 60 Hz frame
+	pub ticks_per_frame: usize,
+	// built-in fonts for FX29 and FX30
+	pub font: Font,
+}
+
+// the machine this emulator has always been - the default quirks with every extension's RAM
+impl Default for Profile {
+	fn default() -> Self {
+		Self {
+			quirks: Quirks::default(),
+			ram_size: RAM_SIZE,
+			start_address: START_ADDRESS,
+			stack_size: STACK_SIZE,
+			ticks_per_frame: 20,
+			font: Font::Octo,
+		}
+	}
+}
+
+impl Profile {
+	// whether a machine can be built from this - its fields are public, so
+	// anything could be in them
+	pub fn validate(&self) -> Result<(), ProfileError> {
+		if self.ram_size < FONT_AREA_SIZE {
+			return Err(ProfileError::RamTooSmall { ram_size: self.ram_size, min_size: FONT_AREA_SIZE });
+		}
+		// the fonts live below the program area, and the program has to start somewhere in RAM
+		let start_address: usize = self.start_address as usize;
+		if start_address < FONT_AREA_SIZE || start_address >= self.ram_size {
+			return Err(ProfileError::InvalidStartAddress { start_address: self.start_address });
+		}
+		Ok(())
+	}
+}
+
+// named interpreters that ROMs are commonly written for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+	// original 1977 interpreter on the RCA COSMAC VIP
+	CosmacVip,
+	// CHIP-48 on the HP48 calculators
+	Chip48,
+	// SUPER-CHIP 1.1 as it behaved on the HP48
+	SchipLegacy,
+	// SUPER-CHIP as most modern emulators and Octo run it
+	SchipModern,
+	// Octo's XO-CHIP extension
+	XoChip,
+}
+
+impl Platform {
+	// every platform, in the order they came out
+	pub const ALL: [Platform; 5] = [
+		Platform::CosmacVip,
+		Platform::Chip48,
+		Platform::SchipLegacy,
+		Platform::SchipModern,
+		Platform::XoChip,
+	];
+
+	// name used on the command line
+	pub fn name(&self) -> &'static str {
+		match self {
+			Platform::CosmacVip => "cosmac-vip",
+			Platform::Chip48 => "chip-48",
+			Platform::SchipLegacy => "schip-legacy",
+			Platform::SchipModern => "schip-modern",
+			Platform::XoChip => "xo-chip",
+		}
+	}
+
+	// settings for a machine running this platform
+	pub fn profile(&self) -> Profile {
+		match self {
+			Platform::CosmacVip => Profile {
+				quirks: Quirks {
+					shift: false,
+					load_store_increment: true,
+					vf_reset: true,
+					jump_with_vx: false,
+					clipping: true,
+					display_wait: true,
+				},
+				ram_size: CLASSIC_RAM_SIZE,
+				start_address: START_ADDRESS,
+				// 48 bytes of stack in the VIP interpreter's work area
+				stack_size: 12,
+				ticks_per_frame: 15,
+				font: Font::CosmacVip,
+			},
+			Platform::Chip48 => Profile {
+				quirks: Quirks {
+					shift: true,
+					load_store_increment: false,
+					vf_reset: false,
+					jump_with_vx: true,
+					clipping: true,
+					display_wait: false,
+				},
+				ram_size: CLASSIC_RAM_SIZE,
+				start_address: START_ADDRESS,
+				stack_size: STACK_SIZE,
+				ticks_per_frame: 30,
+				font: Font::Schip,
+			},
+			Platform::SchipLegacy => Profile {
+				quirks: Quirks {
+					shift: true,
+					load_store_increment: false,
+					vf_reset: false,
+					jump_with_vx: true,
+					clipping: true,
+					display_wait: true,
+				},
+				ram_size: CLASSIC_RAM_SIZE,
+				start_address: START_ADDRESS,
+				stack_size: STACK_SIZE,
+				ticks_per_frame: 30,
+				font: Font::Schip,
+			},
+			Platform::SchipModern => Profile {
+				quirks: Quirks {
+					shift: true,
+					load_store_increment: false,
+					vf_reset: false,
+					jump_with_vx: true,
+					clipping: true,
+					display_wait: false,
+				},
+				ram_size: CLASSIC_RAM_SIZE,
+				start_address: START_ADDRESS,
+				stack_size: STACK_SIZE,
+				ticks_per_frame: 30,
+				font: Font::Octo,
+			},
+			Platform::XoChip => Profile {
+				quirks: Quirks {
+					shift: false,
+					load_store_increment: true,
+					vf_reset: false,
+					jump_with_vx: false,
+					clipping: false,
+					display_wait: false,
+				},
+				ram_size: RAM_SIZE,
+				start_address: START_ADDRESS,
+				stack_size: STACK_SIZE,
+				ticks_per_frame: 1000,
+				font: Font::Octo,
+			},
+		}
+	}
+
+	// best guess at the platform a ROM was written for - XO-CHIP if it uses any
+	// XO-CHIP instructions or is too big for 4K, SUPER-CHIP if it uses any
+	// SUPER-CHIP ones, otherwise the original
+	// sprites and other data can look like instructions, so this can guess wrong
+	pub fn detect(rom: &[u8]) -> Platform {
+		if rom.len() > CLASSIC_RAM_SIZE - START_ADDRESS as usize {
+			return Platform::XoChip;
+		}
+
+		let mut platform: Platform = Platform::CosmacVip;
+		for line in disassemble(rom, START_ADDRESS) {
+			match line.instruction {
+				Some(Instruction::ScrollUp { .. })
+				| Some(Instruction::SaveRange { .. })
+				| Some(Instruction::LoadRange { .. })
+				| Some(Instruction::LoadILong)
+				| Some(Instruction::SelectPlanes { .. })
+				| Some(Instruction::LoadAudioPattern)
+				| Some(Instruction::SetPitch { .. }) => return Platform::XoChip,
+
+				Some(Instruction::ScrollDown { .. })
+				| Some(Instruction::ScrollRight)
+				| Some(Instruction::ScrollLeft)
+				| Some(Instruction::Exit)
+				| Some(Instruction::LowRes)
+				| Some(Instruction::HighRes)
+				| Some(Instruction::Draw { n: 0, .. })
+				| Some(Instruction::LoadBigFont { .. })
+				| Some(Instruction::StoreFlags { .. })
+				| Some(Instruction::LoadFlags { .. }) => platform = Platform::SchipModern,
+
+				_ => (),
+			}
+		}
+
+		platform
+	}
+}
+
+impl From<Platform> for Profile {
+	fn from(platform: Platform) -> Self {
+		platform.profile()
+	}
+}
+
+impl fmt::Display for Platform {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+// parse the command line name of a platform
+impl FromStr for Platform {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Self, Self::Err> {
+		Platform::ALL.iter()
+			.find(|platform| platform.name() == name)
+			.copied()
+			.ok_or_else(|| {
+				let names: Vec<&str> = Platform::ALL.iter().map(|platform| platform.name()).collect();
+				format!("unknown platform '{}', expected one of: {}", name, names.join(", "))
+			})
+	}
+}
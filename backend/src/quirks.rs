@@ -0,0 +1,35 @@
+// behaviours that differ between Chip-8 interpreters
+// ROMs are written for one interpreter, so picking the wrong set can break a game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+	// 8XY6/8XYE shift VX in place and ignore VY (CHIP-48, SUPER-CHIP)
+	// otherwise VY is shifted into VX (COSMAC VIP)
+	pub shift: bool,
+	// FX55/FX65 leave I pointing after the last register stored or loaded (COSMAC VIP)
+	// otherwise I is left untouched
+	pub load_store_increment: bool,
+	// 8XY1/8XY2/8XY3 reset VF to 0 (COSMAC VIP)
+	pub vf_reset: bool,
+	// BXNN jumps to XNN + VX (CHIP-48, SUPER-CHIP)
+	// otherwise BNNN jumps to NNN + V0
+	pub jump_with_vx: bool,
+	// sprites are cut off at the edge of the screen (COSMAC VIP, SUPER-CHIP)
+	// otherwise they wrap around to the other side
+	pub clipping: bool,
+	// DXYN waits for the next frame once drawn, so at most 1 sprite per frame (COSMAC VIP)
+	pub display_wait: bool,
+}
+
+// the behaviours this emulator has always had
+impl Default for Quirks {
+	fn default() -> Self {
+		Self {
+			shift: true,
+			load_store_increment: false,
+			vf_reset: false,
+			jump_with_vx: false,
+			clipping: false,
+			display_wait: false,
+		}
+	}
+}
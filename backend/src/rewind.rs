@@ -0,0 +1,279 @@
+// crates
+use std::collections::VecDeque;
+
+use crate::Emulator;
+
+// rewind history - a save state recorded every frame, kept small by only
+// storing what changed from one frame to the next
+//
+// the newest state is kept in full, and every delta turns a state into the
+// one recorded before it, so rewinding walks back from the newest state
+pub struct RewindBuffer {
+	// most frames kept - the oldest are dropped first
+	capacity: usize,
+	// last state recorded or rewound to
+	latest: Option<Vec<u8>>,
+	// oldest at the front, newest at the back
+	deltas: VecDeque<Delta>,
+}
+
+// change from a save state back to the one before it
+enum Delta {
+	// XOR of the two states, as runs of unchanged bytes followed by changed
+	// bytes - see encode_xor for the layout
+	Xor(Vec<u8>),
+	// states were different lengths, so the whole earlier state is kept
+	Full(Vec<u8>),
+}
+
+impl RewindBuffer {
+	// new constructor for RewindBuffer class - capacity is in frames
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			capacity,
+			latest: None,
+			deltas: VecDeque::new(),
+		}
+	}
+
+	// add the emulator's current state as the newest frame
+	pub fn record(&mut self, emulator: &Emulator) {
+		let state: Vec<u8> = emulator.save_state();
+
+		if let Some(previous) = self.latest.take() {
+			let delta: Delta = if previous.len() == state.len() {
+				Delta::Xor(encode_xor(&state, &previous))
+			} else {
+				Delta::Full(previous)
+			};
+			self.deltas.push_back(delta);
+
+			// forget the oldest frame once full
+			if self.deltas.len() > self.capacity {
+				self.deltas.pop_front();
+			}
+		}
+
+		self.latest = Some(state);
+	}
+
+	// put the emulator back one frame, returning false if there's no history left
+	// the keys held down now are kept, so the game doesn't see stale input
+	pub fn rewind(&mut self, emulator: &mut Emulator) -> bool {
+		let (Some(latest), Some(delta)) = (self.latest.as_mut(), self.deltas.pop_back()) else {
+			return false;
+		};
+
+		match delta {
+			Delta::Xor(runs) => apply_xor(latest, &runs),
+			Delta::Full(previous) => *latest = previous,
+		}
+
+		let keys = emulator.keys;
+		if emulator.load_state(latest).is_err() {
+			// emulator has been swapped for a different machine since recording
+			self.clear();
+			return false;
+		}
+		emulator.keys = keys;
+
+		true
+	}
+
+	// number of frames that can be rewound
+	pub fn len(&self) -> usize {
+		self.deltas.len()
+	}
+
+	// whether there's nothing to rewind to
+	pub fn is_empty(&self) -> bool {
+		self.deltas.is_empty()
+	}
+
+	// forget all history, e.g. after loading a new ROM
+	pub fn clear(&mut self) {
+		self.latest = None;
+		self.deltas.clear();
+	}
+
+	// approximate bytes used by the recorded history
+	pub fn memory_usage(&self) -> usize {
+		let latest_size: usize = self.latest.as_ref().map_or(0, |state| state.len());
+		let delta_size: usize = self.deltas.iter().map(|delta| match delta {
+			Delta::Xor(runs) => runs.len(),
+			Delta::Full(state) => state.len(),
+		}).sum();
+
+		latest_size + delta_size
+	}
+}
+
+// XOR two equal length states, stored as repeated
+// (unchanged byte count, changed byte count, changed bytes) with both counts as varints
+// most of RAM doesn't change between frames, so this is usually tiny
+fn encode_xor(new: &[u8], old: &[u8]) -> Vec<u8> {
+	let mut runs: Vec<u8> = Vec::new();
+	let mut index: usize = 0;
+
+	while index < new.len() {
+		// unchanged bytes - XOR would be 0
+		let unchanged_start: usize = index;
+		while index < new.len() && new[index] == old[index] {
+			index += 1;
+		}
+		if index == new.len() {
+			break;
+		}
+
+		// changed bytes
+		let changed_start: usize = index;
+		while index < new.len() && new[index] != old[index] {
+			index += 1;
+		}
+
+		write_varint(&mut runs, changed_start - unchanged_start);
+		write_varint(&mut runs, index - changed_start);
+		runs.extend(new[changed_start..index].iter().zip(&old[changed_start..index]).map(|(new_byte, old_byte)| new_byte ^ old_byte));
+	}
+
+	runs
+}
+
+// XOR the runs from encode_xor back into a state
+fn apply_xor(state: &mut [u8], runs: &[u8]) {
+	let mut position: usize = 0;
+	let mut index: usize = 0;
+
+	while position < runs.len() {
+		index += read_varint(runs, &mut position);
+		let changed: usize = read_varint(runs, &mut position);
+
+		for (byte, xor) in state[index..index + changed].iter_mut().zip(&runs[position..position + changed]) {
+			*byte ^= xor;
+		}
+
+		index += changed;
+		position += changed;
+	}
+}
+
+// 7 bits at a time, top bit set if more bytes follow
+fn write_varint(output: &mut Vec<u8>, mut value: usize) {
+	while value >= 0x80 {
+		output.push((value as u8 & 0x7F) | 0x80);
+		value >>= 7;
+	}
+	output.push(value as u8);
+}
+
+fn read_varint(input: &[u8], position: &mut usize) -> usize {
+	let mut value: usize = 0;
+	let mut shift: u32 = 0;
+
+	loop {
+		let byte: u8 = input[*position];
+		*position += 1;
+
+		value |= ((byte & 0x7F) as usize) << shift;
+		if byte & 0x80 == 0 {
+			return value;
+		}
+		shift += 7;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// change the emulator so its state says which frame it is
+	fn mark_frame(emulator: &mut Emulator, frame: u8) {
+		emulator.set_delay_timer(frame);
+		emulator.poke(0x300 + frame as u16, frame);
+	}
+
+	#[test]
+	fn xor_delta_turns_new_state_back_into_old() {
+		let old: Vec<u8> = (0..1000).map(|index| (index * 7 % 256) as u8).collect();
+		let mut new: Vec<u8> = old.clone();
+		// a lone change, a run longer than a one byte varint, and the last byte
+		new[3] ^= 0xFF;
+		for byte in new[200..400].iter_mut() {
+			*byte = byte.wrapping_add(1);
+		}
+		new[999] = !new[999];
+
+		let runs: Vec<u8> = encode_xor(&new, &old);
+		let mut state: Vec<u8> = new.clone();
+		apply_xor(&mut state, &runs);
+		assert_eq!(state, old);
+
+		// nothing changed, nothing stored
+		assert!(encode_xor(&old, &old).is_empty());
+	}
+
+	#[test]
+	fn varints_read_back_what_was_written() {
+		let values: [usize; 6] = [0, 1, 0x7F, 0x80, 0x3FFF, 0x123456];
+		let mut output: Vec<u8> = Vec::new();
+		for value in values {
+			write_varint(&mut output, value);
+		}
+
+		let mut position: usize = 0;
+		for value in values {
+			assert_eq!(read_varint(&output, &mut position), value);
+		}
+		assert_eq!(position, output.len());
+	}
+
+	#[test]
+	fn oldest_frames_are_dropped_once_full() {
+		let mut emulator: Emulator = Emulator::default();
+		let mut rewind_buffer: RewindBuffer = RewindBuffer::new(3);
+		let mut states: Vec<Vec<u8>> = Vec::new();
+
+		for frame in 0..6 {
+			mark_frame(&mut emulator, frame);
+			rewind_buffer.record(&emulator);
+			states.push(emulator.save_state());
+		}
+		assert_eq!(rewind_buffer.len(), 3);
+
+		// back through frames 4, 3 and 2, then no further
+		for frame in (2..5).rev() {
+			assert!(rewind_buffer.rewind(&mut emulator));
+			assert_eq!(emulator.save_state(), states[frame]);
+		}
+		assert!(rewind_buffer.is_empty());
+		assert!(!rewind_buffer.rewind(&mut emulator));
+		assert_eq!(emulator.save_state(), states[2]);
+	}
+
+	#[test]
+	fn rewinding_keeps_keys_held_now() {
+		let mut emulator: Emulator = Emulator::default();
+		let mut rewind_buffer: RewindBuffer = RewindBuffer::new(10);
+
+		mark_frame(&mut emulator, 0);
+		rewind_buffer.record(&emulator);
+		mark_frame(&mut emulator, 1);
+		rewind_buffer.record(&emulator);
+
+		emulator.key_press(5, true);
+		assert!(rewind_buffer.rewind(&mut emulator));
+		assert_eq!(emulator.get_delay_timer(), 0);
+		assert!(emulator.get_keys()[5]);
+	}
+
+	#[test]
+	fn nothing_to_rewind_before_recording() {
+		let mut emulator: Emulator = Emulator::default();
+		let mut rewind_buffer: RewindBuffer = RewindBuffer::new(10);
+		assert!(!rewind_buffer.rewind(&mut emulator));
+
+		// a single frame has nothing before it
+		rewind_buffer.record(&emulator);
+		assert!(!rewind_buffer.rewind(&mut emulator));
+	}
+}
@@ -0,0 +1,59 @@
+// source of random numbers for CXNN
+// Send so an emulator can be moved to another thread, e.g. for a debug server
+pub trait RandomSource: Send {
+	// next random byte
+	fn next_byte(&mut self) -> u8;
+
+	// internal state, so save states can put the generator back exactly
+	// generators that can't be saved return nothing
+	fn save(&self) -> Vec<u8> {
+		Vec::new()
+	}
+
+	// put the state from save back, returning false (and changing nothing)
+	// if it isn't a state this generator understands
+	fn load(&mut self, state: &[u8]) -> bool {
+		state.is_empty()
+	}
+}
+
+// xorshift64* - tiny and fast, and the same seed always gives the same numbers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XorShiftRng {
+	state: u64,
+}
+
+impl XorShiftRng {
+	// new constructor for XorShiftRng class - any seed works
+	pub fn new(seed: u64) -> Self {
+		Self {
+			// an all zero state would only ever give zeros
+			state: if seed == 0 {0x9E3779B97F4A7C15} else {seed},
+		}
+	}
+}
+
+impl RandomSource for XorShiftRng {
+	fn next_byte(&mut self) -> u8 {
+		self.state ^= self.state >> 12;
+		self.state ^= self.state << 25;
+		self.state ^= self.state >> 27;
+
+		// top byte is the most random
+		(self.state.wrapping_mul(0x2545F4914F6CDD1D) >> 56) as u8
+	}
+
+	fn save(&self) -> Vec<u8> {
+		self.state.to_be_bytes().to_vec()
+	}
+
+	fn load(&mut self, state: &[u8]) -> bool {
+		match <[u8; 8]>::try_from(state) {
+			Ok(bytes) if bytes != [0; 8] => {
+				self.state = u64::from_be_bytes(bytes);
+				true
+			},
+			_ => false,
+		}
+	}
+}
@@ -0,0 +1,338 @@
+// save states - a snapshot of the whole machine that can be restored later
+//
+// format, with every number big-endian like the Chip-8 itself:
+//
+//   size        field
+//   4           magic bytes "C8SS"
+//   2           format version (currently 1)
+//   4           RAM size in bytes
+//   2           stack size in slots
+//   2           pc
+//   2           i_register
+//   16          v_registers
+//   2           stack_pointer
+//   2 * stack   stack
+//   1           delay_timer
+//   1           sound_timer
+//   16          keys (1 if held)
+//   1           flags - bit 0 hires, bit 1 halted, bit 2 waiting for vblank,
+//               bit 3 audio pattern loaded
+//   1           selected_planes
+//   1           pitch
+//   16          audio_pattern
+//   16          rpl_flags
+//   2           length of the random number generator's state
+//   length      random number generator's state
+//   128 * 64    screen
+//   RAM size    ram
+//   4           CRC-32 of everything before it
+//
+// any change to the fields means a new version number, so old states are
+// rejected rather than being read wrongly
+
+use crate::error::StateError;
+use crate::{Emulator, AUDIO_PATTERN_SIZE, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH, NO_OF_KEYS, NO_OF_REGISTERS, NO_OF_RPL_FLAGS};
+
+// first bytes of every save state
+const MAGIC: [u8; 4] = *b"C8SS";
+// version written by save_state, and the only one load_state reads
+const VERSION: u16 = 1;
+// magic + version
+const HEADER_SIZE: usize = 6;
+// CRC-32 at the end
+const CHECKSUM_SIZE: usize = 4;
+
+// bits in the flags byte
+const FLAG_HIRES: u8 = 1;
+const FLAG_HALTED: u8 = 1 << 1;
+const FLAG_WAITING_FOR_VBLANK: u8 = 1 << 2;
+const FLAG_AUDIO_PATTERN_LOADED: u8 = 1 << 3;
+
+impl Emulator {
+	// snapshot of everything about the running game
+	pub fn save_state(&self) -> Vec<u8> {
+		let mut data: Vec<u8> = Vec::new();
+
+		data.extend_from_slice(&MAGIC);
+		data.extend_from_slice(&VERSION.to_be_bytes());
+
+		data.extend_from_slice(&(self.ram.len() as u32).to_be_bytes());
+		data.extend_from_slice(&(self.stack.len() as u16).to_be_bytes());
+
+		data.extend_from_slice(&self.pc.to_be_bytes());
+		data.extend_from_slice(&self.i_register.to_be_bytes());
+		data.extend_from_slice(&self.v_registers);
+
+		data.extend_from_slice(&self.stack_pointer.to_be_bytes());
+		for address in self.stack.iter() {
+			data.extend_from_slice(&address.to_be_bytes());
+		}
+
+		data.push(self.delay_timer);
+		data.push(self.sound_timer);
+		data.extend(self.keys.iter().map(|&key| key as u8));
+
+		let mut flags: u8 = 0;
+		if self.hires {
+			flags |= FLAG_HIRES;
+		}
+		if self.halted {
+			flags |= FLAG_HALTED;
+		}
+		if self.waiting_for_vblank {
+			flags |= FLAG_WAITING_FOR_VBLANK;
+		}
+		if self.audio_pattern_loaded {
+			flags |= FLAG_AUDIO_PATTERN_LOADED;
+		}
+		data.push(flags);
+		data.push(self.selected_planes);
+		data.push(self.pitch);
+		data.extend_from_slice(&self.audio_pattern);
+		data.extend_from_slice(&self.rpl_flags);
+
+		let rng_state: Vec<u8> = self.rng.save();
+		data.extend_from_slice(&(rng_state.len() as u16).to_be_bytes());
+		data.extend_from_slice(&rng_state);
+
+		data.extend_from_slice(&self.screen);
+		data.extend_from_slice(&self.ram);
+
+		let checksum: u32 = crc32(&data);
+		data.extend_from_slice(&checksum.to_be_bytes());
+
+		data
+	}
+
+	// put the machine back how it was when save_state was called
+	// nothing is changed unless the whole state is valid
+	pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+		if data.len() < HEADER_SIZE + CHECKSUM_SIZE || data[..MAGIC.len()] != MAGIC {
+			return Err(StateError::BadMagic);
+		}
+		let version: u16 = u16::from_be_bytes([data[4], data[5]]);
+		if version != VERSION {
+			return Err(StateError::UnsupportedVersion { version });
+		}
+
+		// checksum covers the header too
+		let (body, checksum) = data.split_at(data.len() - CHECKSUM_SIZE);
+		if crc32(body) != u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) {
+			return Err(StateError::ChecksumMismatch);
+		}
+
+		let mut reader = StateReader { data: &body[HEADER_SIZE..] };
+
+		if reader.u32()? as usize != self.ram.len() || reader.u16()? as usize != self.stack.len() {
+			return Err(StateError::IncompatibleProfile);
+		}
+
+		// read everything before changing anything, so a bad state leaves this machine alone
+		let pc: u16 = reader.u16()?;
+		let i_register: u16 = reader.u16()?;
+		let v_registers: &[u8] = reader.bytes(NO_OF_REGISTERS)?;
+
+		let stack_pointer: u16 = reader.u16()?;
+		if stack_pointer as usize > self.stack.len() {
+			return Err(StateError::IncompatibleProfile);
+		}
+		let stack: &[u8] = reader.bytes(2 * self.stack.len())?;
+
+		let delay_timer: u8 = reader.u8()?;
+		let sound_timer: u8 = reader.u8()?;
+		let keys: &[u8] = reader.bytes(NO_OF_KEYS)?;
+
+		let flags: u8 = reader.u8()?;
+		let selected_planes: u8 = reader.u8()?;
+		let pitch: u8 = reader.u8()?;
+		let audio_pattern: &[u8] = reader.bytes(AUDIO_PATTERN_SIZE)?;
+		let rpl_flags: &[u8] = reader.bytes(NO_OF_RPL_FLAGS)?;
+
+		let rng_state_length: usize = reader.u16()? as usize;
+		let rng_state: &[u8] = reader.bytes(rng_state_length)?;
+
+		let screen: &[u8] = reader.bytes(HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT)?;
+		let ram: &[u8] = reader.bytes(self.ram.len())?;
+
+		if !reader.data.is_empty() {
+			return Err(StateError::WrongLength);
+		}
+
+		// generator goes first as it's the only part that can still refuse
+		if !self.rng.load(rng_state) {
+			return Err(StateError::IncompatibleRandomSource);
+		}
+
+		self.pc = pc;
+		self.i_register = i_register;
+		self.v_registers.copy_from_slice(v_registers);
+
+		self.stack_pointer = stack_pointer;
+		for (address, bytes) in self.stack.iter_mut().zip(stack.chunks(2)) {
+			*address = u16::from_be_bytes([bytes[0], bytes[1]]);
+		}
+
+		self.delay_timer = delay_timer;
+		self.sound_timer = sound_timer;
+		for (key, &held) in self.keys.iter_mut().zip(keys) {
+			*key = held != 0;
+		}
+
+		self.hires = flags & FLAG_HIRES != 0;
+		self.halted = flags & FLAG_HALTED != 0;
+		self.waiting_for_vblank = flags & FLAG_WAITING_FOR_VBLANK != 0;
+		self.audio_pattern_loaded = flags & FLAG_AUDIO_PATTERN_LOADED != 0;
+		self.selected_planes = selected_planes;
+		self.pitch = pitch;
+		self.audio_pattern.copy_from_slice(audio_pattern);
+		self.rpl_flags.copy_from_slice(rpl_flags);
+
+		self.screen.copy_from_slice(screen);
+		self.ram.copy_from_slice(ram);
+
+		Ok(())
+	}
+}
+
+// reads fields off the front of a save state
+struct StateReader<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+	fn bytes(&mut self, length: usize) -> Result<&'a [u8], StateError> {
+		if self.data.len() < length {
+			return Err(StateError::WrongLength);
+		}
+
+		let (field, rest) = self.data.split_at(length);
+		self.data = rest;
+		Ok(field)
+	}
+
+	fn u8(&mut self) -> Result<u8, StateError> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	fn u16(&mut self) -> Result<u16, StateError> {
+		let field: &[u8] = self.bytes(2)?;
+		Ok(u16::from_be_bytes([field[0], field[1]]))
+	}
+
+	fn u32(&mut self) -> Result<u32, StateError> {
+		let field: &[u8] = self.bytes(4)?;
+		Ok(u32::from_be_bytes([field[0], field[1], field[2], field[3]]))
+	}
+}
+
+// standard CRC-32 (the one zip and PNG use)
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+	let mut crc: u32 = 0xFFFFFFFF;
+
+	for &byte in data {
+		crc ^= byte as u32;
+		for _ in 0..8 {
+			// reversed polynomial 0x04C11DB7
+			let mask: u32 = (crc & 1).wrapping_neg();
+			crc = (crc >> 1) ^ (0xEDB88320 & mask);
+		}
+	}
+
+	!crc
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Profile;
+
+	// draws, calls a subroutine and rolls random numbers, so most of the machine has something in it
+	const ROM: [u8; 16] = [
+		0x60, 0x05, // V0 = 5
+		0xF0, 0x29, // I = font digit V0
+		0xD1, 0x25, // draw 5 rows at V1, V2
+		0xC3, 0xFF, // V3 = random
+		0x22, 0x0C, // call 0x20C
+		0x12, 0x00, // jump 0x200
+		0x71, 0x01, // V1 += 1
+		0x00, 0xEE, // return
+	];
+
+	// emulator that's been running ROM for a while
+	fn running_emulator() -> Emulator {
+		let mut emulator: Emulator = Emulator::with_seed(Profile::default(), 7).unwrap();
+		emulator.load_rom(&ROM).unwrap();
+		for _ in 0..45 {
+			emulator.tick().unwrap();
+		}
+		emulator.set_delay_timer(30);
+		emulator.key_press(0xA, true);
+		emulator
+	}
+
+	// same bytes with the checksum worked out again, so only the change is wrong
+	fn with_new_checksum(mut body: Vec<u8>) -> Vec<u8> {
+		let checksum: u32 = crc32(&body);
+		body.extend_from_slice(&checksum.to_be_bytes());
+		body
+	}
+
+	#[test]
+	fn load_restores_saved_state() {
+		let mut emulator: Emulator = running_emulator();
+		let state: Vec<u8> = emulator.save_state();
+
+		let mut restored: Emulator = Emulator::with_seed(Profile::default(), 99).unwrap();
+		restored.load_state(&state).unwrap();
+		assert_eq!(restored.save_state(), state);
+		assert_eq!(restored.get_pc(), emulator.get_pc());
+		assert_eq!(restored.get_display(), emulator.get_display());
+
+		// and they carry on the same, random numbers included
+		for _ in 0..200 {
+			emulator.tick().unwrap();
+			restored.tick().unwrap();
+		}
+		assert_eq!(restored.save_state(), emulator.save_state());
+	}
+
+	#[test]
+	fn bad_states_are_rejected_and_change_nothing() {
+		let emulator: Emulator = running_emulator();
+		let state: Vec<u8> = emulator.save_state();
+		let body: Vec<u8> = state[..state.len() - CHECKSUM_SIZE].to_vec();
+
+		let mut bad_magic: Vec<u8> = body.clone();
+		bad_magic[0] = b'X';
+		let mut old_version: Vec<u8> = body.clone();
+		old_version[4..6].copy_from_slice(&(VERSION + 1).to_be_bytes());
+		let mut damaged: Vec<u8> = state.clone();
+		damaged[100] ^= 1;
+		let truncated_body: Vec<u8> = body[..body.len() - 1].to_vec();
+
+		let cases: [(Vec<u8>, StateError); 7] = [
+			(Vec::new(), StateError::BadMagic),
+			(state[..HEADER_SIZE].to_vec(), StateError::BadMagic),
+			(with_new_checksum(bad_magic), StateError::BadMagic),
+			(with_new_checksum(old_version), StateError::UnsupportedVersion { version: VERSION + 1 }),
+			(damaged, StateError::ChecksumMismatch),
+			(state[..state.len() - 1].to_vec(), StateError::ChecksumMismatch),
+			(with_new_checksum(truncated_body), StateError::WrongLength),
+		];
+
+		let mut target: Emulator = Emulator::with_seed(Profile::default(), 0).unwrap();
+		let before: Vec<u8> = target.save_state();
+		for (data, error) in cases {
+			assert_eq!(target.load_state(&data), Err(error));
+			assert_eq!(target.save_state(), before);
+		}
+	}
+
+	#[test]
+	fn states_from_other_machines_are_rejected() {
+		let state: Vec<u8> = running_emulator().save_state();
+
+		let mut smaller: Emulator = Emulator::with_seed(Profile { ram_size: 0x1000, ..Profile::default() }, 0).unwrap();
+		assert_eq!(smaller.load_state(&state), Err(StateError::IncompatibleProfile));
+	}
+}
@@ -0,0 +1,260 @@
+// execution traces - one line per instruction, showing the machine just
+// before it runs:
+//
+//          5 020C 7001 ADD V0, 0x01         V 01 03 00 00 00 00 00 00 00 00 00 00 00 00 00 01 I 1234 SP 00 DT 00 ST 00
+//
+// cycle, pc, opcode, mnemonic, V0-VF, I, stack pointer, delay and sound timer
+// every column is a fixed width so traces from two runs line up, and can be
+// compared with compare_traces (or the trace-diff tool) to find where they
+// first went different
+
+// crates
+use std::io::{self, BufRead, Write};
+use std::ops::RangeInclusive;
+
+use crate::{Emulator, Instruction};
+
+// writes a line to output for every instruction the emulator runs
+// cycles are counted from when the tracer is attached, including any the
+// filters leave out, so filtered traces still say where they are
+pub struct Tracer {
+	output: Box<dyn Write + Send>,
+	// instructions run so far
+	cycle: u64,
+	// only instructions at these addresses are written
+	addresses: RangeInclusive<u16>,
+	// nothing is written until this many instructions have run
+	start_cycle: u64,
+	// first write that went wrong - nothing more is written after it
+	error: Option<io::Error>,
+}
+
+impl Tracer {
+	// new constructor for Tracer class - everything is written, from the first instruction
+	pub fn new(output: Box<dyn Write + Send>) -> Self {
+		Self {
+			output,
+			cycle: 0,
+			addresses: 0..=u16::MAX,
+			start_cycle: 0,
+			error: None,
+		}
+	}
+
+	// only write instructions whose address is in the range
+	pub fn set_address_range(&mut self, addresses: RangeInclusive<u16>) {
+		self.addresses = addresses;
+	}
+
+	// don't write anything until this many instructions have run
+	pub fn set_start_cycle(&mut self, start_cycle: u64) {
+		self.start_cycle = start_cycle;
+	}
+
+	// instructions run since the tracer was attached
+	pub fn get_cycle(&self) -> u64 {
+		self.cycle
+	}
+
+	// the write error that stopped the trace, if there was one
+	pub fn take_error(&mut self) -> Option<io::Error> {
+		self.error.take()
+	}
+
+	pub fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+
+	// called by tick once an instruction has been fetched, before it runs
+	pub(crate) fn trace(&mut self, emulator: &Emulator, pc: u16, opcode: u16) {
+		let cycle: u64 = self.cycle;
+		self.cycle += 1;
+
+		if self.error.is_some() || cycle < self.start_cycle || !self.addresses.contains(&pc) {
+			return;
+		}
+
+		if let Err(error) = writeln!(self.output, "{}", trace_line(emulator, cycle, pc, opcode)) {
+			self.error = Some(error);
+		}
+	}
+}
+
+// one line of a trace
+fn trace_line(emulator: &Emulator, cycle: u64, pc: u16, opcode: u16) -> String {
+	let mnemonic: String = match Instruction::decode(opcode) {
+		// long I load's address is in the 2 bytes after it
+		Some(Instruction::LoadILong) => {
+			let long_address: Option<u16> = emulator.peek(pc.wrapping_add(2))
+				.zip(emulator.peek(pc.wrapping_add(3)))
+				.map(|(high, low)| u16::from_be_bytes([high, low]));
+			match long_address {
+				Some(long_address) => format!("{} {:#06X}", Instruction::LoadILong, long_address),
+				None => Instruction::LoadILong.to_string(),
+			}
+		},
+		Some(instruction) => instruction.to_string(),
+		None => format!("DW {:#06X}", opcode),
+	};
+	let v_registers: Vec<String> = emulator.get_v_registers().iter().map(|value| format!("{:02X}", value)).collect();
+
+	format!(
+		"{:>10} {:04X} {:04X} {:<20} V {} I {:04X} SP {:02X} DT {:02X} ST {:02X}",
+		cycle,
+		pc,
+		opcode,
+		mnemonic,
+		v_registers.join(" "),
+		emulator.get_i_register(),
+		emulator.get_stack_pointer(),
+		emulator.get_delay_timer(),
+		emulator.get_sound_timer(),
+	)
+}
+
+// where two traces first stopped matching
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+	// 1-based line number - the same in both traces
+	pub line: usize,
+	// cycle the line starts with, if it starts with one
+	pub cycle: Option<u64>,
+	// line from each trace, or None if that trace ended first
+	pub left: Option<String>,
+	pub right: Option<String>,
+}
+
+// compare two traces line by line - None if they're the same
+pub fn compare_traces<L: BufRead, R: BufRead>(left: L, right: R) -> io::Result<Option<Divergence>> {
+	let mut left_lines = left.lines();
+	let mut right_lines = right.lines();
+	let mut line: usize = 0;
+
+	loop {
+		line += 1;
+		let left_line: Option<String> = left_lines.next().transpose()?;
+		let right_line: Option<String> = right_lines.next().transpose()?;
+
+		if left_line == right_line {
+			// both ended at the same time
+			if left_line.is_none() {
+				return Ok(None);
+			}
+			continue;
+		}
+
+		// whichever trace still has a line says which cycle it was
+		let cycle: Option<u64> = left_line.as_ref()
+			.or(right_line.as_ref())
+			.and_then(|text| text.split_whitespace().next())
+			.and_then(|first| first.parse().ok());
+
+		return Ok(Some(Divergence { line, cycle, left: left_line, right: right_line }));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Cursor;
+	use std::sync::{Arc, Mutex};
+
+	use crate::Profile;
+
+	// V0 = 3, then adds 1 to it forever, with I set to a long address on the way
+	const COUNT_ROM: [u8; 12] = [
+		0x60, 0x03, // 0x200: V0 = 3
+		0xF0, 0x00, 0x12, 0x34, // 0x202: I = 0x1234
+		0x70, 0x01, // 0x206: V0 += 1
+		0x12, 0x06, // 0x208: jump 0x206
+		0x00, 0x00,
+	];
+
+	// output the test can read back once the tracer has written to it
+	#[derive(Clone, Default)]
+	struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+	impl Write for SharedOutput {
+		fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+			self.0.lock().unwrap().write(bytes)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	// trace of a ROM running for a number of instructions
+	fn trace(rom: &[u8], ticks: usize, configure: fn(&mut Tracer)) -> String {
+		let mut emulator: Emulator = Emulator::with_seed(Profile::default(), 0).unwrap();
+		emulator.load_rom(rom).unwrap();
+
+		let output: SharedOutput = SharedOutput::default();
+		let mut tracer: Tracer = Tracer::new(Box::new(output.clone()));
+		configure(&mut tracer);
+		emulator.set_tracer(tracer);
+		for _ in 0..ticks {
+			emulator.tick().unwrap();
+		}
+
+		assert_eq!(emulator.take_tracer().unwrap().get_cycle(), ticks as u64);
+		let bytes: Vec<u8> = output.0.lock().unwrap().clone();
+		String::from_utf8(bytes).unwrap()
+	}
+
+	#[test]
+	fn traces_are_the_same_every_run() {
+		let expected: &str = concat!(
+			"         0 0200 6003 LD V0, 0x03          V 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 0000 SP 00 DT 00 ST 00\n",
+			"         1 0202 F000 LD I, LONG 0x1234    V 03 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 0000 SP 00 DT 00 ST 00\n",
+			"         2 0206 7001 ADD V0, 0x01         V 03 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 1234 SP 00 DT 00 ST 00\n",
+			"         3 0208 1206 JP 0x206             V 04 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 1234 SP 00 DT 00 ST 00\n",
+		);
+		assert_eq!(trace(&COUNT_ROM, 4, |_| ()), expected);
+		assert_eq!(trace(&COUNT_ROM, 4, |_| ()), expected);
+	}
+
+	#[test]
+	fn filtered_traces_keep_counting_cycles() {
+		let lines: Vec<String> = trace(&COUNT_ROM, 10, |tracer| {
+			tracer.set_address_range(0x206..=0x206);
+			tracer.set_start_cycle(4);
+		}).lines().map(|line| line[..20].to_string()).collect();
+
+		assert_eq!(lines, ["         4 0206 7001", "         6 0206 7001", "         8 0206 7001"]);
+	}
+
+	fn compare(left: &str, right: &str) -> Option<Divergence> {
+		compare_traces(Cursor::new(left), Cursor::new(right)).unwrap()
+	}
+
+	#[test]
+	fn compare_traces_finds_the_first_difference() {
+		let first: String = trace(&COUNT_ROM, 6, |_| ());
+		assert_eq!(compare(&first, &first), None);
+
+		// a different opcode shows up on its own line, and what it loads on the lines after
+		let mut other_rom: [u8; 12] = COUNT_ROM;
+		other_rom[1] = 0x04;
+		let other: String = trace(&other_rom, 6, |_| ());
+		let divergence: Divergence = compare(&first, &other).unwrap();
+		assert_eq!((divergence.line, divergence.cycle), (1, Some(0)));
+		assert_eq!(divergence.left.as_deref(), first.lines().next());
+		assert_eq!(divergence.right.as_deref(), other.lines().next());
+
+		let divergence: Divergence = compare(&first[first.find('\n').unwrap() + 1..], &other[other.find('\n').unwrap() + 1..]).unwrap();
+		assert_eq!((divergence.line, divergence.cycle), (1, Some(1)));
+
+		// one trace ending first
+		let shorter: String = trace(&COUNT_ROM, 4, |_| ());
+		assert_eq!(compare(&first, &shorter), Some(Divergence {
+			line: 5,
+			cycle: Some(4),
+			left: first.lines().nth(4).map(String::from),
+			right: None,
+		}));
+
+		// lines that don't start with a cycle
+		assert_eq!(compare("a\nb\n", "a\nc\n"), Some(Divergence { line: 2, cycle: None, left: Some(String::from("b")), right: Some(String::from("c")) }));
+	}
+}
@@ -0,0 +1,232 @@
+// golden-screenshot tests - each test ROM in tests/roms runs for a number of
+// frames on a platform, then its screen is compared with a PGM in
+// tests/golden, where each pixel is 0-3 for the bitplanes it's lit on
+//
+// the .asm next to each ROM is its source, with what every result should be -
+// reassemble it with the assembler crate after changing it
+// run with UPDATE_GOLDEN=1 to write the current screens as the golden images,
+// then look at them to check they're right before committing them
+
+// crates
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use backend::{Emulator, Platform};
+
+// long enough for every ROM to finish, even one sprite per frame
+const FRAMES: usize = 180;
+// most differing pixels listed one by one
+const MAX_LISTED_PIXELS: usize = 10;
+
+// a key going down or up at the start of a frame
+struct KeyEvent {
+	frame: usize,
+	key: usize,
+	pressed: bool,
+}
+
+// an image with a value for each pixel
+#[derive(Debug, PartialEq, Eq)]
+struct Screen {
+	width: usize,
+	height: usize,
+	pixels: Vec<u8>,
+}
+
+// run a ROM and compare its screen with the golden image for that platform
+fn check(rom: &str, platform: Platform, keys: &[KeyEvent]) {
+	let directory: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+	let rom_path: PathBuf = directory.join("roms").join(format!("{}.ch8", rom));
+	let golden_path: PathBuf = directory.join("golden").join(format!("{}.{}.pgm", rom, platform));
+
+	let actual: Screen = run(&fs::read(&rom_path).unwrap(), platform, keys);
+
+	if env::var_os("UPDATE_GOLDEN").is_some() {
+		fs::write(&golden_path, to_pgm(&actual, &format!("{}.ch8 on {} after {} frames", rom, platform, FRAMES))).unwrap();
+		return;
+	}
+
+	let golden: String = fs::read_to_string(&golden_path)
+		.unwrap_or_else(|error| panic!("couldn't read {} ({}) - run with UPDATE_GOLDEN=1 to make it", golden_path.display(), error));
+	let expected: Screen = from_pgm(&golden).unwrap_or_else(|message| panic!("{}: {}", golden_path.display(), message));
+
+	if let Some(report) = compare(&expected, &actual) {
+		panic!("{}.ch8 on {} doesn't match {}\n{}", rom, platform, golden_path.display(), report);
+	}
+}
+
+// the screen after FRAMES frames, or once the ROM exits
+fn run(rom: &[u8], platform: Platform, keys: &[KeyEvent]) -> Screen {
+	let mut emulator: Emulator = Emulator::with_seed(platform.profile(), 0).unwrap();
+	emulator.load_rom(rom).unwrap();
+	let ticks_per_frame: usize = emulator.get_profile().ticks_per_frame;
+
+	'frames: for frame in 0..FRAMES {
+		for event in keys.iter().filter(|event| event.frame == frame) {
+			emulator.key_press(event.key, event.pressed);
+		}
+
+		for _ in 0..ticks_per_frame {
+			if emulator.is_halted() {
+				break 'frames;
+			}
+			if let Err(error) = emulator.tick() {
+				panic!("emulator error on {}: {}", platform, error);
+			}
+		}
+		emulator.tick_timers();
+	}
+
+	let (width, height) = emulator.get_resolution();
+	Screen { width, height, pixels: emulator.get_display().to_vec() }
+}
+
+// plain (P2) PGM with a maximum of 3
+fn to_pgm(screen: &Screen, comment: &str) -> String {
+	let mut text: String = format!("P2\n# {}\n{} {}\n3\n", comment, screen.width, screen.height);
+
+	for row in screen.pixels.chunks(screen.width) {
+		let values: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+		text.push_str(&values.join(" "));
+		text.push('\n');
+	}
+
+	text
+}
+
+fn from_pgm(text: &str) -> Result<Screen, String> {
+	// comments run to the end of the line
+	let mut values = text.lines()
+		.map(|line| line.split('#').next().unwrap_or(""))
+		.flat_map(|line| line.split_whitespace());
+
+	if values.next() != Some("P2") {
+		return Err(String::from("not a plain PGM"));
+	}
+	let mut number = || -> Result<usize, String> {
+		values.next().and_then(|value| value.parse().ok()).ok_or_else(|| String::from("ends early or has something that isn't a number"))
+	};
+
+	let width: usize = number()?;
+	let height: usize = number()?;
+	let _maximum: usize = number()?;
+	let pixels: Vec<u8> = (0..width * height).map(|_| number().map(|value| value as u8)).collect::<Result<_, _>>()?;
+
+	Ok(Screen { width, height, pixels })
+}
+
+// None if they're the same, otherwise the pixels that differ and a picture of where
+fn compare(expected: &Screen, actual: &Screen) -> Option<String> {
+	if (expected.width, expected.height) != (actual.width, actual.height) {
+		return Some(format!("golden image is {}x{}, but the screen is {}x{}", expected.width, expected.height, actual.width, actual.height));
+	}
+
+	let differing: Vec<usize> = (0..expected.pixels.len()).filter(|&index| expected.pixels[index] != actual.pixels[index]).collect();
+	if differing.is_empty() {
+		return None;
+	}
+
+	let mut report: String = format!("{} pixels differ (x, y: expected -> actual):\n", differing.len());
+	for &index in differing.iter().take(MAX_LISTED_PIXELS) {
+		report.push_str(&format!("  {}, {}: {} -> {}\n", index % expected.width, index / expected.width, expected.pixels[index], actual.pixels[index]));
+	}
+	if differing.len() > MAX_LISTED_PIXELS {
+		report.push_str(&format!("  and {} more\n", differing.len() - MAX_LISTED_PIXELS));
+	}
+
+	// . and # match, - should be lit but isn't, + is lit but shouldn't be,
+	// and * is lit on the wrong planes
+	report.push_str("screen (. off, # on, - missing, + extra, * wrong planes):\n");
+	for y in 0..expected.height {
+		let row: String = (0..expected.width)
+			.map(|x| {
+				let index: usize = y * expected.width + x;
+				match (expected.pixels[index], actual.pixels[index]) {
+					(0, 0) => '.',
+					(expected, actual) if expected == actual => '#',
+					(_, 0) => '-',
+					(0, _) => '+',
+					_ => '*',
+				}
+			})
+			.collect();
+		report.push_str(&row);
+		report.push('\n');
+	}
+
+	Some(report)
+}
+
+#[test]
+fn opcodes_cosmac_vip() {
+	check("opcodes", Platform::CosmacVip, &[]);
+}
+
+#[test]
+fn opcodes_schip_modern() {
+	check("opcodes", Platform::SchipModern, &[]);
+}
+
+#[test]
+fn opcodes_xo_chip() {
+	check("opcodes", Platform::XoChip, &[]);
+}
+
+#[test]
+fn quirks_cosmac_vip() {
+	check("quirks", Platform::CosmacVip, &[]);
+}
+
+#[test]
+fn quirks_chip_48() {
+	check("quirks", Platform::Chip48, &[]);
+}
+
+#[test]
+fn quirks_schip_legacy() {
+	check("quirks", Platform::SchipLegacy, &[]);
+}
+
+#[test]
+fn quirks_schip_modern() {
+	check("quirks", Platform::SchipModern, &[]);
+}
+
+#[test]
+fn quirks_xo_chip() {
+	check("quirks", Platform::XoChip, &[]);
+}
+
+// tap A, then hold 5 down for half a second
+const KEYPAD_EVENTS: [KeyEvent; 4] = [
+	KeyEvent { frame: 10, key: 0xA, pressed: true },
+	KeyEvent { frame: 11, key: 0xA, pressed: false },
+	KeyEvent { frame: 30, key: 0x5, pressed: true },
+	KeyEvent { frame: 60, key: 0x5, pressed: false },
+];
+
+#[test]
+fn keypad_cosmac_vip() {
+	check("keypad", Platform::CosmacVip, &KEYPAD_EVENTS);
+}
+
+#[test]
+fn keypad_schip_modern() {
+	check("keypad", Platform::SchipModern, &KEYPAD_EVENTS);
+}
+
+#[test]
+fn schip_schip_legacy() {
+	check("schip", Platform::SchipLegacy, &[]);
+}
+
+#[test]
+fn schip_schip_modern() {
+	check("schip", Platform::SchipModern, &[]);
+}
+
+#[test]
+fn xochip_xo_chip() {
+	check("xochip", Platform::XoChip, &[]);
+}
@@ -0,0 +1,25 @@
+; keypad opcodes - the harness taps key A on frame 10, then holds key 5
+; down from frame 30 to frame 60
+; assemble with: cargo run -p assembler backend/tests/roms/keypad.asm
+
+        CLS
+        LD VD, 0
+        LD VE, 0
+
+        LD V0, K            ; FX0A - 0A
+        CALL show
+
+        LD V1, 5            ; EX9E once key 5 is down - 55
+wait:   SKP V1
+        JP wait
+        LD V0, 0x55
+        CALL show
+
+up:     SKNP V1             ; EXA1 once it's let go - 66
+        JP up
+        LD V0, 0x66
+        CALL show
+
+end:    JP end
+
+        INCLUDE "show.asm"
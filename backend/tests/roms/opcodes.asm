@@ -0,0 +1,144 @@
+; every Chip-8 opcode that behaves the same on every platform - each test
+; leaves a result in V0 and shows it, so a wrong result shows as a wrong
+; digit. expected results are in the comments
+; assemble with: cargo run -p assembler backend/tests/roms/opcodes.asm
+
+        CLS                 ; 00E0
+        LD VD, 0
+        LD VE, 0
+
+        LD V0, 0x12         ; 6XNN - 12
+        CALL show           ; 2NNN and 00EE
+
+        LD V0, 0xFF         ; 7XNN wraps - 01
+        ADD V0, 2
+        CALL show
+
+        LD V1, 0x34         ; 8XY0 - 34
+        LD V0, V1
+        CALL show
+
+        LD V0, 0x0F         ; 8XY1 - FF
+        LD V1, 0xF0
+        OR V0, V1
+        CALL show
+
+        LD V0, 0x3C         ; 8XY2 - 0C
+        LD V1, 0x0F
+        AND V0, V1
+        CALL show
+
+        LD V0, 0x3C         ; 8XY3 - C3
+        LD V1, 0xFF
+        XOR V0, V1
+        CALL show
+
+        LD V0, 0xF0         ; 8XY4 - 10
+        LD V1, 0x20
+        ADD V0, V1
+        LD V2, VF           ; carry - 01
+        CALL show
+        LD V0, V2
+        CALL show
+
+        LD V0, 0x10         ; 8XY5 - F0
+        LD V1, 0x20
+        SUB V0, V1
+        LD V2, VF           ; borrow - 00
+        CALL show
+        LD V0, V2
+        CALL show
+
+        LD V0, 0x10         ; 8XY7 - 20
+        LD V1, 0x30
+        SUBN V0, V1
+        LD V2, VF           ; no borrow - 01
+        CALL show
+        LD V0, V2
+        CALL show
+
+        LD V0, 0x81         ; 8XY6 in place - 40
+        SHR V0, V0
+        CALL show
+
+        LD V0, 0x81         ; 8XYE in place - 02
+        SHL V0, V0
+        CALL show
+
+        LD V0, 0            ; 3XNN, 4XNN, 5XY0 and 9XY0, skipping and not - A5
+        LD V1, 5
+        LD V2, 5
+        LD V3, 6
+        SE V1, 5
+        ADD V0, 0x80
+        SE V1, 6
+        ADD V0, 0x01
+        SNE V1, 6
+        ADD V0, 0x40
+        SNE V1, 5
+        ADD V0, 0x04
+        SE V1, V2
+        ADD V0, 0x08
+        SE V1, V3
+        ADD V0, 0x20
+        SNE V1, V3
+        ADD V0, 0x10
+        SNE V1, V2
+        ADD V0, 0x80
+        CALL show
+
+        LD V0, 0x9C         ; FX33 and FX65 - 01 05 06
+        LD I, scratch
+        LD B, V0
+        LD I, scratch
+        LD V2, [I]
+        LD V4, V1
+        LD V5, V2
+        CALL show
+        LD V0, V4
+        CALL show
+        LD V0, V5
+        CALL show
+
+        LD V0, 0xAB         ; FX55 and FX1E - CD
+        LD V1, 0xCD
+        LD I, scratch
+        LD [I], V1
+        LD I, scratch
+        LD V1, 1
+        ADD I, V1
+        LD V0, [I]
+        CALL show
+
+        LD V0, 0x20         ; FX15 and FX07 - 20
+        LD DT, V0
+        LD V0, DT
+        CALL show
+
+        RND V0, 0           ; CXNN masks - 00
+        CALL show
+
+        LD V0, 0            ; EX9E and EXA1 with no keys down - 01
+        LD V1, 5
+        SKP V1
+        ADD V0, 1
+        SKNP V1
+        ADD V0, 2
+        CALL show
+
+        LD V1, 0            ; DXYN collision - 00 then 01
+        LD F, V1
+        LD V1, 56
+        LD V2, 25
+        DRW V1, V2, 5
+        LD V0, VF
+        CALL show
+        DRW V1, V2, 5
+        LD V0, VF
+        CALL show
+
+end:    JP end              ; 1NNN
+
+scratch: DB 0, 0, 0, 0
+
+        INCLUDE "show.asm"
@@ -0,0 +1,52 @@
+; opcodes that behave differently between platforms - each golden image
+; shows what one platform does
+; assemble with: cargo run -p assembler backend/tests/roms/quirks.asm
+
+        CLS
+        LD VD, 0
+        LD VE, 0
+
+        LD VF, 0x55         ; vF reset - 00 if 8XY1 clears VF, otherwise 55
+        LD V0, 1
+        LD V1, 2
+        OR V0, V1
+        LD V0, VF
+        CALL show
+
+        LD V1, 0x08         ; shift - 04 if VY is shifted, 00 if VX is
+        LD V0, 0x01
+        SHR V0, V1
+        CALL show
+
+        LD I, scratch       ; load/store increment - 33 if I moves on, 11 if not
+        LD V0, 0x11
+        LD V1, 0x22
+        LD V2, 0x33
+        LD [I], V2
+        LD I, scratch
+        LD [I], V1
+        LD V0, [I]
+        CALL show
+
+        LD V0, 0            ; jump - C0 for NNN + V0, 5C for XNN + VX
+        LD V3, 4
+        JP V0, table
+back:   CALL show
+
+        LD V1, 0            ; clipping - a 0 at the bottom right is cut off or wraps around
+        LD F, V1
+        LD V1, 62
+        LD V2, 30
+        DRW V1, V2, 5
+
+end:    JP end
+
+scratch: DB 0, 0, 0, 0
+
+        INCLUDE "show.asm"
+
+        ORG 0x300
+table:  LD V0, 0xC0
+        JP back
+        LD V0, 0x5C
+        JP back
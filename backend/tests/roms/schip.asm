@@ -0,0 +1,39 @@
+; SUPER-CHIP opcodes - hi-res, big sprites, big font, scrolling, RPL flags
+; and exit
+; assemble with: cargo run -p assembler backend/tests/roms/schip.asm
+
+        LOW                 ; 00FE and 00FF - switching clears the screen, so the
+        LD I, square        ; square drawn in lo-res is gone once it's hi-res
+        DRW VD, VE, 15
+        HIGH
+        LD VD, 0
+        LD VE, 0
+
+        LD V0, 0xA7         ; FX75 and FX85 - A7
+        LD R, V0
+        LD V0, 0
+        LD V0, R
+        CALL show
+
+        LD I, square        ; DXY0 draws 16x16, then 00C4 scrolls it down
+        LD V1, 100
+        LD V2, 0
+        DRW V1, V2, 0
+        SCD 4
+
+        LD V1, 8            ; FX30 - a big 8
+        LD HF, V1
+        LD V1, 80
+        LD V2, 40
+        DRW V1, V2, 10
+
+        SCR                 ; 00FB, 00FC, 00FB - everything ends up 4 pixels right
+        SCL
+        SCR
+
+        EXIT                ; 00FD - the harness stops here
+
+square: DW 0xFFFF, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001
+        DW 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0xFFFF
+
+        INCLUDE "show.asm"
@@ -0,0 +1,23 @@
+; shared by the test ROMs - draws V0 as 2 hex digits at (VD, VE), then
+; moves along to where the next byte goes, 5 bytes to a row
+; uses VB, VC and VF, so tests keep to V0-VA, and VD and VE start at 0
+
+show:   LD VC, V0
+        SHR VC, VC          ; same in place on every platform
+        SHR VC, VC
+        SHR VC, VC
+        SHR VC, VC
+        LD F, VC
+        DRW VD, VE, 5
+        ADD VD, 5
+        LD VC, V0
+        LD VB, 0x0F
+        AND VC, VB
+        LD F, VC
+        DRW VD, VE, 5
+        ADD VD, 8
+        SE VD, 65
+        RET
+        LD VD, 0
+        ADD VE, 6
+        RET
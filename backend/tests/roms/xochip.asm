@@ -0,0 +1,53 @@
+; XO-CHIP opcodes - bitplanes, scrolling up, register ranges and long I
+; assemble with: cargo run -p assembler backend/tests/roms/xochip.asm
+
+        CLS
+        LD VD, 0
+        LD VE, 0
+
+        LD V1, 0x12         ; 5XY2 and 5XY3 - 12 34 56
+        LD V2, 0x34
+        LD V3, 0x56
+        LD I, scratch
+        SAVE V1, V3
+        LD V1, 0
+        LD V2, 0
+        LD V3, 0
+        LD I, scratch
+        LOAD V1, V3
+        LD V0, V1
+        CALL show
+        LD V0, V2
+        CALL show
+        LD V0, V3
+        CALL show
+
+        LD I, LONG far      ; F000 - 7E
+        LD V0, [I]
+        CALL show
+
+        PLANE 2             ; FN01 - a block on plane 2, one on both planes
+        LD I, block
+        LD V1, 8
+        LD V2, 20
+        DRW V1, V2, 8
+        PLANE 3
+        LD I, both
+        LD V1, 24
+        DRW V1, V2, 8
+
+        PLANE 3             ; 00D2 - scroll everything up 2
+        SCU 2
+
+end:    JP end
+
+scratch: DB 0, 0, 0
+block:  DB 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+; plane 1's 8 rows, then plane 2's
+both:   DB 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0
+        DB 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00
+
+        INCLUDE "show.asm"
+
+        ORG 0x1000
+far:    DB 0x7E
@@ -0,0 +1,71 @@
+// runs the trace-diff tool on traces written to a temporary directory, and
+// checks what it prints and its exit code
+
+// crates
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Output};
+
+const TRACE: &str = "\
+         0 0200 6003 LD V0, 0x03          V 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 0000 SP 00 DT 00 ST 00
+         1 0202 7001 ADD V0, 0x01         V 03 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 0000 SP 00 DT 00 ST 00
+";
+
+// write each trace to a file of its own, then compare them
+fn trace_diff(name: &str, left: &str, right: &str) -> (Option<i32>, String) {
+	let directory: PathBuf = env::temp_dir().join(format!("trace-diff-{}-{}", name, process::id()));
+	fs::create_dir_all(&directory).unwrap();
+	let left_path: PathBuf = directory.join("left.txt");
+	let right_path: PathBuf = directory.join("right.txt");
+	fs::write(&left_path, left).unwrap();
+	fs::write(&right_path, right).unwrap();
+
+	let output: Output = Command::new(env!("CARGO_BIN_EXE_trace-diff")).arg(&left_path).arg(&right_path).output().unwrap();
+	let _ = fs::remove_dir_all(&directory);
+
+	let text: String = String::from_utf8(output.stdout).unwrap().replace(&*left_path.to_string_lossy(), "left.txt").replace(&*right_path.to_string_lossy(), "right.txt");
+	(output.status.code(), text)
+}
+
+#[test]
+fn same_traces() {
+	assert_eq!(trace_diff("same", TRACE, TRACE), (Some(0), String::from("Traces are the same\n")));
+}
+
+#[test]
+fn differing_traces_are_marked_at_the_first_difference() {
+	let right: String = TRACE.replace("ADD V0, 0x01         V 03", "ADD V0, 0x01         V 04");
+	let (code, text) = trace_diff("differing", TRACE, &right);
+
+	assert_eq!(code, Some(1));
+	let lines: Vec<&str> = text.lines().collect();
+	assert_eq!(lines[0], "First difference is at cycle 1 (line 2)");
+	assert_eq!(lines[1], format!("< {}", TRACE.lines().nth(1).unwrap()));
+	assert_eq!(lines[2], format!("> {}", right.lines().nth(1).unwrap()));
+	// the marker is under the second digit of V0
+	assert_eq!(lines[3], format!("  {}^", " ".repeat(TRACE.lines().nth(1).unwrap().find("V 03").unwrap() + 3)));
+	assert_eq!(lines.len(), 4);
+}
+
+#[test]
+fn a_trace_ending_first() {
+	let shorter: &str = TRACE.lines().next().unwrap();
+	assert_eq!(trace_diff("ending", shorter, TRACE), (Some(1), String::from("First difference is at cycle 1 (line 2)\nleft.txt ended first\n")));
+}
+
+#[test]
+fn missing_traces_are_an_error() {
+	let output: Output = Command::new(env!("CARGO_BIN_EXE_trace-diff")).arg("no-such-trace.txt").arg("no-such-trace.txt").output().unwrap();
+	assert_eq!(output.status.code(), Some(2));
+	assert!(output.stdout.is_empty());
+	assert!(String::from_utf8(output.stderr).unwrap().starts_with("Unable to read traces: "));
+}
+
+#[test]
+fn usage_goes_to_stderr() {
+	let output: Output = Command::new(env!("CARGO_BIN_EXE_trace-diff")).arg("only-one-trace.txt").output().unwrap();
+	assert_eq!(output.status.code(), Some(2));
+	assert!(output.stdout.is_empty());
+	assert!(String::from_utf8(output.stderr).unwrap().starts_with("Usage: "));
+}
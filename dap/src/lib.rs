@@ -0,0 +1,141 @@
+// Debug Adapter Protocol server, so editors can debug CHIP-8 games from
+// their Octo or assembly source
+//
+// launch takes a program - .8o files are compiled with octo, .asm and .s files
+// with the assembler, and anything else is loaded as a ROM, which can only be
+// debugged by address. platform (e.g. "xo-chip") and stopOnEntry are optional
+//
+// breakpoints go on source lines through the assembly's source map, stepping
+// goes a source line at a time, the call stack comes from the machine's
+// stack, and the variables are V0-VF and I, and the timers
+// there's no screen or keyboard - the game runs on its own, 60 frames a second
+
+// crates
+use std::io::{self, BufReader, Read, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+// modules
+mod protocol;
+mod session;
+
+pub use protocol::{read_message, write_message};
+pub use session::Session;
+
+// how often run_frame is called while the game runs
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// talk to a client until it disconnects or the input ends
+// input is read on its own thread, so messages like pause still arrive
+// while the game is running
+pub fn serve<R, W>(input: R, mut output: W) -> io::Result<()>
+where
+	R: Read + Send + 'static,
+	W: Write,
+{
+	let receiver: Receiver<io::Result<Value>> = spawn_reader(input);
+	let mut session: Session = Session::new();
+	let mut next_frame: Instant = Instant::now();
+
+	while !session.is_finished() {
+		let message: Option<io::Result<Value>> = if session.is_running() {
+			match receiver.recv_timeout(next_frame.saturating_duration_since(Instant::now())) {
+				Ok(message) => Some(message),
+				Err(RecvTimeoutError::Timeout) => None,
+				Err(RecvTimeoutError::Disconnected) => return Ok(()),
+			}
+		} else {
+			match receiver.recv() {
+				Ok(message) => Some(message),
+				Err(_) => return Ok(()),
+			}
+		};
+
+		let replies: Vec<Value> = match message {
+			Some(message) => session.handle(&message?),
+			None => {
+				// frames are kept to time however long handling messages takes
+				next_frame = next_frame.max(Instant::now() - FRAME_TIME) + FRAME_TIME;
+				session.run_frame()
+			},
+		};
+		for reply in replies.iter() {
+			write_message(&mut output, reply)?;
+		}
+
+		// a stopped game starts its next frame from whenever it's resumed
+		if !session.is_running() {
+			next_frame = Instant::now();
+		}
+	}
+
+	Ok(())
+}
+
+// read messages on another thread - the channel closes when the input ends
+fn spawn_reader<R: Read + Send + 'static>(input: R) -> Receiver<io::Result<Value>> {
+	let (sender, receiver) = mpsc::channel();
+
+	thread::spawn(move || {
+		let mut reader: BufReader<R> = BufReader::new(input);
+		loop {
+			let message: io::Result<Value> = match read_message(&mut reader) {
+				Ok(Some(message)) => Ok(message),
+				Ok(None) => break,
+				Err(error) => Err(error),
+			};
+			let failed: bool = message.is_err();
+			if sender.send(message).is_err() || failed {
+				break;
+			}
+		}
+	});
+
+	receiver
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Cursor;
+
+	use serde_json::json;
+
+	#[test]
+	fn serve_answers_a_script_of_messages() {
+		let program: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/entry.asm");
+		let requests: [Value; 5] = [
+			json!({ "seq": 1, "type": "request", "command": "initialize", "arguments": {} }),
+			json!({ "seq": 2, "type": "request", "command": "setBreakpoints", "arguments": { "source": { "path": program }, "breakpoints": [{ "line": 2 }] } }),
+			json!({ "seq": 3, "type": "request", "command": "launch", "arguments": { "program": program } }),
+			json!({ "seq": 4, "type": "request", "command": "configurationDone", "arguments": {} }),
+			json!({ "seq": 5, "type": "request", "command": "disconnect", "arguments": {} }),
+		];
+		let mut input: Vec<u8> = Vec::new();
+		for request in requests.iter() {
+			write_message(&mut input, request).unwrap();
+		}
+
+		let mut output: Vec<u8> = Vec::new();
+		serve(Cursor::new(input), &mut output).unwrap();
+
+		let mut reader: Cursor<Vec<u8>> = Cursor::new(output);
+		let mut messages: Vec<Value> = Vec::new();
+		while let Some(message) = read_message(&mut reader).unwrap() {
+			messages.push(message);
+		}
+
+		// every message is numbered in the order it was sent
+		let seqs: Vec<i64> = messages.iter().map(|message| message["seq"].as_i64().unwrap()).collect();
+		assert_eq!(seqs, (1..=messages.len() as i64).collect::<Vec<i64>>());
+
+		let names: Vec<&str> = messages.iter()
+			.map(|message| message["command"].as_str().or(message["event"].as_str()).unwrap())
+			.collect();
+		assert_eq!(names, ["initialize", "initialized", "setBreakpoints", "launch", "breakpoint", "configurationDone", "stopped", "disconnect"]);
+		assert_eq!(messages[6]["body"]["reason"], "breakpoint");
+	}
+}
@@ -0,0 +1,40 @@
+// crates
+use std::env;
+use std::io;
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+	let args: Vec<String> = env::args().collect();
+
+	// stdin and stdout unless there's a port to listen on
+	let result: io::Result<()> = match args.as_slice() {
+		[_] => dap::serve(io::stdin(), io::stdout()),
+		[_, option, port] if option == "--port" => match port.parse::<u16>() {
+			Ok(port) => serve_port(port),
+			Err(_) => {
+				eprintln!("{} isn't a port number", port);
+				return ExitCode::FAILURE;
+			},
+		},
+		_ => {
+			eprintln!("Usage: cargo run -p dap [--port port]");
+			return ExitCode::FAILURE;
+		},
+	};
+
+	if let Err(error) = result {
+		eprintln!("{}", error);
+		return ExitCode::FAILURE;
+	}
+	ExitCode::SUCCESS
+}
+
+// wait for one client on localhost, then serve it
+fn serve_port(port: u16) -> io::Result<()> {
+	let listener: TcpListener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+	eprintln!("Waiting for a client on localhost:{}", port);
+
+	let (stream, _): (TcpStream, _) = listener.accept()?;
+	dap::serve(stream.try_clone()?, stream)
+}
@@ -0,0 +1,45 @@
+// DAP messages go both ways as a Content-Length header, a blank line, then
+// that many bytes of JSON
+
+// crates
+use std::io::{self, BufRead, ErrorKind, Write};
+
+use serde_json::Value;
+
+// next message, or None once the input has ended
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+	let mut length: Option<usize> = None;
+
+	// headers end with a blank line
+	loop {
+		let mut line: String = String::new();
+		if reader.read_line(&mut line)? == 0 {
+			return Ok(None);
+		}
+
+		let line: &str = line.trim_end();
+		if line.is_empty() {
+			if length.is_some() {
+				break;
+			}
+			continue;
+		}
+		if let Some((name, value)) = line.split_once(':') {
+			if name.trim().eq_ignore_ascii_case("Content-Length") {
+				let value: usize = value.trim().parse().map_err(|_| io::Error::new(ErrorKind::InvalidData, "Content-Length isn't a number"))?;
+				length = Some(value);
+			}
+		}
+	}
+
+	let mut body: Vec<u8> = vec![0; length.unwrap_or(0)];
+	reader.read_exact(&mut body)?;
+
+	serde_json::from_slice(&body).map(Some).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+	let body: String = message.to_string();
+	write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+	writer.flush()
+}
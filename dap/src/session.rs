@@ -0,0 +1,648 @@
+// crates
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use assembler::{Assembly, SourceLocation};
+use backend::{Debugger, Emulator, Platform, Profile, StopReason};
+use serde_json::{json, Value};
+
+// DAP is all about threads, so the machine is the only one
+const THREAD_ID: i64 = 1;
+// variablesReference of each scope
+const REGISTERS_REFERENCE: i64 = 1;
+const TIMERS_REFERENCE: i64 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StepKind {
+	// next
+	Over,
+	In,
+	Out,
+}
+
+// where a step started from
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Step {
+	kind: StepKind,
+	// None for a ROM with no source, where every instruction is a step
+	location: Option<SourceLocation>,
+	stack_pointer: u16,
+}
+
+// what the game is doing between messages
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Mode {
+	// waiting for launch and configurationDone
+	Starting,
+	Stopped,
+	Running,
+	// running an instruction at a time until the step is done
+	Stepping(Step),
+	// game has run the exit opcode
+	Exited,
+}
+
+// a launched game
+struct Program {
+	emulator: Emulator,
+	// None for a plain ROM, which only has addresses
+	assembly: Option<Assembly>,
+	// canonical path of each source file, by the name the source map uses
+	sources: HashMap<String, PathBuf>,
+}
+
+impl Program {
+	// first address at or after a source line that has code, and that line
+	fn resolve_line(&self, path: &Path, line: usize) -> Option<(u16, usize)> {
+		let assembly: &Assembly = self.assembly.as_ref()?;
+
+		assembly.source_map.iter()
+			.filter(|entry| entry.location.line >= line && self.sources.get(&entry.location.file).is_some_and(|source| source == path))
+			.min_by_key(|entry| (entry.location.line, entry.address))
+			.map(|entry| (entry.address, entry.location.line))
+	}
+
+	fn location_of(&self, address: u16) -> Option<&SourceLocation> {
+		self.assembly.as_ref()?.location_of(address)
+	}
+
+	// whether the pc has moved off the line a step started on
+	fn has_left(&self, location: &Option<SourceLocation>) -> bool {
+		let current: Option<&SourceLocation> = self.location_of(self.emulator.get_pc());
+		current.is_none() || current != location.as_ref()
+	}
+
+	// run up to a frame's worth of instructions towards finishing a step
+	// returns None if the step still isn't done
+	fn step(&mut self, debugger: &Debugger, step: &Step, max_ticks: usize) -> Option<StopReason> {
+		for _ in 0..max_ticks {
+			match debugger.step(&mut self.emulator) {
+				StopReason::Step => (),
+				// carries on next frame
+				StopReason::WaitingForFrame => return None,
+				reason => return Some(reason),
+			}
+
+			let stack_pointer: u16 = self.emulator.get_stack_pointer();
+			let done: bool = match step.kind {
+				// subroutines run until they return
+				StepKind::Over => stack_pointer <= step.stack_pointer && self.has_left(&step.location),
+				StepKind::In => self.has_left(&step.location),
+				StepKind::Out => stack_pointer < step.stack_pointer,
+			};
+			if done {
+				return Some(StopReason::Step);
+			}
+			if debugger.is_breakpoint_hit(&self.emulator) {
+				return Some(StopReason::Breakpoint { address: self.emulator.get_pc() });
+			}
+		}
+
+		None
+	}
+
+	// DAP stack frame for an address
+	fn frame(&self, id: usize, address: u16) -> Value {
+		// nearest label at or before the address
+		let name: String = self.assembly.as_ref()
+			.and_then(|assembly| assembly.labels.iter().filter(|(_, &label)| label <= address).max_by_key(|(_, &label)| label))
+			.map(|(name, _)| name.clone())
+			.unwrap_or_else(|| format!("{:#06X}", address));
+
+		let mut frame: Value = json!({
+			"id": id,
+			"name": name,
+			"line": 0,
+			"column": 0,
+			"instructionPointerReference": format!("{:#06X}", address),
+		});
+		if let Some(location) = self.location_of(address) {
+			frame["source"] = source_json(&location.file, self.sources.get(&location.file));
+			frame["line"] = json!(location.line);
+			frame["column"] = json!(1);
+		}
+
+		frame
+	}
+}
+
+// one debugging session - handle is given each request from the client,
+// and run_frame is called 60 times a second while the game runs
+// both give back the messages to send, so a session can be driven by a
+// script of messages as easily as by an editor
+pub struct Session {
+	program: Option<Program>,
+	debugger: Debugger,
+	// id and line of every breakpoint asked for, by canonical source path -
+	// kept so they can be worked out again once a program is launched
+	breakpoints: HashMap<PathBuf, Vec<(i64, usize)>>,
+	next_breakpoint_id: i64,
+	configured: bool,
+	stop_on_entry: bool,
+	mode: Mode,
+	// where a breakpoint or step last stopped the game - running again goes
+	// past a breakpoint there rather than stopping on it a second time
+	stopped_at: Option<u16>,
+	// client has disconnected
+	finished: bool,
+	// events to send after the current response
+	events: Vec<(&'static str, Value)>,
+	// sequence number of the next message sent
+	seq: i64,
+}
+
+impl Default for Session {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Session {
+	// new constructor for Session class - nothing is launched yet
+	pub fn new() -> Self {
+		Self {
+			program: None,
+			debugger: Debugger::new(),
+			breakpoints: HashMap::new(),
+			next_breakpoint_id: 1,
+			configured: false,
+			stop_on_entry: false,
+			mode: Mode::Starting,
+			stopped_at: None,
+			finished: false,
+			events: Vec::new(),
+			seq: 1,
+		}
+	}
+
+	// whether run_frame needs calling
+	pub fn is_running(&self) -> bool {
+		matches!(self.mode, Mode::Running | Mode::Stepping(_))
+	}
+
+	// client has disconnected, so there's nothing more to do
+	pub fn is_finished(&self) -> bool {
+		self.finished
+	}
+
+	// answer a message from the client, followed by any events it caused
+	pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+		// only requests need an answer
+		if message["type"] != "request" {
+			return Vec::new();
+		}
+		let command: &str = message["command"].as_str().unwrap_or("");
+		let arguments: &Value = &message["arguments"];
+
+		let result: Result<Value, String> = match command {
+			"initialize" => {
+				self.queue_event("initialized", json!({}));
+				Ok(json!({
+					"supportsConfigurationDoneRequest": true,
+					"supportsTerminateRequest": true,
+				}))
+			},
+			"launch" => self.launch(arguments),
+			"setBreakpoints" => self.set_breakpoints(arguments),
+			"configurationDone" => {
+				self.configured = true;
+				self.start();
+				Ok(json!({}))
+			},
+			"threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+			"stackTrace" => self.stack_trace(),
+			"scopes" => Ok(json!({ "scopes": [
+				{ "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+				{ "name": "Timers", "variablesReference": TIMERS_REFERENCE, "expensive": false },
+			] })),
+			"variables" => self.variables(arguments),
+			"continue" => self.resume().map(|_| json!({ "allThreadsContinued": true })),
+			"next" => self.start_step(StepKind::Over),
+			"stepIn" => self.start_step(StepKind::In),
+			"stepOut" => self.start_step(StepKind::Out),
+			"pause" => {
+				if self.is_running() {
+					self.stopped(None);
+				}
+				Ok(json!({}))
+			},
+			"terminate" => {
+				self.mode = Mode::Exited;
+				self.queue_event("terminated", json!({}));
+				Ok(json!({}))
+			},
+			"disconnect" => {
+				self.finished = true;
+				Ok(json!({}))
+			},
+			_ => Err(format!("unsupported request '{}'", command)),
+		};
+
+		let response: Value = match result {
+			Ok(body) => json!({
+				"type": "response",
+				"request_seq": message["seq"],
+				"success": true,
+				"command": command,
+				"body": body,
+			}),
+			Err(error) => json!({
+				"type": "response",
+				"request_seq": message["seq"],
+				"success": false,
+				"command": command,
+				"message": error,
+			}),
+		};
+
+		let mut messages: Vec<Value> = vec![self.number(response)];
+		messages.extend(self.take_events());
+		messages
+	}
+
+	// run a frame of the game, if it's running, and say if it stopped
+	pub fn run_frame(&mut self) -> Vec<Value> {
+		let Some(program) = self.program.as_mut() else {
+			return Vec::new();
+		};
+		let max_ticks: usize = program.emulator.get_profile().ticks_per_frame;
+
+		let reason: Option<StopReason> = match &self.mode {
+			Mode::Running => match self.debugger.run(&mut program.emulator, max_ticks) {
+				StopReason::WaitingForFrame | StopReason::TickLimit => None,
+				reason => Some(reason),
+			},
+			Mode::Stepping(step) => program.step(&self.debugger, step, max_ticks),
+			_ => return Vec::new(),
+		};
+		program.emulator.tick_timers();
+
+		if let Some(reason) = reason {
+			self.stopped(Some(reason));
+		}
+		self.take_events()
+	}
+
+	// compile or load the program, ready to start once configuration is done
+	// arguments are program (.8o for Octo, .asm or .s for the assembler,
+	// anything else is a ROM), and optionally platform and stopOnEntry
+	fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+		let program: &str = arguments["program"].as_str().ok_or("launch needs a program")?;
+		let profile: Profile = match arguments["platform"].as_str() {
+			Some(name) => name.parse::<Platform>()?.profile(),
+			None => Profile::default(),
+		};
+
+		let path: &Path = Path::new(program);
+		let assembly: Option<Assembly> = match path.extension().and_then(|extension| extension.to_str()) {
+			Some("8o") => Some(octo::compile_file(path).map_err(|error| error.to_string())?),
+			Some("asm") | Some("s") => Some(assembler::assemble_file(path).map_err(|error| error.to_string())?),
+			_ => None,
+		};
+
+		let mut emulator: Emulator = Emulator::new(profile).map_err(|error| error.to_string())?;
+		let loaded = match &assembly {
+			Some(assembly) => emulator.load_rom_at(assembly.base, &assembly.bytes),
+			None => {
+				let rom: Vec<u8> = fs::read(path).map_err(|error| format!("couldn't read {}: {}", program, error))?;
+				emulator.load_rom(&rom)
+			},
+		};
+		loaded.map_err(|error| error.to_string())?;
+
+		let mut sources: HashMap<String, PathBuf> = HashMap::new();
+		for entry in assembly.iter().flat_map(|assembly| assembly.source_map.iter()) {
+			sources.entry(entry.location.file.clone()).or_insert_with(|| canonical(&entry.location.file));
+		}
+
+		self.program = Some(Program { emulator, assembly, sources });
+		self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+
+		// breakpoints set before launch can be checked now
+		self.update_breakpoints();
+		let changed: Vec<Value> = self.breakpoints.iter()
+			.flat_map(|(path, lines)| lines.iter().map(move |&(id, line)| (path, id, line)))
+			.map(|(path, id, line)| self.breakpoint_json(path, id, line))
+			.collect();
+		for breakpoint in changed {
+			self.queue_event("breakpoint", json!({ "reason": "changed", "breakpoint": breakpoint }));
+		}
+
+		self.start();
+		Ok(json!({}))
+	}
+
+	// every breakpoint in one source file, replacing the ones there before
+	fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+		let path: &str = arguments["source"]["path"].as_str().ok_or("setBreakpoints needs a source path")?;
+		let path: PathBuf = canonical(path);
+
+		let lines: Vec<usize> = arguments["breakpoints"].as_array()
+			.map(|breakpoints| breakpoints.iter().filter_map(|breakpoint| breakpoint["line"].as_u64()).map(|line| line as usize).collect())
+			.unwrap_or_default();
+		let mut entries: Vec<(i64, usize)> = Vec::with_capacity(lines.len());
+		for line in lines {
+			entries.push((self.next_breakpoint_id, line));
+			self.next_breakpoint_id += 1;
+		}
+
+		let breakpoints: Vec<Value> = entries.iter().map(|&(id, line)| self.breakpoint_json(&path, id, line)).collect();
+		self.breakpoints.insert(path, entries);
+		self.update_breakpoints();
+
+		Ok(json!({ "breakpoints": breakpoints }))
+	}
+
+	// put every breakpoint that's on a line with code into the debugger
+	fn update_breakpoints(&mut self) {
+		self.debugger.clear_breakpoints();
+
+		let Some(program) = self.program.as_ref() else {
+			return;
+		};
+		for (path, lines) in self.breakpoints.iter() {
+			for &(_, line) in lines.iter() {
+				if let Some((address, _)) = program.resolve_line(path, line) {
+					self.debugger.add_breakpoint(address);
+				}
+			}
+		}
+	}
+
+	// DAP breakpoint - moved down to the next line with code if there's none on its own
+	fn breakpoint_json(&self, path: &Path, id: i64, line: usize) -> Value {
+		let Some(program) = self.program.as_ref() else {
+			return json!({ "id": id, "verified": false, "line": line, "message": "the program hasn't been launched yet" });
+		};
+
+		match program.resolve_line(path, line) {
+			Some((_, line)) => json!({ "id": id, "verified": true, "line": line }),
+			None => json!({ "id": id, "verified": false, "line": line, "message": "there's no code on or after this line" }),
+		}
+	}
+
+	// start the game once it's launched and the client has finished configuring
+	fn start(&mut self) {
+		if self.program.is_none() || !self.configured || self.mode != Mode::Starting {
+			return;
+		}
+
+		if self.stop_on_entry {
+			self.mode = Mode::Stopped;
+			self.queue_event("stopped", json!({ "reason": "entry", "threadId": THREAD_ID, "allThreadsStopped": true }));
+		} else {
+			self.run();
+		}
+	}
+
+	fn resume(&mut self) -> Result<(), String> {
+		self.check_started()?;
+		self.run();
+		Ok(())
+	}
+
+	// let the game run - the debugger only checks breakpoints after each
+	// instruction, so one on the instruction about to run is checked here
+	fn run(&mut self) {
+		let hit: Option<u16> = self.program.as_ref()
+			.filter(|program| self.debugger.is_breakpoint_hit(&program.emulator))
+			.map(|program| program.emulator.get_pc())
+			.filter(|&address| self.stopped_at != Some(address));
+
+		match hit {
+			Some(address) => self.stopped(Some(StopReason::Breakpoint { address })),
+			None => self.mode = Mode::Running,
+		}
+	}
+
+	fn start_step(&mut self, kind: StepKind) -> Result<Value, String> {
+		self.check_started()?;
+		let program: &Program = self.program.as_ref().ok_or("the program hasn't been launched yet")?;
+
+		self.mode = Mode::Stepping(Step {
+			kind,
+			location: program.location_of(program.emulator.get_pc()).cloned(),
+			stack_pointer: program.emulator.get_stack_pointer(),
+		});
+		Ok(json!({}))
+	}
+
+	// the game can only be continued or stepped between starting and exiting
+	fn check_started(&self) -> Result<(), String> {
+		match self.mode {
+			Mode::Stopped | Mode::Running | Mode::Stepping(_) => Ok(()),
+			Mode::Starting => Err(String::from("the program hasn't started yet")),
+			Mode::Exited => Err(String::from("the program has exited")),
+		}
+	}
+
+	// call stack, innermost first - each return address on the stack is just
+	// after the CALL in that frame
+	fn stack_trace(&self) -> Result<Value, String> {
+		let program: &Program = self.program.as_ref().ok_or("the program hasn't been launched yet")?;
+		let emulator: &Emulator = &program.emulator;
+
+		let stack_pointer: usize = (emulator.get_stack_pointer() as usize).min(emulator.get_stack().len());
+		let mut addresses: Vec<u16> = vec![emulator.get_pc()];
+		addresses.extend(emulator.get_stack()[..stack_pointer].iter().rev().map(|&return_address| return_address.wrapping_sub(2)));
+
+		let frames: Vec<Value> = addresses.iter().enumerate().map(|(id, &address)| program.frame(id, address)).collect();
+		Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+	}
+
+	fn variables(&self, arguments: &Value) -> Result<Value, String> {
+		let program: &Program = self.program.as_ref().ok_or("the program hasn't been launched yet")?;
+		let emulator: &Emulator = &program.emulator;
+
+		let variables: Vec<Value> = match arguments["variablesReference"].as_i64() {
+			Some(REGISTERS_REFERENCE) => {
+				let mut variables: Vec<Value> = emulator.get_v_registers().iter().enumerate()
+					.map(|(index, &value)| variable(&format!("V{:X}", index), format!("{:#04X} ({})", value, value)))
+					.collect();
+				variables.push(variable("I", format!("{:#06X}", emulator.get_i_register())));
+				variables
+			},
+			Some(TIMERS_REFERENCE) => vec![
+				variable("DT", emulator.get_delay_timer().to_string()),
+				variable("ST", emulator.get_sound_timer().to_string()),
+			],
+			_ => Vec::new(),
+		};
+
+		Ok(json!({ "variables": variables }))
+	}
+
+	// game has stopped running - None is a pause
+	fn stopped(&mut self, reason: Option<StopReason>) {
+		self.mode = Mode::Stopped;
+		self.stopped_at = match reason {
+			Some(StopReason::Breakpoint { .. }) | Some(StopReason::Step) => self.program.as_ref().map(|program| program.emulator.get_pc()),
+			_ => None,
+		};
+
+		let (name, text): (&str, Option<String>) = match reason {
+			None => ("pause", None),
+			Some(StopReason::Breakpoint { .. }) => ("breakpoint", None),
+			Some(StopReason::Watchpoint { .. }) => ("data breakpoint", None),
+			Some(StopReason::Error(error)) => ("exception", Some(error.to_string())),
+			Some(StopReason::Halted) => {
+				self.mode = Mode::Exited;
+				self.queue_event("exited", json!({ "exitCode": 0 }));
+				self.queue_event("terminated", json!({}));
+				return;
+			},
+			Some(_) => ("step", None),
+		};
+
+		let mut body: Value = json!({ "reason": name, "threadId": THREAD_ID, "allThreadsStopped": true });
+		if let Some(text) = text {
+			body["description"] = json!(text);
+			body["text"] = json!(text);
+		}
+		self.queue_event("stopped", body);
+	}
+
+	fn queue_event(&mut self, name: &'static str, body: Value) {
+		self.events.push((name, body));
+	}
+
+	// queued events, numbered in the order they're sent
+	fn take_events(&mut self) -> Vec<Value> {
+		let events: Vec<(&'static str, Value)> = std::mem::take(&mut self.events);
+		events.into_iter()
+			.map(|(name, body)| self.number(json!({ "type": "event", "event": name, "body": body })))
+			.collect()
+	}
+
+	// give a message to send its sequence number
+	fn number(&mut self, mut message: Value) -> Value {
+		message["seq"] = json!(self.seq);
+		self.seq += 1;
+		message
+	}
+}
+
+// paths from the client and from the source map only match once they're canonical
+fn canonical(path: &str) -> PathBuf {
+	fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+fn source_json(name: &str, path: Option<&PathBuf>) -> Value {
+	let file_name: String = Path::new(name).file_name().map_or_else(|| name.to_string(), |file_name| file_name.to_string_lossy().into_owned());
+	json!({
+		"name": file_name,
+		"path": path.map_or_else(|| name.to_string(), |path| path.display().to_string()),
+	})
+}
+
+fn variable(name: &str, value: String) -> Value {
+	json!({ "name": name, "value": value, "variablesReference": 0 })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const COUNTER: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/counter.8o");
+	const ENTRY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/entry.asm");
+
+	// response to a request, then any events it caused
+	fn request(session: &mut Session, command: &str, arguments: Value) -> Vec<Value> {
+		let messages: Vec<Value> = session.handle(&json!({ "seq": 1, "type": "request", "command": command, "arguments": arguments }));
+		assert_eq!(messages[0]["success"], true, "{}", messages[0]);
+		messages
+	}
+
+	// initialize, set breakpoints and launch, ready for configurationDone
+	fn launch(session: &mut Session, program: &str, lines: &[usize]) -> Vec<Value> {
+		request(session, "initialize", json!({}));
+		let breakpoints: Vec<Value> = lines.iter().map(|line| json!({ "line": line })).collect();
+		request(session, "setBreakpoints", json!({ "source": { "path": program }, "breakpoints": breakpoints }));
+		request(session, "launch", json!({ "program": program }))
+	}
+
+	// body of the stopped event the game stops with
+	fn run_until_stopped(session: &mut Session) -> Value {
+		for _ in 0..100 {
+			assert!(session.is_running());
+			if let Some(event) = session.run_frame().into_iter().find(|message| message["event"] == "stopped") {
+				return event["body"].clone();
+			}
+		}
+		panic!("the game never stopped");
+	}
+
+	// source line of each frame on the call stack, innermost first
+	fn stack_lines(session: &mut Session) -> Vec<u64> {
+		let messages: Vec<Value> = request(session, "stackTrace", json!({ "threadId": THREAD_ID }));
+		messages[0]["body"]["stackFrames"].as_array().unwrap().iter().map(|frame| frame["line"].as_u64().unwrap()).collect()
+	}
+
+	fn register(session: &mut Session, name: &str) -> String {
+		let messages: Vec<Value> = request(session, "variables", json!({ "variablesReference": REGISTERS_REFERENCE }));
+		let variable: &Value = messages[0]["body"]["variables"].as_array().unwrap().iter().find(|variable| variable["name"] == name).unwrap();
+		variable["value"].as_str().unwrap().to_string()
+	}
+
+	#[test]
+	fn breakpoints_and_steps_stop_on_source_lines() {
+		let mut session: Session = Session::new();
+
+		// a breakpoint on a label moves down to the line with code once launched
+		let messages: Vec<Value> = launch(&mut session, COUNTER, &[9]);
+		assert_eq!(messages[1]["event"], "breakpoint");
+		assert_eq!(messages[1]["body"]["breakpoint"]["verified"], true);
+		assert_eq!(messages[1]["body"]["breakpoint"]["line"], 10);
+		assert!(!session.is_running());
+
+		request(&mut session, "configurationDone", json!({}));
+		assert_eq!(run_until_stopped(&mut session)["reason"], "breakpoint");
+		assert_eq!(stack_lines(&mut session), [10, 5]);
+		assert_eq!(register(&mut session, "V0"), "0x00 (0)");
+
+		request(&mut session, "next", json!({ "threadId": THREAD_ID }));
+		assert_eq!(run_until_stopped(&mut session)["reason"], "step");
+		assert_eq!(stack_lines(&mut session), [11, 5]);
+		assert_eq!(register(&mut session, "V0"), "0x01 (1)");
+
+		request(&mut session, "stepIn", json!({ "threadId": THREAD_ID }));
+		assert_eq!(run_until_stopped(&mut session)["reason"], "step");
+		assert_eq!(stack_lines(&mut session), [15, 11, 5]);
+
+		request(&mut session, "stepOut", json!({ "threadId": THREAD_ID }));
+		assert_eq!(run_until_stopped(&mut session)["reason"], "step");
+		assert_eq!(stack_lines(&mut session), [12, 5]);
+		assert_eq!(register(&mut session, "V2"), "0x02 (2)");
+
+		// next over a return comes back out to the caller
+		request(&mut session, "next", json!({ "threadId": THREAD_ID }));
+		assert_eq!(run_until_stopped(&mut session)["reason"], "step");
+		assert_eq!(stack_lines(&mut session), [6]);
+
+		request(&mut session, "continue", json!({ "threadId": THREAD_ID }));
+		assert_eq!(run_until_stopped(&mut session)["reason"], "breakpoint");
+		assert_eq!(stack_lines(&mut session), [10, 5]);
+		assert_eq!(register(&mut session, "V0"), "0x01 (1)");
+	}
+
+	#[test]
+	fn breakpoint_on_the_entry_instruction_is_hit() {
+		let mut session: Session = Session::new();
+		launch(&mut session, ENTRY, &[2]);
+
+		// nothing has run yet
+		let messages: Vec<Value> = request(&mut session, "configurationDone", json!({}));
+		assert_eq!(messages[1]["event"], "stopped");
+		assert_eq!(messages[1]["body"]["reason"], "breakpoint");
+		assert_eq!(stack_lines(&mut session), [2]);
+		assert_eq!(register(&mut session, "V0"), "0x00 (0)");
+
+		// continuing goes past it, and stops on it again next time round
+		let messages: Vec<Value> = request(&mut session, "continue", json!({ "threadId": THREAD_ID }));
+		assert_eq!(messages.len(), 1);
+		assert_eq!(run_until_stopped(&mut session)["reason"], "breakpoint");
+		assert_eq!(stack_lines(&mut session), [2]);
+		assert_eq!(register(&mut session, "V0"), "0x01 (1)");
+	}
+}
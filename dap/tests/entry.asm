@@ -0,0 +1,3 @@
+; the first instruction is the first one run, so nothing runs before it
+start:  LD V0, 1
+        JP start
@@ -0,0 +1,46 @@
+// crates
+use backend::{AudioSettings, Emulator, ToneGenerator};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+// SDL calls this from its audio thread whenever it needs more samples
+pub struct Beeper {
+	generator: ToneGenerator,
+}
+
+impl AudioCallback for Beeper {
+	type Channel = f32;
+
+	fn callback(&mut self, samples: &mut [f32]) {
+		self.generator.fill(samples);
+	}
+}
+
+// open the default output device and start it playing (silence until the
+// sound timer is set) - None if there's no audio device
+pub fn open_beeper(sdl_context: &sdl2::Sdl, settings: AudioSettings) -> Option<AudioDevice<Beeper>> {
+	let audio_subsystem = sdl_context.audio().ok()?;
+
+	// mono, and let SDL pick the buffer size
+	let desired_spec = AudioSpecDesired {
+		freq: Some(settings.sample_rate as i32),
+		channels: Some(1),
+		samples: None,
+	};
+
+	let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+		// the device might not support the rate asked for
+		let device_settings = AudioSettings {
+			sample_rate: spec.freq as u32,
+			..settings
+		};
+		Beeper { generator: ToneGenerator::new(device_settings) }
+	}).ok()?;
+
+	device.resume();
+	Some(device)
+}
+
+// pass the emulator's sound state across to the audio thread - once a frame
+pub fn update_beeper(device: &mut AudioDevice<Beeper>, emulator: &Emulator) {
+	device.lock().generator.update(emulator);
+}
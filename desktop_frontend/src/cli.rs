@@ -0,0 +1,274 @@
+// crates
+use std::str::FromStr;
+
+use backend::Platform;
+
+use crate::keymap::Preset;
+use crate::palette::{self, Palette};
+
+// printed for help, or when the arguments don't make sense
+pub const USAGE: &str = "\
+Usage: cargo run -- [run] [options] path_to_game
+       cargo run -- disasm path_to_game
+       cargo run -- info path_to_game
+       cargo run -- trace [--frames n] [--speed n] [--platform name] [--seed n] [--output path] path_to_game
+
+Options for run:
+  --platform name   platform the game was written for: cosmac-vip, chip-48,
+                    schip-legacy, schip-modern or xo-chip
+  --speed n         instructions per frame, instead of the platform's
+  --scale n         window pixels for each lo-res pixel (default 20)
+  --palette name    mono, amber, green or octo, or 4 colours like
+                    000000,FFFFFF,AAAAAA,555555
+  --fullscreen      fill the screen
+  --windowed        play in a window, even if the config file says fullscreen
+  --mute            no sound
+  --sound           play sound, even if the config file says mute
+  --keyboard name   keyboard layout for the keypad: qwerty, azerty, qwertz,
+                    dvorak or numpad
+  --seed n          random number seed, so the game plays the same every time
+  --gdb port        let GDB connect on localhost
+  --config path     settings file to read instead of config.toml in the
+                    user's config directory";
+
+// frames traced when --frames isn't given - a second
+const DEFAULT_TRACE_FRAMES: u64 = 60;
+
+// what to do with the game
+pub enum Command {
+	// play it in a window
+	Run(RunOptions),
+	// list its instructions
+	Disasm { game_path: String },
+	// print its size, hash and which platform it looks like it's for
+	Info { game_path: String },
+	// run it without a window, writing a line for every instruction
+	Trace(TraceOptions),
+	Help,
+}
+
+// anything not given is left as None, to use the config file or the default
+#[derive(Default)]
+pub struct RunOptions {
+	pub game_path: String,
+	pub platform: Option<Platform>,
+	pub speed: Option<usize>,
+	pub scale: Option<u32>,
+	pub palette: Option<Palette>,
+	pub fullscreen: Option<bool>,
+	pub mute: Option<bool>,
+	pub keyboard: Option<Preset>,
+	pub seed: Option<u64>,
+	pub gdb_port: Option<u16>,
+	pub config_path: Option<String>,
+}
+
+pub struct TraceOptions {
+	pub game_path: String,
+	pub platform: Option<Platform>,
+	pub speed: Option<usize>,
+	pub seed: Option<u64>,
+	pub frames: u64,
+	// None writes the trace to stdout
+	pub output_path: Option<String>,
+}
+
+// a subcommand, then options in any order and exactly one game path
+// with no subcommand, the game is run, as it always has been
+pub fn parse_arguments(arguments: &[String]) -> Result<Command, String> {
+	let mut remaining = arguments.iter().skip(1).peekable();
+
+	let subcommand: &str = match remaining.peek().map(|argument| argument.as_str()) {
+		Some(name @ ("run" | "disasm" | "info" | "trace")) => {
+			remaining.next();
+			name
+		},
+		Some("help" | "--help" | "-h") => return Ok(Command::Help),
+		_ => "run",
+	};
+	// options each subcommand takes
+	let allowed_options: &[&str] = match subcommand {
+		"run" => &["--platform", "--speed", "--scale", "--palette", "--fullscreen", "--windowed", "--mute", "--sound", "--keyboard", "--seed", "--gdb", "--config"],
+		"trace" => &["--platform", "--speed", "--seed", "--frames", "--output"],
+		_ => &[],
+	};
+
+	// every option is collected here, then sorted into the subcommand's
+	let mut run_options: RunOptions = RunOptions::default();
+	let mut frames: u64 = DEFAULT_TRACE_FRAMES;
+	let mut output_path: Option<String> = None;
+	let mut game_path: Option<&String> = None;
+
+	while let Some(argument) = remaining.next() {
+		if argument == "--help" || argument == "-h" {
+			return Ok(Command::Help);
+		}
+		if !argument.starts_with("--") {
+			if game_path.is_some() {
+				return Err(String::from(USAGE));
+			}
+			game_path = Some(argument);
+			continue;
+		}
+		if !allowed_options.contains(&argument.as_str()) {
+			return Err(format!("{} doesn't take {}\n\n{}", subcommand, argument, USAGE));
+		}
+
+		let mut value = || remaining.next().ok_or_else(|| format!("{} needs a value", argument));
+		match argument.as_str() {
+			"--platform" => run_options.platform = Some(value()?.parse()?),
+			"--speed" => run_options.speed = Some(parse_positive(argument, value()?)?),
+			"--scale" => run_options.scale = Some(parse_positive(argument, value()?)?),
+			"--palette" => run_options.palette = Some(palette::parse_palette(value()?)?),
+			"--fullscreen" => run_options.fullscreen = Some(true),
+			"--windowed" => run_options.fullscreen = Some(false),
+			"--mute" => run_options.mute = Some(true),
+			"--sound" => run_options.mute = Some(false),
+			"--keyboard" => run_options.keyboard = Some(value()?.parse()?),
+			"--seed" => run_options.seed = Some(parse_number(argument, value()?)?),
+			"--gdb" => run_options.gdb_port = Some(parse_number(argument, value()?)?),
+			"--config" => run_options.config_path = Some(value()?.clone()),
+			"--frames" => frames = parse_number(argument, value()?)?,
+			"--output" => output_path = Some(value()?.clone()),
+			_ => return Err(String::from(USAGE)),
+		}
+	}
+	let game_path: String = game_path.ok_or(USAGE)?.clone();
+
+	Ok(match subcommand {
+		"disasm" => Command::Disasm { game_path },
+		"info" => Command::Info { game_path },
+		"trace" => Command::Trace(TraceOptions {
+			game_path,
+			platform: run_options.platform,
+			speed: run_options.speed,
+			seed: run_options.seed,
+			frames,
+			output_path,
+		}),
+		_ => Command::Run(RunOptions { game_path, ..run_options }),
+	})
+}
+
+fn parse_number<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+	value.parse().map_err(|_| format!("{} needs a number, not '{}'", option, value))
+}
+
+// speed and scale can't be 0
+fn parse_positive<T: FromStr + Default + PartialEq>(option: &str, value: &str) -> Result<T, String> {
+	match parse_number(option, value)? {
+		number if number == T::default() => Err(format!("{} can't be 0", option)),
+		number => Ok(number),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(arguments: &[&str]) -> Result<Command, String> {
+		let arguments: Vec<String> = std::iter::once("chip8").chain(arguments.iter().copied()).map(String::from).collect();
+		parse_arguments(&arguments)
+	}
+
+	fn parse_run(arguments: &[&str]) -> RunOptions {
+		match parse(arguments) {
+			Ok(Command::Run(options)) => options,
+			_ => panic!("{:?} isn't a run command", arguments),
+		}
+	}
+
+	#[test]
+	fn no_subcommand_runs_the_game() {
+		let options: RunOptions = parse_run(&["game.ch8"]);
+		assert_eq!(options.game_path, "game.ch8");
+		assert_eq!(options.platform, None);
+		assert_eq!(options.speed, None);
+		assert_eq!(options.fullscreen, None);
+		assert_eq!(options.mute, None);
+
+		assert_eq!(parse_run(&["--mute", "game.ch8"]).mute, Some(true));
+		assert_eq!(parse_run(&["run", "game.ch8"]).game_path, "game.ch8");
+	}
+
+	#[test]
+	fn options_in_any_order() {
+		for arguments in [
+			["--speed", "12", "game.ch8", "--platform", "xo-chip", "--scale", "4"],
+			["game.ch8", "--scale", "4", "--speed", "12", "--platform", "xo-chip"],
+			["--platform", "xo-chip", "--scale", "4", "--speed", "12", "game.ch8"],
+		] {
+			let options: RunOptions = parse_run(&arguments);
+			assert_eq!(options.game_path, "game.ch8");
+			assert_eq!(options.platform, Some(Platform::XoChip));
+			assert_eq!(options.speed, Some(12));
+			assert_eq!(options.scale, Some(4));
+		}
+
+		let Ok(Command::Trace(options)) = parse(&["trace", "--output", "out.txt", "game.ch8", "--frames", "5", "--seed", "3"]) else {
+			panic!("not a trace command");
+		};
+		assert_eq!(options.game_path, "game.ch8");
+		assert_eq!(options.frames, 5);
+		assert_eq!(options.seed, Some(3));
+		assert_eq!(options.output_path.as_deref(), Some("out.txt"));
+	}
+
+	#[test]
+	fn trace_frames_default_to_a_second() {
+		let Ok(Command::Trace(options)) = parse(&["trace", "game.ch8"]) else {
+			panic!("not a trace command");
+		};
+		assert_eq!(options.frames, DEFAULT_TRACE_FRAMES);
+		assert_eq!(options.output_path, None);
+	}
+
+	#[test]
+	fn subcommands_reject_options_they_dont_take() {
+		assert_eq!(parse(&["disasm", "--speed", "10", "game.ch8"]).err(), Some(format!("disasm doesn't take --speed\n\n{}", USAGE)));
+		assert_eq!(parse(&["info", "--platform", "xo-chip", "game.ch8"]).err(), Some(format!("info doesn't take --platform\n\n{}", USAGE)));
+		assert_eq!(parse(&["trace", "--fullscreen", "game.ch8"]).err(), Some(format!("trace doesn't take --fullscreen\n\n{}", USAGE)));
+		assert_eq!(parse(&["--frames", "10", "game.ch8"]).err(), Some(format!("run doesn't take --frames\n\n{}", USAGE)));
+	}
+
+	#[test]
+	fn speed_and_scale_cant_be_0() {
+		assert_eq!(parse(&["--speed", "0", "game.ch8"]).err(), Some(String::from("--speed can't be 0")));
+		assert_eq!(parse(&["--scale", "0", "game.ch8"]).err(), Some(String::from("--scale can't be 0")));
+		assert_eq!(parse(&["trace", "--speed", "0", "game.ch8"]).err(), Some(String::from("--speed can't be 0")));
+	}
+
+	#[test]
+	fn bad_values_are_errors() {
+		assert_eq!(parse(&["game.ch8", "--speed"]).err(), Some(String::from("--speed needs a value")));
+		assert_eq!(parse(&["trace", "game.ch8", "--output"]).err(), Some(String::from("--output needs a value")));
+		assert_eq!(parse(&["--seed", "lots", "game.ch8"]).err(), Some(String::from("--seed needs a number, not 'lots'")));
+		assert!(parse(&["--platform", "nes", "game.ch8"]).is_err());
+	}
+
+	#[test]
+	fn exactly_one_game_path() {
+		assert_eq!(parse(&["one.ch8", "two.ch8"]).err(), Some(String::from(USAGE)));
+		assert_eq!(parse(&["disasm", "one.ch8", "two.ch8"]).err(), Some(String::from(USAGE)));
+		assert_eq!(parse(&[]).err(), Some(String::from(USAGE)));
+		assert_eq!(parse(&["info"]).err(), Some(String::from(USAGE)));
+	}
+
+	#[test]
+	fn later_flags_override_earlier_ones() {
+		let options: RunOptions = parse_run(&["--fullscreen", "--mute", "game.ch8", "--windowed", "--sound"]);
+		assert_eq!(options.fullscreen, Some(false));
+		assert_eq!(options.mute, Some(false));
+
+		let options: RunOptions = parse_run(&["--windowed", "--sound", "--fullscreen", "--mute", "game.ch8"]);
+		assert_eq!(options.fullscreen, Some(true));
+		assert_eq!(options.mute, Some(true));
+	}
+
+	#[test]
+	fn help_anywhere() {
+		assert!(matches!(parse(&["help"]), Ok(Command::Help)));
+		assert!(matches!(parse(&["-h"]), Ok(Command::Help)));
+		assert!(matches!(parse(&["trace", "game.ch8", "--help"]), Ok(Command::Help)));
+	}
+}
@@ -0,0 +1,413 @@
+// the config file - TOML in the user's config directory, e.g.
+// ~/.config/chip8-emulator/config.toml on Linux
+//
+//     scale = 10
+//     palette = "amber"
+//     keyboard = "azerty"
+//
+//     [audio]
+//     waveform = "triangle"
+//
+//     [keymap]
+//     5 = ["W", "Up"]
+//
+//     [gamepad]
+//     layout = "wasd"
+//
+//     # a game's own settings, under the SHA-1 of its ROM (see the info command)
+//     [roms.0123456789abcdef0123456789abcdef01234567]
+//     platform = "schip-legacy"
+//     speed = 50
+//
+//     [roms.0123456789abcdef0123456789abcdef01234567.gamepad]
+//     a = "F"
+//
+// everything's optional - a game's settings go on top of the general ones,
+// and command line options go on top of both
+
+// crates
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use backend::{AudioSettings, Platform, Profile, Quirks, Waveform};
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+
+use crate::gamepad::GamepadLayout;
+use crate::keymap::Preset;
+use crate::palette::{self, Palette};
+
+// folder in the config directory
+const CONFIG_FOLDER: &str = "chip8-emulator";
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Default)]
+pub struct Config {
+	// settings for every game
+	pub settings: Settings,
+	// settings for particular games, by the SHA-1 of the ROM
+	pub roms: HashMap<String, Settings>,
+}
+
+// everything that can be set - None (or empty) leaves it as it was
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+	#[serde(deserialize_with = "parse_field")]
+	pub platform: Option<Platform>,
+	// instructions per frame, instead of the platform's
+	pub speed: Option<usize>,
+	pub scale: Option<u32>,
+	#[serde(deserialize_with = "parse_palette_field")]
+	pub palette: Option<Palette>,
+	pub fullscreen: Option<bool>,
+	pub mute: Option<bool>,
+	// changes to the platform's quirks
+	pub quirks: QuirkSettings,
+	pub audio: AudioOverrides,
+	// layout the keymap starts from
+	#[serde(deserialize_with = "parse_field")]
+	pub keyboard: Option<Preset>,
+	// Chip-8 key (hex digit) to the keyboard keys that press it, instead of the layout's
+	pub keymap: BTreeMap<String, KeyNames>,
+	pub gamepad: GamepadOverrides,
+}
+
+// one keyboard key name, or a list of them
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum KeyNames {
+	One(String),
+	Many(Vec<String>),
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuirkSettings {
+	pub shift: Option<bool>,
+	pub load_store_increment: Option<bool>,
+	pub vf_reset: Option<bool>,
+	pub jump_with_vx: Option<bool>,
+	pub clipping: Option<bool>,
+	pub display_wait: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioOverrides {
+	// pitch of the beep in Hz
+	pub frequency: Option<f32>,
+	#[serde(deserialize_with = "parse_field")]
+	pub waveform: Option<Waveform>,
+	// 0.0 is silent, 1.0 is full scale
+	pub volume: Option<f32>,
+}
+
+// gamepad controls - anything that isn't layout or deadzone is a control name
+// with the Chip-8 key it presses (a hex digit, or a number 0 to 15), or "none"
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct GamepadOverrides {
+	// where the D-pad and left stick go
+	#[serde(deserialize_with = "parse_field")]
+	pub layout: Option<GamepadLayout>,
+	// how far sticks and triggers have to go, 0.0 to 1.0
+	pub deadzone: Option<f32>,
+	#[serde(flatten, deserialize_with = "parse_controls")]
+	pub controls: BTreeMap<String, String>,
+}
+
+// the config file in the user's config directory - not having one is the same as an empty one
+pub fn load_default() -> Result<Config, String> {
+	match dirs::config_dir() {
+		Some(directory) => {
+			let path: PathBuf = directory.join(CONFIG_FOLDER).join(CONFIG_FILE);
+			match fs::read_to_string(&path) {
+				Ok(text) => parse(&text, &path),
+				Err(error) if error.kind() == ErrorKind::NotFound => Ok(Config::default()),
+				Err(error) => Err(format!("Unable to read {}: {}", path.display(), error)),
+			}
+		},
+		None => Ok(Config::default()),
+	}
+}
+
+// a config file asked for by name, which has to be there
+pub fn load(path: &Path) -> Result<Config, String> {
+	let text: String = fs::read_to_string(path).map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+	parse(&text, path)
+}
+
+fn parse(text: &str, path: &Path) -> Result<Config, String> {
+	let in_file = |message: String| format!("Error in {}: {}", path.display(), message);
+	let mut table: toml::Table = toml::from_str(text).map_err(|error: toml::de::Error| in_file(error.to_string()))?;
+
+	// games' settings are taken out first, so anything left that isn't a setting is a mistake
+	let mut roms: HashMap<String, Settings> = HashMap::new();
+	match table.remove("roms") {
+		Some(toml::Value::Table(rom_tables)) => {
+			for (hash, rom_table) in rom_tables {
+				let settings: Settings = rom_table.try_into()
+					.map_err(|error: toml::de::Error| in_file(format!("[roms.{}]: {}", hash, error.message())))?;
+				roms.insert(hash, settings);
+			}
+		},
+		Some(_) => return Err(in_file(String::from("roms should be a table of games' settings"))),
+		None => (),
+	}
+
+	let settings: Settings = toml::Value::Table(table).try_into().map_err(|error: toml::de::Error| in_file(error.message().to_string()))?;
+	Ok(Config { settings, roms })
+}
+
+impl Config {
+	// settings for a game - its own on top of the general ones
+	pub fn settings_for(&self, rom_hash: &str) -> Settings {
+		let mut settings: Settings = self.settings.clone();
+
+		let game_settings = self.roms.iter().find(|(hash, _)| hash.eq_ignore_ascii_case(rom_hash));
+		if let Some((_, game_settings)) = game_settings {
+			settings.merge(game_settings.clone());
+		}
+
+		settings
+	}
+}
+
+impl Settings {
+	// anything set in other replaces what's set here
+	pub fn merge(&mut self, other: Settings) {
+		self.platform = other.platform.or(self.platform);
+		self.speed = other.speed.or(self.speed);
+		self.scale = other.scale.or(self.scale);
+		self.palette = other.palette.or(self.palette);
+		self.fullscreen = other.fullscreen.or(self.fullscreen);
+		self.mute = other.mute.or(self.mute);
+		self.quirks.merge(other.quirks);
+		self.audio.merge(other.audio);
+		self.keyboard = other.keyboard.or(self.keyboard);
+		self.keymap.extend(other.keymap);
+		self.gamepad.merge(other.gamepad);
+	}
+
+	// the platform's profile (or the emulator's own defaults), with the quirks and speed changed
+	pub fn profile(&self) -> Profile {
+		let mut profile: Profile = self.platform.map(|platform| platform.profile()).unwrap_or_default();
+		self.quirks.apply(&mut profile.quirks);
+		if let Some(speed) = self.speed {
+			profile.ticks_per_frame = speed;
+		}
+		profile
+	}
+
+	pub fn audio_settings(&self) -> AudioSettings {
+		let defaults: AudioSettings = AudioSettings::default();
+
+		AudioSettings {
+			frequency: self.audio.frequency.unwrap_or(defaults.frequency),
+			waveform: self.audio.waveform.unwrap_or(defaults.waveform),
+			volume: self.audio.volume.unwrap_or(defaults.volume),
+			..defaults
+		}
+	}
+}
+
+impl QuirkSettings {
+	fn merge(&mut self, other: QuirkSettings) {
+		self.shift = other.shift.or(self.shift);
+		self.load_store_increment = other.load_store_increment.or(self.load_store_increment);
+		self.vf_reset = other.vf_reset.or(self.vf_reset);
+		self.jump_with_vx = other.jump_with_vx.or(self.jump_with_vx);
+		self.clipping = other.clipping.or(self.clipping);
+		self.display_wait = other.display_wait.or(self.display_wait);
+	}
+
+	fn apply(&self, quirks: &mut Quirks) {
+		quirks.shift = self.shift.unwrap_or(quirks.shift);
+		quirks.load_store_increment = self.load_store_increment.unwrap_or(quirks.load_store_increment);
+		quirks.vf_reset = self.vf_reset.unwrap_or(quirks.vf_reset);
+		quirks.jump_with_vx = self.jump_with_vx.unwrap_or(quirks.jump_with_vx);
+		quirks.clipping = self.clipping.unwrap_or(quirks.clipping);
+		quirks.display_wait = self.display_wait.unwrap_or(quirks.display_wait);
+	}
+}
+
+impl KeyNames {
+	pub fn as_slice(&self) -> &[String] {
+		match self {
+			KeyNames::One(name) => std::slice::from_ref(name),
+			KeyNames::Many(names) => names,
+		}
+	}
+}
+
+impl GamepadOverrides {
+	fn merge(&mut self, other: GamepadOverrides) {
+		self.layout = other.layout.or(self.layout);
+		self.deadzone = other.deadzone.or(self.deadzone);
+		self.controls.extend(other.controls);
+	}
+}
+
+impl AudioOverrides {
+	fn merge(&mut self, other: AudioOverrides) {
+		self.frequency = other.frequency.or(self.frequency);
+		self.waveform = other.waveform.or(self.waveform);
+		self.volume = other.volume.or(self.volume);
+	}
+}
+
+// names like platforms, waveforms and keyboards are parsed the same as on the command line
+fn parse_field<'de, D: Deserializer<'de>, T: FromStr<Err = String>>(deserializer: D) -> Result<Option<T>, D::Error> {
+	let text: String = String::deserialize(deserializer)?;
+	text.parse().map(Some).map_err(de::Error::custom)
+}
+
+fn parse_palette_field<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Palette>, D::Error> {
+	let text: String = String::deserialize(deserializer)?;
+	palette::parse_palette(&text).map(Some).map_err(de::Error::custom)
+}
+
+// a bare number is the key's number, so a = 10 is the same as a = "A" - kept
+// as hex text so every control is parsed the same way
+fn parse_controls<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error> {
+	let controls: BTreeMap<String, toml::Value> = BTreeMap::deserialize(deserializer)?;
+
+	controls.into_iter()
+		.map(|(control_name, chip8_key)| match chip8_key {
+			toml::Value::String(chip8_key) => Ok((control_name, chip8_key)),
+			toml::Value::Integer(chip8_key @ 0..=15) => Ok((control_name, format!("{:X}", chip8_key))),
+			_ => Err(de::Error::custom(format!("gamepad control {} should be a Chip-8 key from 0 to F, or \"none\"", control_name))),
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const HASH: &str = "0123456789abcdef0123456789abcdef01234567";
+
+	fn parse_text(text: &str) -> Result<Config, String> {
+		parse(text, Path::new("config.toml"))
+	}
+
+	#[test]
+	fn empty_files_set_nothing() {
+		let config: Config = parse_text("").unwrap();
+		assert_eq!(config.settings.platform, None);
+		assert_eq!(config.settings.speed, None);
+		assert!(config.settings.keymap.is_empty());
+		assert!(config.roms.is_empty());
+	}
+
+	#[test]
+	fn game_settings_go_on_top_of_general_ones() {
+		let config: Config = parse_text(&format!("\
+			platform = \"chip-48\"
+			speed = 20
+			scale = 10
+			[quirks]
+			shift = false
+			clipping = false
+			[gamepad]
+			a = \"1\"
+			b = \"2\"
+			[roms.{}]
+			speed = 50
+			[roms.{}.quirks]
+			shift = true
+			[roms.{}.gamepad]
+			a = \"F\"
+		", HASH, HASH, HASH)).unwrap();
+
+		let settings: Settings = config.settings_for(HASH);
+		// the game's own
+		assert_eq!(settings.speed, Some(50));
+		assert_eq!(settings.quirks.shift, Some(true));
+		assert_eq!(settings.gamepad.controls["a"], "F");
+		// general ones the game doesn't change
+		assert_eq!(settings.platform, Some(Platform::Chip48));
+		assert_eq!(settings.scale, Some(10));
+		assert_eq!(settings.quirks.clipping, Some(false));
+		assert_eq!(settings.gamepad.controls["b"], "2");
+
+		// other games only get the general ones
+		let settings: Settings = config.settings_for("ffffffffffffffffffffffffffffffffffffffff");
+		assert_eq!(settings.speed, Some(20));
+		assert_eq!(settings.quirks.shift, Some(false));
+	}
+
+	#[test]
+	fn game_hashes_match_in_any_case() {
+		let config: Config = parse_text(&format!("[roms.{}]\nspeed = 50\n", HASH.to_uppercase())).unwrap();
+		assert_eq!(config.settings_for(HASH).speed, Some(50));
+		assert_eq!(config.settings_for(&HASH.to_uppercase()).speed, Some(50));
+	}
+
+	#[test]
+	fn command_line_goes_on_top_of_everything() {
+		let config: Config = parse_text(&format!("speed = 20\nmute = true\n[roms.{}]\nspeed = 50\nfullscreen = true\n", HASH)).unwrap();
+
+		let mut settings: Settings = config.settings_for(HASH);
+		settings.merge(Settings { speed: Some(100), fullscreen: Some(false), ..Settings::default() });
+		assert_eq!(settings.speed, Some(100));
+		assert_eq!(settings.fullscreen, Some(false));
+		// not given on the command line
+		assert_eq!(settings.mute, Some(true));
+	}
+
+	#[test]
+	fn merged_settings_shape_the_profile() {
+		let mut settings: Settings = Settings { platform: Some(Platform::XoChip), ..Settings::default() };
+		settings.merge(Settings { speed: Some(7), quirks: QuirkSettings { clipping: Some(true), ..QuirkSettings::default() }, ..Settings::default() });
+
+		let profile: Profile = settings.profile();
+		assert_eq!(profile.ticks_per_frame, 7);
+		assert!(profile.quirks.clipping);
+		assert_eq!(profile.quirks.shift, Platform::XoChip.profile().quirks.shift);
+	}
+
+	#[test]
+	fn unknown_settings_are_errors() {
+		assert!(parse_text("sped = 20").unwrap_err().contains("unknown field `sped`"));
+		assert!(parse_text("[quirks]\nshfit = true").unwrap_err().contains("unknown field `shfit`"));
+		assert!(parse_text("[audio]\npitch = 440").unwrap_err().contains("unknown field `pitch`"));
+
+		let error: String = parse_text(&format!("[roms.{}]\nsped = 20", HASH)).unwrap_err();
+		assert!(error.starts_with(&format!("Error in config.toml: [roms.{}]: ", HASH)));
+		assert!(error.contains("unknown field `sped`"));
+	}
+
+	#[test]
+	fn bad_values_are_errors() {
+		assert!(parse_text("platform = \"nes\"").is_err());
+		assert!(parse_text("speed = \"fast\"").is_err());
+		assert_eq!(parse_text("roms = 5").unwrap_err(), "Error in config.toml: roms should be a table of games' settings");
+	}
+
+	#[test]
+	fn gamepad_keys_can_be_numbers() {
+		let config: Config = parse_text("[gamepad]\nlayout = \"wasd\"\ndeadzone = 0.5\na = 5\nb = 10\nx = \"c\"\ny = \"none\"").unwrap();
+		let gamepad: &GamepadOverrides = &config.settings.gamepad;
+
+		assert_eq!(gamepad.layout, Some(GamepadLayout::Wasd));
+		assert_eq!(gamepad.deadzone, Some(0.5));
+		assert_eq!(gamepad.controls["a"], "5");
+		assert_eq!(gamepad.controls["b"], "A");
+		assert_eq!(gamepad.controls["x"], "c");
+		assert_eq!(gamepad.controls["y"], "none");
+		assert_eq!(gamepad.controls.len(), 4);
+	}
+
+	#[test]
+	fn gamepad_keys_that_arent_keys_are_clear_errors() {
+		for value in ["16", "-1", "true", "1.5", "[1]"] {
+			let error: String = parse_text(&format!("[gamepad]\na = {}", value)).unwrap_err();
+			assert!(error.contains("gamepad control a should be a Chip-8 key from 0 to F, or \"none\""), "{}", error);
+		}
+	}
+}
@@ -0,0 +1,19 @@
+// crates
+use std::fs;
+use std::path::Path;
+
+// Octo source is compiled first, anything else is already a ROM
+pub fn load_game(game_path: &str) -> Result<Vec<u8>, String> {
+	let path: &Path = Path::new(game_path);
+
+	if path.extension().is_some_and(|extension| extension == "8o") {
+		octo::compile_file(path).map(|assembly| assembly.bytes).map_err(|error| format!("Unable to compile: {}", error))
+	} else {
+		fs::read(path).map_err(|error| format!("Unable to open {}: {}", game_path, error))
+	}
+}
+
+// SHA-1 of a ROM in hex - the same as sha1sum gives for the ROM file
+pub fn hash(rom: &[u8]) -> String {
+	sha1_smol::Sha1::from(rom).digest().to_string()
+}
@@ -0,0 +1,318 @@
+// crates
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use backend::Emulator;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+use crate::held_keys::{HeldKeys, Source};
+use crate::keymap;
+
+// how far a stick or trigger has to go to press its key when the settings
+// don't say, from 0.0 (any movement) to 1.0 (all the way)
+pub const DEFAULT_DEADZONE: f32 = 0.3;
+
+// a button, or a stick pushed one way, or a trigger pulled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Control {
+	Button(Button),
+	// true for the positive direction - right, down, or pulled
+	Axis(Axis, bool),
+}
+
+// names used in settings
+const CONTROL_NAMES: [(&str, Control); 25] = [
+	("a", Control::Button(Button::A)),
+	("b", Control::Button(Button::B)),
+	("x", Control::Button(Button::X)),
+	("y", Control::Button(Button::Y)),
+	("back", Control::Button(Button::Back)),
+	("guide", Control::Button(Button::Guide)),
+	("start", Control::Button(Button::Start)),
+	("left_stick_click", Control::Button(Button::LeftStick)),
+	("right_stick_click", Control::Button(Button::RightStick)),
+	("left_shoulder", Control::Button(Button::LeftShoulder)),
+	("right_shoulder", Control::Button(Button::RightShoulder)),
+	("dpad_up", Control::Button(Button::DPadUp)),
+	("dpad_down", Control::Button(Button::DPadDown)),
+	("dpad_left", Control::Button(Button::DPadLeft)),
+	("dpad_right", Control::Button(Button::DPadRight)),
+	("left_stick_up", Control::Axis(Axis::LeftY, false)),
+	("left_stick_down", Control::Axis(Axis::LeftY, true)),
+	("left_stick_left", Control::Axis(Axis::LeftX, false)),
+	("left_stick_right", Control::Axis(Axis::LeftX, true)),
+	("right_stick_up", Control::Axis(Axis::RightY, false)),
+	("right_stick_down", Control::Axis(Axis::RightY, true)),
+	("right_stick_left", Control::Axis(Axis::RightX, false)),
+	("right_stick_right", Control::Axis(Axis::RightX, true)),
+	("left_trigger", Control::Axis(Axis::TriggerLeft, true)),
+	("right_trigger", Control::Axis(Axis::TriggerRight, true)),
+];
+
+// the two ways games usually move - the D-pad and left stick go to these
+// keys, with A and B on the keys those games most often use for fire
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GamepadLayout {
+	// up, left, right, down on 2, 4, 6, 8, like a number pad - A is 5 and B is 0
+	#[default]
+	Numbers,
+	// up, left, down, right on 5, 7, 8, 9, where WASD is on a QWERTY keyboard - A is 6 (E) and B is 4 (Q)
+	Wasd,
+}
+
+impl GamepadLayout {
+	// every layout
+	pub const ALL: [GamepadLayout; 2] = [GamepadLayout::Numbers, GamepadLayout::Wasd];
+
+	// name used in settings
+	pub fn name(&self) -> &'static str {
+		match self {
+			GamepadLayout::Numbers => "2468",
+			GamepadLayout::Wasd => "wasd",
+		}
+	}
+
+	// Chip-8 keys for up, left, right, down, then A and B
+	fn keys(&self) -> [usize; 6] {
+		match self {
+			GamepadLayout::Numbers => [0x2, 0x4, 0x6, 0x8, 0x5, 0x0],
+			GamepadLayout::Wasd => [0x5, 0x7, 0x9, 0x8, 0x6, 0x4],
+		}
+	}
+}
+
+impl fmt::Display for GamepadLayout {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+// parse the name of a layout
+impl FromStr for GamepadLayout {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Self, Self::Err> {
+		GamepadLayout::ALL.iter()
+			.find(|layout| layout.name() == name)
+			.copied()
+			.ok_or_else(|| {
+				let names: Vec<&str> = GamepadLayout::ALL.iter().map(|layout| layout.name()).collect();
+				format!("unknown gamepad layout '{}', expected one of: {}", name, names.join(", "))
+			})
+	}
+}
+
+// which controls press which Chip-8 key
+#[derive(Clone, Debug)]
+pub struct GamepadMap {
+	controls: HashMap<Control, usize>,
+	// how far a stick or trigger has to go, out of i16::MAX
+	threshold: i16,
+}
+
+impl GamepadMap {
+	pub fn new(layout: GamepadLayout, deadzone: f32) -> Self {
+		let [up, left, right, down, a, b] = layout.keys();
+
+		Self {
+			controls: HashMap::from([
+				(Control::Button(Button::DPadUp), up),
+				(Control::Button(Button::DPadLeft), left),
+				(Control::Button(Button::DPadRight), right),
+				(Control::Button(Button::DPadDown), down),
+				(Control::Axis(Axis::LeftY, false), up),
+				(Control::Axis(Axis::LeftX, false), left),
+				(Control::Axis(Axis::LeftX, true), right),
+				(Control::Axis(Axis::LeftY, true), down),
+				(Control::Button(Button::A), a),
+				(Control::Button(Button::B), b),
+			]),
+			// a stick resting slightly off centre mustn't hold a key down
+			threshold: ((deadzone.clamp(0.0, 1.0) * i16::MAX as f32) as i16).max(1),
+		}
+	}
+
+	// Chip-8 key for a control, if it's bound to one
+	pub fn get(&self, control: Control) -> Option<usize> {
+		self.controls.get(&control).copied()
+	}
+
+	// whether an axis at this value has gone past the deadzone one way -
+	// true for the positive direction
+	fn is_pushed(&self, value: i16, positive: bool) -> bool {
+		if positive {
+			value >= self.threshold
+		} else {
+			value <= -self.threshold
+		}
+	}
+
+	// rebind controls from settings - control names to Chip-8 keys as hex
+	// digits, or "none" to unbind one, e.g. a = "4"
+	pub fn apply_settings(&mut self, controls: &BTreeMap<String, String>) -> Result<(), String> {
+		for (control_name, chip8_key) in controls {
+			let control: Control = CONTROL_NAMES.iter()
+				.find(|(name, _)| name == control_name)
+				.map(|(_, control)| *control)
+				.ok_or_else(|| format!("unknown control '{}'", control_name))?;
+
+			if chip8_key == "none" {
+				self.controls.remove(&control);
+			} else {
+				self.controls.insert(control, keymap::parse_chip8_key(chip8_key)?);
+			}
+		}
+		Ok(())
+	}
+}
+
+// every gamepad that's plugged in - they can come and go while the game runs,
+// and all of them press the same keys
+pub struct Gamepads {
+	// controller events stop once this is dropped
+	subsystem: GameControllerSubsystem,
+	// open gamepads by SDL instance id
+	controllers: HashMap<u32, GameController>,
+	map: GamepadMap,
+}
+
+// start listening for gamepads - ones already plugged in arrive as events
+// straight away, like ones plugged in later
+// None if SDL can't use gamepads
+pub fn open_gamepads(sdl_context: &sdl2::Sdl, map: GamepadMap) -> Option<Gamepads> {
+	let subsystem: GameControllerSubsystem = sdl_context.game_controller().ok()?;
+
+	Some(Gamepads {
+		subsystem,
+		controllers: HashMap::new(),
+		map,
+	})
+}
+
+impl Gamepads {
+	// gamepads being plugged in or out, and their buttons and sticks moving
+	// keys are held through held_keys, so the keyboard and every gamepad can
+	// hold the same one
+	pub fn handle_event(&mut self, event: &Event, held_keys: &mut HeldKeys, emulator: &mut Emulator) {
+		match *event {
+			// which is the device index here, not the instance id
+			Event::ControllerDeviceAdded { which, .. } => match self.subsystem.open(which) {
+				Ok(controller) => {
+					println!("Gamepad connected: {}", controller.name());
+					self.controllers.insert(controller.instance_id(), controller);
+				},
+				Err(error) => println!("Unable to open gamepad: {}", error),
+			},
+			Event::ControllerDeviceRemoved { which, .. } => {
+				if let Some(controller) = self.controllers.remove(&which) {
+					println!("Gamepad disconnected: {}", controller.name());
+				}
+
+				// let go of everything it was holding down
+				held_keys.release_where(|source| matches!(source, Source::Gamepad(id, _) if *id == which), emulator);
+			},
+			Event::ControllerButtonDown { which, button, .. } => self.set_held(which, Control::Button(button), true, held_keys, emulator),
+			Event::ControllerButtonUp { which, button, .. } => self.set_held(which, Control::Button(button), false, held_keys, emulator),
+			// each way along an axis is its own control
+			Event::ControllerAxisMotion { which, axis, value, .. } => {
+				self.set_held(which, Control::Axis(axis, false), self.map.is_pushed(value, false), held_keys, emulator);
+				self.set_held(which, Control::Axis(axis, true), self.map.is_pushed(value, true), held_keys, emulator);
+			},
+			_ => (),
+		}
+	}
+
+	fn set_held(&self, id: u32, control: Control, held: bool, held_keys: &mut HeldKeys, emulator: &mut Emulator) {
+		let source: Source = Source::Gamepad(id, control);
+		match self.map.get(control) {
+			Some(chip8_key) if held => held_keys.press(source, chip8_key, emulator),
+			_ => held_keys.release(source, emulator),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn controls(controls: &[(&str, &str)]) -> BTreeMap<String, String> {
+		controls.iter().map(|(name, chip8_key)| (name.to_string(), chip8_key.to_string())).collect()
+	}
+
+	#[test]
+	fn layouts_put_the_dpad_and_left_stick_on_the_same_keys() {
+		let map: GamepadMap = GamepadMap::new(GamepadLayout::Wasd, DEFAULT_DEADZONE);
+
+		assert_eq!(map.get(Control::Button(Button::DPadUp)), Some(0x5));
+		assert_eq!(map.get(Control::Axis(Axis::LeftY, false)), Some(0x5));
+		assert_eq!(map.get(Control::Axis(Axis::LeftX, true)), Some(0x9));
+		assert_eq!(map.get(Control::Button(Button::A)), Some(0x6));
+		assert_eq!(map.get(Control::Button(Button::Start)), None);
+		assert_eq!("2468".parse(), Ok(GamepadLayout::Numbers));
+		assert!("arrows".parse::<GamepadLayout>().is_err());
+	}
+
+	#[test]
+	fn settings_rebind_and_unbind_controls() {
+		let mut map: GamepadMap = GamepadMap::new(GamepadLayout::Numbers, DEFAULT_DEADZONE);
+		map.apply_settings(&controls(&[("a", "0xf"), ("b", "none"), ("start", "1"), ("right_trigger", "C")])).unwrap();
+
+		assert_eq!(map.get(Control::Button(Button::A)), Some(0xF));
+		assert_eq!(map.get(Control::Button(Button::B)), None);
+		assert_eq!(map.get(Control::Button(Button::Start)), Some(0x1));
+		assert_eq!(map.get(Control::Axis(Axis::TriggerRight, true)), Some(0xC));
+		// left alone
+		assert_eq!(map.get(Control::Button(Button::DPadUp)), Some(0x2));
+	}
+
+	#[test]
+	fn bad_settings_are_errors() {
+		let mut map: GamepadMap = GamepadMap::new(GamepadLayout::Numbers, DEFAULT_DEADZONE);
+
+		assert_eq!(map.apply_settings(&controls(&[("z", "1")])), Err(String::from("unknown control 'z'")));
+		assert_eq!(map.apply_settings(&controls(&[("a", "10")])), Err(String::from("'10' isn't a Chip-8 key - keys are 0-F")));
+		assert_eq!(map.apply_settings(&controls(&[("a", "None")])), Err(String::from("'None' isn't a Chip-8 key - keys are 0-F")));
+	}
+
+	#[test]
+	fn axes_press_at_the_deadzone() {
+		let map: GamepadMap = GamepadMap::new(GamepadLayout::Numbers, 0.5);
+
+		assert!(!map.is_pushed(16382, true));
+		assert!(map.is_pushed(16383, true));
+		assert!(!map.is_pushed(-16382, false));
+		assert!(map.is_pushed(-16383, false));
+		// not the other way
+		assert!(!map.is_pushed(i16::MAX, false));
+		assert!(!map.is_pushed(i16::MIN, true));
+	}
+
+	#[test]
+	fn a_deadzone_of_1_needs_the_axis_all_the_way() {
+		let map: GamepadMap = GamepadMap::new(GamepadLayout::Numbers, 1.0);
+
+		assert!(map.is_pushed(i16::MAX, true));
+		assert!(!map.is_pushed(i16::MAX - 1, true));
+		assert!(map.is_pushed(-i16::MAX, false));
+		assert!(map.is_pushed(i16::MIN, false));
+		assert!(!map.is_pushed(-i16::MAX + 1, false));
+
+		// past 1 is the same as 1
+		assert!(!GamepadMap::new(GamepadLayout::Numbers, 2.0).is_pushed(i16::MAX - 1, true));
+	}
+
+	#[test]
+	fn a_deadzone_of_0_still_ignores_a_centred_axis() {
+		for deadzone in [0.0, -1.0] {
+			let map: GamepadMap = GamepadMap::new(GamepadLayout::Numbers, deadzone);
+
+			assert!(!map.is_pushed(0, true));
+			assert!(!map.is_pushed(0, false));
+			assert!(map.is_pushed(1, true));
+			assert!(map.is_pushed(-1, false));
+		}
+	}
+}
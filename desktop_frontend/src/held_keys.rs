@@ -0,0 +1,160 @@
+// crates
+use std::collections::HashMap;
+
+use backend::Emulator;
+use sdl2::keyboard::Keycode;
+
+use crate::gamepad::Control;
+
+// something that can hold a Chip-8 key down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Source {
+	Keyboard(Keycode),
+	// a control on the gamepad with this SDL instance id
+	Gamepad(u32, Control),
+}
+
+// every keyboard key and gamepad control holding a Chip-8 key down - any
+// number of them can hold the same one, and it only goes up once they've all
+// let go
+#[derive(Debug, Default)]
+pub struct HeldKeys {
+	// the Chip-8 key each is holding, from when it was pressed, so it's let
+	// go of the same key even if the bindings change in between
+	held: HashMap<Source, usize>,
+}
+
+impl HeldKeys {
+	// new constructor for HeldKeys class - nothing is held
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn is_held(&self, chip8_key: usize) -> bool {
+		self.held.values().any(|held_key| *held_key == chip8_key)
+	}
+
+	pub fn press(&mut self, source: Source, chip8_key: usize, emulator: &mut Emulator) {
+		let was_held: bool = self.is_held(chip8_key);
+		if let Some(old_key) = self.held.insert(source, chip8_key) {
+			self.lift_if_free(old_key, emulator);
+		}
+		if !was_held {
+			emulator.key_press(chip8_key, true);
+		}
+	}
+
+	pub fn release(&mut self, source: Source, emulator: &mut Emulator) {
+		if let Some(chip8_key) = self.held.remove(&source) {
+			self.lift_if_free(chip8_key, emulator);
+		}
+	}
+
+	// let go of everything one kind of source is holding, e.g. an unplugged gamepad
+	pub fn release_where<F>(&mut self, matches: F, emulator: &mut Emulator)
+	where
+		F: Fn(&Source) -> bool,
+	{
+		let sources: Vec<Source> = self.held.keys().filter(|source| matches(source)).copied().collect();
+		for source in sources {
+			self.release(source, emulator);
+		}
+	}
+
+	// a key only goes up once nothing else is holding it
+	fn lift_if_free(&mut self, chip8_key: usize, emulator: &mut Emulator) {
+		if !self.is_held(chip8_key) {
+			emulator.key_press(chip8_key, false);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sdl2::controller::Button;
+
+	const KEY: usize = 5;
+	const KEYBOARD: Source = Source::Keyboard(Keycode::W);
+	const GAMEPAD: Source = Source::Gamepad(1, Control::Button(Button::A));
+	const OTHER_GAMEPAD: Source = Source::Gamepad(2, Control::Button(Button::A));
+
+	fn is_pressed(emulator: &Emulator, chip8_key: usize) -> bool {
+		emulator.get_keys()[chip8_key]
+	}
+
+	#[test]
+	fn a_key_is_pressed_and_released() {
+		let mut held_keys: HeldKeys = HeldKeys::new();
+		let mut emulator: Emulator = Emulator::default();
+
+		held_keys.press(KEYBOARD, KEY, &mut emulator);
+		assert!(held_keys.is_held(KEY));
+		assert!(is_pressed(&emulator, KEY));
+
+		held_keys.release(KEYBOARD, &mut emulator);
+		assert!(!held_keys.is_held(KEY));
+		assert!(!is_pressed(&emulator, KEY));
+	}
+
+	#[test]
+	fn a_key_held_twice_is_released_by_the_last_source() {
+		for (first, last) in [(KEYBOARD, GAMEPAD), (GAMEPAD, KEYBOARD)] {
+			let mut held_keys: HeldKeys = HeldKeys::new();
+			let mut emulator: Emulator = Emulator::default();
+
+			held_keys.press(KEYBOARD, KEY, &mut emulator);
+			held_keys.press(GAMEPAD, KEY, &mut emulator);
+
+			held_keys.release(first, &mut emulator);
+			assert!(is_pressed(&emulator, KEY));
+			held_keys.release(last, &mut emulator);
+			assert!(!is_pressed(&emulator, KEY));
+		}
+	}
+
+	#[test]
+	fn releasing_something_not_held_does_nothing() {
+		let mut held_keys: HeldKeys = HeldKeys::new();
+		let mut emulator: Emulator = Emulator::default();
+
+		held_keys.press(KEYBOARD, KEY, &mut emulator);
+		held_keys.release(GAMEPAD, &mut emulator);
+		assert!(is_pressed(&emulator, KEY));
+	}
+
+	#[test]
+	fn a_source_pressing_a_new_key_lets_go_of_its_old_one() {
+		let mut held_keys: HeldKeys = HeldKeys::new();
+		let mut emulator: Emulator = Emulator::default();
+
+		held_keys.press(KEYBOARD, KEY, &mut emulator);
+		held_keys.press(KEYBOARD, 6, &mut emulator);
+		assert!(!is_pressed(&emulator, KEY));
+		assert!(is_pressed(&emulator, 6));
+
+		// the old key is released, even though the source now holds another
+		held_keys.release(KEYBOARD, &mut emulator);
+		assert!(!is_pressed(&emulator, 6));
+	}
+
+	#[test]
+	fn unplugging_a_gamepad_releases_only_its_keys() {
+		let mut held_keys: HeldKeys = HeldKeys::new();
+		let mut emulator: Emulator = Emulator::default();
+
+		held_keys.press(KEYBOARD, KEY, &mut emulator);
+		held_keys.press(GAMEPAD, KEY, &mut emulator);
+		held_keys.press(Source::Gamepad(1, Control::Button(Button::B)), 0, &mut emulator);
+		held_keys.press(OTHER_GAMEPAD, 7, &mut emulator);
+
+		held_keys.release_where(|source| matches!(source, Source::Gamepad(1, _)), &mut emulator);
+		// still held by the keyboard
+		assert!(is_pressed(&emulator, KEY));
+		assert!(!is_pressed(&emulator, 0));
+		assert!(is_pressed(&emulator, 7));
+
+		held_keys.release_where(|_| true, &mut emulator);
+		assert!(!emulator.get_keys().iter().any(|pressed| *pressed));
+	}
+}
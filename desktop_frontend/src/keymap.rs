@@ -0,0 +1,486 @@
+// crates
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use sdl2::keyboard::Keycode;
+
+use crate::config::KeyNames;
+
+// skips a key while rebinding
+pub const SKIP_KEY: Keycode = Keycode::Tab;
+// stops rebinding and puts the keys back how they were
+pub const CANCEL_KEY: Keycode = Keycode::Escape;
+// closes the emulator
+pub const QUIT_KEY: Keycode = Keycode::Escape;
+// hold down to play the game backwards
+pub const REWIND_KEY: Keycode = Keycode::Backspace;
+// choose new keys for the keypad, one at a time
+pub const REBIND_KEY: Keycode = Keycode::F1;
+
+// keys the emulator uses itself, with what for - they never reach the game,
+// so they can't press a Chip-8 key
+const HOTKEYS: [(Keycode, &str); 4] = [
+	(QUIT_KEY, "quitting"),
+	(REWIND_KEY, "rewinding"),
+	(REBIND_KEY, "choosing keys"),
+	(SKIP_KEY, "skipping keys while choosing them"),
+];
+
+// the Chip-8 keypad, row by row - presets list keyboard keys in the same order
+const KEYPAD: [usize; 16] = [
+	0x1, 0x2, 0x3, 0xC,
+	0x4, 0x5, 0x6, 0xD,
+	0x7, 0x8, 0x9, 0xE,
+	0xA, 0x0, 0xB, 0xF,
+];
+
+// built-in layouts - the keypad goes on the left of the keyboard, where the
+// same keys are on each layout, or on the numeric keypad
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+	Qwerty,
+	Azerty,
+	Qwertz,
+	Dvorak,
+	Numpad,
+}
+
+impl Preset {
+	// every preset
+	pub const ALL: [Preset; 5] = [Preset::Qwerty, Preset::Azerty, Preset::Qwertz, Preset::Dvorak, Preset::Numpad];
+
+	// name used on the command line and in settings
+	pub fn name(&self) -> &'static str {
+		match self {
+			Preset::Qwerty => "qwerty",
+			Preset::Azerty => "azerty",
+			Preset::Qwertz => "qwertz",
+			Preset::Dvorak => "dvorak",
+			Preset::Numpad => "numpad",
+		}
+	}
+
+	// keyboard keys for each Chip-8 key, in KEYPAD order
+	fn keys(&self) -> [Keycode; 16] {
+		match self {
+			Preset::Qwerty => [
+				Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4,
+				Keycode::Q, Keycode::W, Keycode::E, Keycode::R,
+				Keycode::A, Keycode::S, Keycode::D, Keycode::F,
+				Keycode::Z, Keycode::X, Keycode::C, Keycode::V,
+			],
+			Preset::Azerty => [
+				Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4,
+				Keycode::A, Keycode::Z, Keycode::E, Keycode::R,
+				Keycode::Q, Keycode::S, Keycode::D, Keycode::F,
+				Keycode::W, Keycode::X, Keycode::C, Keycode::V,
+			],
+			Preset::Qwertz => [
+				Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4,
+				Keycode::Q, Keycode::W, Keycode::E, Keycode::R,
+				Keycode::A, Keycode::S, Keycode::D, Keycode::F,
+				Keycode::Y, Keycode::X, Keycode::C, Keycode::V,
+			],
+			Preset::Dvorak => [
+				Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4,
+				Keycode::Quote, Keycode::Comma, Keycode::Period, Keycode::P,
+				Keycode::A, Keycode::O, Keycode::E, Keycode::U,
+				Keycode::Semicolon, Keycode::Q, Keycode::J, Keycode::K,
+			],
+			// 7 8 9 are the top row, so up is still up in games that move with 2, 4, 6 and 8
+			Preset::Numpad => [
+				Keycode::Kp7, Keycode::Kp8, Keycode::Kp9, Keycode::KpDivide,
+				Keycode::Kp4, Keycode::Kp5, Keycode::Kp6, Keycode::KpMultiply,
+				Keycode::Kp1, Keycode::Kp2, Keycode::Kp3, Keycode::KpMinus,
+				Keycode::Kp0, Keycode::KpPeriod, Keycode::KpEnter, Keycode::KpPlus,
+			],
+		}
+	}
+}
+
+impl fmt::Display for Preset {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+// parse the name of a preset
+impl FromStr for Preset {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Self, Self::Err> {
+		Preset::ALL.iter()
+			.find(|preset| preset.name() == name)
+			.copied()
+			.ok_or_else(|| {
+				let names: Vec<&str> = Preset::ALL.iter().map(|preset| preset.name()).collect();
+				format!("unknown keyboard '{}', expected one of: {}", name, names.join(", "))
+			})
+	}
+}
+
+// which keyboard keys press which Chip-8 key - any number of keyboard keys
+// can press the same Chip-8 key
+#[derive(Clone, Debug)]
+pub struct Keymap {
+	bindings: HashMap<Keycode, usize>,
+}
+
+impl Default for Keymap {
+	fn default() -> Self {
+		Keymap::from_preset(Preset::Qwerty)
+	}
+}
+
+impl Keymap {
+	pub fn from_preset(preset: Preset) -> Self {
+		Self {
+			bindings: preset.keys().into_iter().zip(KEYPAD).collect(),
+		}
+	}
+
+	// Chip-8 key for a keyboard key, if it's bound to one
+	pub fn get(&self, keycode: Keycode) -> Option<usize> {
+		self.bindings.get(&keycode).copied()
+	}
+
+	// keyboard keys that press a Chip-8 key, sorted by name so they always come out the same
+	pub fn keys_for(&self, chip8_key: usize) -> Vec<Keycode> {
+		let mut keycodes: Vec<Keycode> = self.bindings.iter()
+			.filter(|(_, bound_key)| **bound_key == chip8_key)
+			.map(|(keycode, _)| *keycode)
+			.collect();
+		keycodes.sort_by_key(|keycode| keycode.name());
+		keycodes
+	}
+
+	// press a Chip-8 key with these keyboard keys, instead of the ones it had before
+	// a keyboard key only presses one Chip-8 key, so any of them that pressed
+	// another one stop doing so - they're returned, with the key they pressed
+	pub fn bind(&mut self, chip8_key: usize, keycodes: &[Keycode]) -> Vec<(Keycode, usize)> {
+		self.bindings.retain(|_, bound_key| *bound_key != chip8_key);
+
+		let mut taken: Vec<(Keycode, usize)> = Vec::new();
+		for keycode in keycodes {
+			if let Some(old_key) = self.bindings.insert(*keycode, chip8_key) {
+				taken.push((*keycode, old_key));
+			}
+		}
+		taken
+	}
+
+	// rebind keys from settings - Chip-8 keys as hex digits, keyboard keys by
+	// their SDL names, e.g. 5 = "Up" or 5 = ["W", "Up"]
+	// a keyboard key given to two Chip-8 keys, a Chip-8 key written two ways
+	// (5 and 0x5), or a hotkey is an error, and the keys taken from ones the
+	// settings leave alone are returned, with the key they pressed
+	pub fn apply_settings(&mut self, keys: &BTreeMap<String, KeyNames>) -> Result<Vec<(Keycode, usize)>, String> {
+		let mut chosen: HashMap<Keycode, usize> = HashMap::new();
+		let mut written_as: HashMap<usize, &str> = HashMap::new();
+		let mut taken: Vec<(Keycode, usize)> = Vec::new();
+
+		for (chip8_key_text, key_names) in keys {
+			let chip8_key: usize = parse_chip8_key(chip8_key_text)?;
+			if let Some(other_text) = written_as.insert(chip8_key, chip8_key_text) {
+				return Err(format!("'{}' and '{}' are both Chip-8 key {:X}", other_text, chip8_key_text, chip8_key));
+			}
+			let keycodes: Vec<Keycode> = key_names.as_slice().iter()
+				.map(|key_name| Keycode::from_name(key_name).ok_or_else(|| format!("unknown key '{}'", key_name)))
+				.collect::<Result<_, _>>()?;
+
+			for keycode in keycodes.iter() {
+				if let Some(hotkey_use) = hotkey_use(*keycode) {
+					return Err(format!("'{}' is for {}, so it can't press {:X}", keycode.name(), hotkey_use, chip8_key));
+				}
+				match chosen.insert(*keycode, chip8_key) {
+					Some(other_key) if other_key != chip8_key => {
+						return Err(format!("'{}' is given to both {:X} and {:X}", keycode.name(), other_key, chip8_key));
+					},
+					_ => (),
+				}
+			}
+			taken.extend(self.bind(chip8_key, &keycodes));
+		}
+
+		// a key the settings rebind has new keys, so it's lost nothing
+		taken.retain(|(_, old_key)| !chosen.values().any(|chip8_key| chip8_key == old_key));
+		taken.sort_by_key(|(keycode, _)| keycode.name());
+		Ok(taken)
+	}
+
+	// the [keymap] section that would give these bindings
+	pub fn to_settings(&self) -> String {
+		let mut text: String = String::from("[keymap]\n");
+
+		for chip8_key in 0..16 {
+			let names: Vec<String> = self.keys_for(chip8_key).iter().map(|keycode| format!("{:?}", keycode.name())).collect();
+			text.push_str(&format!("{:X} = [{}]\n", chip8_key, names.join(", ")));
+		}
+
+		text
+	}
+}
+
+// where rebinding got to after a key press
+pub enum RebindState {
+	// asking for the next key
+	Waiting,
+	// every key's been asked for - these are the new bindings
+	Finished(Keymap),
+	// cancelled - the keys stay as they were
+	Cancelled,
+}
+
+// asks for a keyboard key for each Chip-8 key in turn, row by row across the keypad
+pub struct Rebinding {
+	keymap: Keymap,
+	// place in KEYPAD of the Chip-8 key being asked for
+	position: usize,
+	// what the last key press did that the player should know about
+	notice: Option<String>,
+}
+
+impl Rebinding {
+	// new constructor for Rebinding class - starts from the current bindings,
+	// so skipped keys keep theirs
+	pub fn new(keymap: &Keymap) -> Self {
+		Self {
+			keymap: keymap.clone(),
+			position: 0,
+			notice: None,
+		}
+	}
+
+	// what to ask the player
+	pub fn prompt(&self) -> String {
+		let question: String = format!("Press a key for {:#X} ({} to skip, {} to cancel)", KEYPAD[self.position], SKIP_KEY.name(), CANCEL_KEY.name());
+		match &self.notice {
+			Some(notice) => format!("{} - {}", notice, question),
+			None => question,
+		}
+	}
+
+	// what the last key press did that the player should know about - still
+	// there once rebinding's finished, as the last key can take one too
+	pub fn get_notice(&self) -> Option<&str> {
+		self.notice.as_deref()
+	}
+
+	// a hotkey, or a key already on a Chip-8 key that's been asked for, is
+	// refused, and the Chip-8 key is asked for again - one taken from a key
+	// still to come is allowed, as that key gets asked for anyway, but the
+	// player is told
+	pub fn press(&mut self, keycode: Keycode) -> RebindState {
+		self.notice = None;
+		let chip8_key: usize = KEYPAD[self.position];
+
+		match (keycode, hotkey_use(keycode)) {
+			(CANCEL_KEY, _) => return RebindState::Cancelled,
+			(SKIP_KEY, _) => (),
+			(_, Some(hotkey_use)) => {
+				self.notice = Some(format!("{} is for {}", keycode.name(), hotkey_use));
+				return RebindState::Waiting;
+			},
+			_ => match self.keymap.get(keycode) {
+				Some(bound_key) if bound_key != chip8_key && KEYPAD[..self.position].contains(&bound_key) => {
+					self.notice = Some(format!("{} is already on {:#X}", keycode.name(), bound_key));
+					return RebindState::Waiting;
+				},
+				_ => {
+					for (keycode, old_key) in self.keymap.bind(chip8_key, &[keycode]) {
+						self.notice = Some(format!("{} no longer presses {:#X}", keycode.name(), old_key));
+					}
+				},
+			},
+		}
+
+		self.position += 1;
+		if self.position == KEYPAD.len() {
+			return RebindState::Finished(self.keymap.clone());
+		}
+		RebindState::Waiting
+	}
+}
+
+// what a key is for, if the emulator uses it itself
+fn hotkey_use(keycode: Keycode) -> Option<&'static str> {
+	HOTKEYS.iter().find(|(hotkey, _)| *hotkey == keycode).map(|(_, hotkey_use)| *hotkey_use)
+}
+
+// single hex digit, 0-F
+pub fn parse_chip8_key(text: &str) -> Result<usize, String> {
+	match usize::from_str_radix(text.trim_start_matches("0x"), 16) {
+		Ok(key) if key < 16 => Ok(key),
+		_ => Err(format!("'{}' isn't a Chip-8 key - keys are 0-F", text)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn settings(keys: &[(&str, &[&str])]) -> BTreeMap<String, KeyNames> {
+		keys.iter()
+			.map(|(chip8_key, key_names)| (chip8_key.to_string(), KeyNames::Many(key_names.iter().map(|name| name.to_string()).collect())))
+			.collect()
+	}
+
+	#[test]
+	fn presets_cover_the_keypad() {
+		for preset in Preset::ALL {
+			let keymap: Keymap = Keymap::from_preset(preset);
+			for chip8_key in 0..16 {
+				assert_eq!(keymap.keys_for(chip8_key).len(), 1, "{} {:X}", preset, chip8_key);
+			}
+		}
+		assert_eq!("dvorak".parse(), Ok(Preset::Dvorak));
+		assert!("colemak".parse::<Preset>().is_err());
+	}
+
+	#[test]
+	fn bind_replaces_a_keys_keyboard_keys() {
+		let mut keymap: Keymap = Keymap::default();
+
+		// Q was on 4
+		let taken: Vec<(Keycode, usize)> = keymap.bind(5, &[Keycode::Up, Keycode::Q]);
+		assert_eq!(taken, vec![(Keycode::Q, 4)]);
+		assert_eq!(keymap.keys_for(5), vec![Keycode::Q, Keycode::Up]);
+		assert_eq!(keymap.get(Keycode::W), None);
+		assert!(keymap.keys_for(4).is_empty());
+	}
+
+	#[test]
+	fn settings_rebind_keys() {
+		let mut keymap: Keymap = Keymap::default();
+		let taken: Vec<(Keycode, usize)> = keymap.apply_settings(&settings(&[("5", &["Up", "Q"]), ("0xa", &["Space"])])).unwrap();
+
+		assert_eq!(keymap.keys_for(5), vec![Keycode::Q, Keycode::Up]);
+		assert_eq!(keymap.keys_for(0xA), vec![Keycode::Space]);
+		assert_eq!(keymap.get(Keycode::Z), None);
+		// 4 is left without a key
+		assert_eq!(taken, vec![(Keycode::Q, 4)]);
+	}
+
+	#[test]
+	fn keys_moved_between_rebound_keys_arent_reported() {
+		let mut keymap: Keymap = Keymap::default();
+		let taken: Vec<(Keycode, usize)> = keymap.apply_settings(&settings(&[("4", &["W"]), ("5", &["Q"])])).unwrap();
+
+		assert_eq!(keymap.get(Keycode::W), Some(4));
+		assert_eq!(keymap.get(Keycode::Q), Some(5));
+		assert!(taken.is_empty());
+	}
+
+	#[test]
+	fn bad_settings_are_errors() {
+		let mut keymap: Keymap = Keymap::default();
+
+		assert_eq!(keymap.apply_settings(&settings(&[("5", &["Up"]), ("6", &["Up"])])), Err(String::from("'Up' is given to both 5 and 6")));
+		assert_eq!(keymap.apply_settings(&settings(&[("5", &["Nope"])])), Err(String::from("unknown key 'Nope'")));
+		assert_eq!(keymap.apply_settings(&settings(&[("G", &["Up"])])), Err(String::from("'G' isn't a Chip-8 key - keys are 0-F")));
+		assert_eq!(keymap.apply_settings(&settings(&[("10", &["Up"])])), Err(String::from("'10' isn't a Chip-8 key - keys are 0-F")));
+	}
+
+	#[test]
+	fn the_same_chip8_key_written_twice_is_an_error() {
+		let mut keymap: Keymap = Keymap::default();
+		assert_eq!(
+			keymap.apply_settings(&settings(&[("5", &["Up"]), ("0x5", &["Down"])])),
+			Err(String::from("'0x5' and '5' are both Chip-8 key 5")),
+		);
+	}
+
+	#[test]
+	fn hotkeys_cant_be_bound() {
+		for (key_name, hotkey_use) in [("Escape", "quitting"), ("Backspace", "rewinding"), ("F1", "choosing keys"), ("Tab", "skipping keys while choosing them")] {
+			let mut keymap: Keymap = Keymap::default();
+			assert_eq!(
+				keymap.apply_settings(&settings(&[("5", &["Up", key_name])])),
+				Err(format!("'{}' is for {}, so it can't press 5", key_name, hotkey_use)),
+			);
+		}
+	}
+
+	#[test]
+	fn settings_round_trip() {
+		let mut keymap: Keymap = Keymap::from_preset(Preset::Azerty);
+		keymap.bind(5, &[Keycode::Up, Keycode::Z]);
+
+		let table: toml::Table = toml::from_str(&keymap.to_settings()).unwrap();
+		let keys: BTreeMap<String, KeyNames> = table["keymap"].clone().try_into().unwrap();
+		let mut reloaded: Keymap = Keymap::from_preset(Preset::Qwerty);
+		reloaded.apply_settings(&keys).unwrap();
+
+		for chip8_key in 0..16 {
+			assert_eq!(reloaded.keys_for(chip8_key), keymap.keys_for(chip8_key));
+		}
+	}
+
+	// press keys, expecting to still be waiting after each
+	fn press_all(rebinding: &mut Rebinding, keycodes: &[Keycode]) {
+		for keycode in keycodes {
+			assert!(matches!(rebinding.press(*keycode), RebindState::Waiting));
+		}
+	}
+
+	#[test]
+	fn rebinding_asks_for_every_key() {
+		let mut rebinding: Rebinding = Rebinding::new(&Keymap::default());
+		assert!(rebinding.prompt().starts_with("Press a key for 0x1 "));
+
+		press_all(&mut rebinding, &[Keycode::Up]);
+		assert!(rebinding.prompt().starts_with("Press a key for 0x2 "));
+		press_all(&mut rebinding, &[SKIP_KEY; 14]);
+
+		let RebindState::Finished(keymap) = rebinding.press(Keycode::Down) else {
+			panic!("rebinding didn't finish");
+		};
+		assert_eq!(keymap.keys_for(1), vec![Keycode::Up]);
+		assert_eq!(keymap.keys_for(0xF), vec![Keycode::Down]);
+		// skipped keys keep theirs
+		assert_eq!(keymap.keys_for(2), vec![Keycode::Num2]);
+	}
+
+	#[test]
+	fn rebinding_can_be_cancelled() {
+		let mut rebinding: Rebinding = Rebinding::new(&Keymap::default());
+		press_all(&mut rebinding, &[Keycode::Up, SKIP_KEY]);
+		assert!(matches!(rebinding.press(CANCEL_KEY), RebindState::Cancelled));
+	}
+
+	#[test]
+	fn rebinding_refuses_keys_already_chosen() {
+		let mut rebinding: Rebinding = Rebinding::new(&Keymap::default());
+		press_all(&mut rebinding, &[Keycode::Up, Keycode::Up]);
+
+		assert_eq!(rebinding.get_notice(), Some("Up is already on 0x1"));
+		assert!(rebinding.prompt().starts_with("Up is already on 0x1 - Press a key for 0x2 "));
+	}
+
+	#[test]
+	fn rebinding_tells_of_keys_taken_from_ones_to_come() {
+		let mut rebinding: Rebinding = Rebinding::new(&Keymap::default());
+		// W was on 5, which is still to come
+		press_all(&mut rebinding, &[Keycode::W]);
+
+		assert_eq!(rebinding.get_notice(), Some("W no longer presses 0x5"));
+		assert!(rebinding.prompt().starts_with("W no longer presses 0x5 - Press a key for 0x2 "));
+
+		// the notice only lasts for one press
+		press_all(&mut rebinding, &[SKIP_KEY]);
+		assert_eq!(rebinding.get_notice(), None);
+	}
+
+	#[test]
+	fn rebinding_refuses_hotkeys() {
+		let mut rebinding: Rebinding = Rebinding::new(&Keymap::default());
+
+		press_all(&mut rebinding, &[REWIND_KEY]);
+		assert_eq!(rebinding.get_notice(), Some("Backspace is for rewinding"));
+		press_all(&mut rebinding, &[REBIND_KEY]);
+		assert_eq!(rebinding.get_notice(), Some("F1 is for choosing keys"));
+		// still asking for the first key
+		assert!(rebinding.prompt().ends_with(&format!("Press a key for 0x1 ({} to skip, {} to cancel)", SKIP_KEY.name(), CANCEL_KEY.name())));
+	}
+}
@@ -1,140 +1,395 @@
 // crates
-use backend::{self, Emulator, SCREEN_WIDTH};
-use sdl2;
-use std::io::Read;
-
-// scale up 64x32 monitor
-const SCALE_SIZE: u32 = 21;
-// actual window width
-const WINDOW_WIDTH: u32 = (backend::SCREEN_WIDTH as u32) * SCALE_SIZE;
-// actual window height
-const WINDOW_HEIGHT: u32 = (backend::SCREEN_HEIGHT as u32) * SCALE_SIZE;
-// ticks per frame
-const TICKS_PER_FRAME: usize = 20;
-
-fn main() {
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use backend::{self, Emulator, GdbServer, Platform, Profile, RewindBuffer, Tracer};
+
+// modules
+mod audio;
+mod cli;
+mod config;
+mod game;
+mod gamepad;
+mod held_keys;
+mod keymap;
+mod palette;
+mod timing;
+
+use cli::{Command, RunOptions, TraceOptions};
+use config::{Config, Settings};
+use gamepad::{GamepadMap, Gamepads};
+use held_keys::{HeldKeys, Source};
+use keymap::{Keymap, Preset, RebindState, Rebinding, QUIT_KEY, REBIND_KEY, REWIND_KEY};
+use palette::Palette;
+use timing::FrameClock;
+
+// scale up 64x32 monitor when --scale isn't given - kept even so 128x64 hi-res pixels are whole
+const DEFAULT_SCALE: u32 = 20;
+// frames of rewind history - 3 minutes at 60 frames a second
+const REWIND_FRAMES: usize = 3 * 60 * 60;
+// emulated frames a second - the timers count down once a frame
+const FRAMES_PER_SECOND: u32 = 60;
+// most frames run back to back when the host falls behind, before giving up and skipping them
+const MAX_CATCH_UP_FRAMES: u32 = 4;
+const WINDOW_TITLE: &str = "Chip-8 Emulator";
+
+fn main() -> ExitCode {
 	// get arguments from command line
-	let arguments: Vec<_> = std::env::args().collect();
+	let arguments: Vec<String> = std::env::args().collect();
+
+	let command: Command = match cli::parse_arguments(&arguments) {
+		Ok(command) => command,
+		Err(message) => {
+			eprintln!("{}", message);
+			return ExitCode::FAILURE;
+		},
+	};
+
+	let result: Result<(), String> = match command {
+		Command::Run(options) => run(options),
+		Command::Disasm { game_path } => disasm(&game_path),
+		Command::Info { game_path } => info(&game_path),
+		Command::Trace(options) => trace(options),
+		Command::Help => {
+			println!("{}", cli::USAGE);
+			Ok(())
+		},
+	};
+
+	if let Err(message) = result {
+		eprintln!("{}", message);
+		return ExitCode::FAILURE;
+	}
+	ExitCode::SUCCESS
+}
+
+// settings from the config file for this game, with the command line's on top
+fn load_settings(options: &RunOptions, rom: &[u8]) -> Result<Settings, String> {
+	let config: Config = match &options.config_path {
+		Some(path) => config::load(Path::new(path))?,
+		None => config::load_default()?,
+	};
+
+	let mut settings: Settings = config.settings_for(&game::hash(rom));
+	settings.merge(Settings {
+		platform: options.platform,
+		speed: options.speed,
+		scale: options.scale,
+		palette: options.palette,
+		fullscreen: options.fullscreen,
+		mute: options.mute,
+		keyboard: options.keyboard,
+		..Settings::default()
+	});
+	Ok(settings)
+}
+
+// play the game in a window until it's closed or exits
+fn run(options: RunOptions) -> Result<(), String> {
+	let game_buffer: Vec<u8> = game::load_game(&options.game_path)?;
 
-	// must only have the game path, no other arguments
-	if arguments.len() != 2 {
-		println!("Usage: cargo run path_to_game");
-		return;
+	let settings: Settings = load_settings(&options, &game_buffer)?;
+	let profile: Profile = settings.profile();
+	let scale: u32 = settings.scale.unwrap_or(DEFAULT_SCALE);
+	let palette: Palette = settings.palette.unwrap_or(palette::MONO);
+	let mut keymap: Keymap = Keymap::from_preset(settings.keyboard.unwrap_or(Preset::Qwerty));
+	let taken: Vec<(sdl2::keyboard::Keycode, usize)> = keymap.apply_settings(&settings.keymap).map_err(|message| format!("Error in keymap: {}", message))?;
+	for (keycode, chip8_key) in taken {
+		println!("{} no longer presses {:X}, as the keymap gives it to another key", keycode.name(), chip8_key);
 	}
+	let mut gamepad_map: GamepadMap = GamepadMap::new(
+		settings.gamepad.layout.unwrap_or_default(),
+		settings.gamepad.deadzone.unwrap_or(gamepad::DEFAULT_DEADZONE),
+	);
+	gamepad_map.apply_settings(&settings.gamepad.controls).map_err(|message| format!("Error in gamepad controls: {}", message))?;
+
+	// initialise an emulator object - with a seed, random numbers come out the same every time
+	let mut chip8: Emulator = match options.seed {
+		Some(seed) => Emulator::with_seed(profile, seed),
+		None => Emulator::new(profile),
+	}.map_err(|error| format!("Unable to start: {}", error))?;
+
+	// load game from buffer to rom and chip8
+	chip8.load_rom(&game_buffer).map_err(|error| format!("Unable to load ROM: {}", error))?;
 
 	// setup SDL2
-	let sdl_context = sdl2::init().unwrap();
-	let video_subsystem = sdl_context.video().unwrap();
+	let sdl_context = sdl2::init()?;
+	let video_subsystem = sdl_context.video()?;
 
 	// window for screen to be held in
-	let window = video_subsystem.window("Chip-8 Emulator", WINDOW_WIDTH, WINDOW_HEIGHT).position_centered().opengl().build().unwrap();
+	let window_width: u32 = (backend::SCREEN_WIDTH as u32) * scale;
+	let window_height: u32 = (backend::SCREEN_HEIGHT as u32) * scale;
+	let mut window_builder = video_subsystem.window(WINDOW_TITLE, window_width, window_height);
+	window_builder.position_centered().opengl();
+	if settings.fullscreen == Some(true) {
+		window_builder.fullscreen_desktop();
+	}
+	let window = window_builder.build().map_err(|error| error.to_string())?;
 
-	// actual screen for user
-	let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+	// actual screen for user - frames are paced by the clock rather than vsync,
+	// so games run at the same speed on any monitor
+	let mut canvas = window.into_canvas().build().map_err(|error| error.to_string())?;
+	// drawn at hi-res size and scaled up to fit the window, with borders to keep the shape in fullscreen
+	canvas.set_logical_size(backend::HIRES_SCREEN_WIDTH as u32, backend::HIRES_SCREEN_HEIGHT as u32).map_err(|error| error.to_string())?;
 	// clear and display to user
 	canvas.clear();
 	canvas.present();
 
-	let mut event_pump = sdl_context.event_pump().unwrap();
+	let mut event_pump = sdl_context.event_pump()?;
+
+	// games still run without sound if there's no audio device
+	let mut beeper = if settings.mute == Some(true) {
+		None
+	} else {
+		let beeper = audio::open_beeper(&sdl_context, settings.audio_settings());
+		if beeper.is_none() {
+			println!("Unable to open audio device, sound is off.");
+		}
+		beeper
+	};
+
+	// gamepads can be plugged in and out while playing - the keyboard works either way
+	let mut gamepads: Option<Gamepads> = gamepad::open_gamepads(&sdl_context, gamepad_map);
+	if gamepads.is_none() {
+		println!("Unable to use gamepads, only the keyboard works.");
+	}
+	// keyboard keys and gamepad controls holding Chip-8 keys down
+	let mut held_keys: HeldKeys = HeldKeys::new();
 
-	// initialise an emulator object
-	let mut chip8 = backend::Emulator::new();
+	// gdb can connect at any point, and the game stops when it does
+	let mut gdb_server: Option<GdbServer> = match options.gdb_port {
+		Some(port) => {
+			let server: GdbServer = GdbServer::bind(port).map_err(|error| format!("Unable to listen on port {}: {}", port, error))?;
+			println!("GDB can connect on localhost:{}", port);
+			Some(server)
+		},
+		None => None,
+	};
 
-	// load in ROM file, expect - if file doesn't exist
-	let mut game_rom = std::fs::File::open(&arguments[1]).expect("Unable to open file.");
-	// create buffer for game file
-	let mut game_buffer = Vec::new();
+	// every frame is recorded so it can be rewound to
+	let mut rewind_buffer = RewindBuffer::new(REWIND_FRAMES);
+	let mut rewinding: bool = false;
+	// keys being chosen for the keypad - the game waits until it's done
+	let mut rebinding: Option<Rebinding> = None;
 
-	// load game from buffer to rom and chip8
-	game_rom.read_to_end(&mut game_buffer).unwrap();
-	chip8.load_rom(&game_buffer);
+	// frames run at 60 a second of real time, whether or not anything's happening
+	let mut frame_clock = FrameClock::new(FRAMES_PER_SECOND, MAX_CATCH_UP_FRAMES);
 
 	'main_game_loop: loop {
+		// handle all the input waiting, before running any frames
 		for event in event_pump.poll_iter() {
 			match event {
 
+				// while rebinding, key presses choose keys instead of going to the game
+				sdl2::event::Event::KeyDown{keycode: Some(key), repeat, ..} if rebinding.is_some() => {
+					if let (Some(rebind), false) = (rebinding.as_mut(), repeat) {
+						match rebind.press(key) {
+							RebindState::Waiting => canvas.window_mut().set_title(&rebind.prompt()).unwrap(),
+							RebindState::Finished(new_keymap) => {
+								if let Some(notice) = rebind.get_notice() {
+									println!("{}", notice);
+								}
+								keymap = new_keymap;
+								rebinding = None;
+								println!("Keys rebound - to keep them, put this in the config file:\n{}", keymap.to_settings());
+								canvas.window_mut().set_title(WINDOW_TITLE).unwrap();
+							},
+							RebindState::Cancelled => {
+								rebinding = None;
+								canvas.window_mut().set_title(WINDOW_TITLE).unwrap();
+							},
+						}
+					}
+				},
+
 				// clicks on red x button of window
-				sdl2::event::Event::Quit{..} | sdl2::event::Event::KeyDown{keycode: Some(sdl2::keyboard::Keycode::Escape), ..} => {
+				sdl2::event::Event::Quit{..} | sdl2::event::Event::KeyDown{keycode: Some(QUIT_KEY), ..} => {
 					break 'main_game_loop;
 				},
 
+				// start or stop rewinding
+				sdl2::event::Event::KeyDown{keycode: Some(REWIND_KEY), ..} => {
+					rewinding = true;
+				},
+				sdl2::event::Event::KeyUp{keycode: Some(REWIND_KEY), ..} => {
+					rewinding = false;
+				},
+
+				// start choosing new keys, with none of the old ones left held down
+				sdl2::event::Event::KeyDown{keycode: Some(REBIND_KEY), ..} => {
+					held_keys.release_where(|_| true, &mut chip8);
+					let rebind: Rebinding = Rebinding::new(&keymap);
+					canvas.window_mut().set_title(&rebind.prompt()).unwrap();
+					rebinding = Some(rebind);
+				},
+
 				// press a key down
 				sdl2::event::Event::KeyDown{keycode: Some(key), ..} => {
-					if let Some(k) = key_to_btn(key) {
-						chip8.key_press(k, true);
+					if let Some(k) = keymap.get(key) {
+						held_keys.press(Source::Keyboard(key), k, &mut chip8);
 					}
 				},
 
-				// lift a key up
+				// lift a key up, unless something else is still holding it
 				sdl2::event::Event::KeyUp{keycode: Some(key), ..} => {
-					if let Some(k) = key_to_btn(key) {
-						chip8.key_press(k, false);
+					held_keys.release(Source::Keyboard(key), &mut chip8);
+				},
+
+				// gamepads coming and going, and their buttons and sticks
+				sdl2::event::Event::ControllerDeviceAdded{..}
+				| sdl2::event::Event::ControllerDeviceRemoved{..}
+				| sdl2::event::Event::ControllerButtonDown{..}
+				| sdl2::event::Event::ControllerButtonUp{..}
+				| sdl2::event::Event::ControllerAxisMotion{..} => {
+					if let Some(gamepads) = gamepads.as_mut() {
+						gamepads.handle_event(&event, &mut held_keys, &mut chip8);
 					}
 				},
 
 				// other undefined event
 				_ => ()
 			}
+		}
+
+		// usually 1, more if the host fell behind, or 0 if it's running ahead
+		let frames_due: u32 = frame_clock.frames_due();
+		// the game waits while keys are being chosen
+		let frames_to_run: u32 = if rebinding.is_some() { 0 } else { frames_due };
+		for _ in 0..frames_to_run {
+			// game has run the SUPER-CHIP exit opcode
+			if chip8.is_halted() {
+				break 'main_game_loop;
+			}
+
+			if rewinding {
+				// go back a frame - play resumes from here once the key is let go
+				rewind_buffer.rewind(&mut chip8);
+			} else if let Some(server) = gdb_server.as_mut() {
+				// gdb decides when the game runs, so a frame may be cut short or not run at all
+				match server.update(&mut chip8, profile.ticks_per_frame) {
+					Ok(true) => {
+						chip8.tick_timers();
+						rewind_buffer.record(&chip8);
+					},
+					Ok(false) => (),
+					Err(error) => return Err(format!("Emulator error: {}", error)),
+				}
+			} else {
+				// ticks required during 1 frame
+				for _ in 0..profile.ticks_per_frame {
+					// bad ROM - say what went wrong rather than crashing
+					chip8.tick().map_err(|error| format!("Emulator error: {}", error))?;
+				}
+
+				// tick both timers
+				chip8.tick_timers();
+				rewind_buffer.record(&chip8);
+			}
+		}
+
+		// update sound and screen once, however many frames ran to catch up
+		if frames_due > 0 {
+			if let Some(device) = beeper.as_mut() {
+				audio::update_beeper(device, &chip8);
+			}
+			draw_screen(&chip8, &mut canvas, &palette);
+		}
+
+		frame_clock.wait();
+	}
+
+	Ok(())
+}
+
+// list every instruction in the game, as it would be loaded
+fn disasm(game_path: &str) -> Result<(), String> {
+	let rom: Vec<u8> = game::load_game(game_path)?;
 
-			// ticks required during 1 frame
-			for _ in 0..TICKS_PER_FRAME {
-				chip8.tick();
+	for line in backend::disassemble(&rom, Profile::default().start_address) {
+		println!("{}", line);
+	}
+	Ok(())
+}
+
+fn info(game_path: &str) -> Result<(), String> {
+	let rom: Vec<u8> = game::load_game(game_path)?;
+
+	println!("size: {} bytes", rom.len());
+	println!("sha-1: {}", game::hash(&rom));
+	println!("platform: {} (best guess)", Platform::detect(&rom));
+	Ok(())
+}
+
+// run the game with no window for a number of frames, writing a trace line for every instruction
+fn trace(options: TraceOptions) -> Result<(), String> {
+	let rom: Vec<u8> = game::load_game(&options.game_path)?;
+	// the config file isn't read, so a trace comes out the same wherever it's made
+	let profile: Profile = Settings { platform: options.platform, speed: options.speed, ..Settings::default() }.profile();
+
+	// the same seed every time unless told otherwise, so traces can be compared
+	let mut emulator: Emulator = Emulator::with_seed(profile, options.seed.unwrap_or(0)).map_err(|error| format!("Unable to start: {}", error))?;
+	emulator.load_rom(&rom).map_err(|error| format!("Unable to load ROM: {}", error))?;
+
+	let output: Box<dyn Write + Send> = match &options.output_path {
+		Some(path) => Box::new(BufWriter::new(File::create(path).map_err(|error| format!("Unable to create {}: {}", path, error))?)),
+		None => Box::new(BufWriter::new(io::stdout())),
+	};
+	emulator.set_tracer(Tracer::new(output));
+
+	let mut result: Result<(), String> = Ok(());
+	'frames: for _ in 0..options.frames {
+		for _ in 0..profile.ticks_per_frame {
+			if emulator.is_halted() {
+				break 'frames;
+			}
+			if let Err(error) = emulator.tick() {
+				result = Err(format!("Emulator error: {}", error));
+				break 'frames;
 			}
+		}
+		emulator.tick_timers();
+	}
 
-			// tick both timers
-			chip8.tick_timers();
-			// update screen
-			draw_screen(&chip8, &mut canvas);
+	// the trace is still written after an error - it shows how the game got there
+	if let Some(mut tracer) = emulator.take_tracer() {
+		if let Some(error) = tracer.take_error() {
+			return Err(format!("Unable to write trace: {}", error));
 		}
+		tracer.flush().map_err(|error| format!("Unable to write trace: {}", error))?;
 	}
+	result
 }
 
 // clear screen by setting all to black, etc.
-fn draw_screen(emulator: &Emulator, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
-	// clear canvas as black
-	canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 0));
+fn draw_screen(emulator: &Emulator, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, palette: &Palette) {
+	// clear canvas as the background colour
+	let (red, green, blue) = palette[0];
+	canvas.set_draw_color(sdl2::pixels::Color::RGB(red, green, blue));
 	canvas.clear();
 
 	let screen_buffer = emulator.get_display();
+	// hi-res games have twice as many pixels, so each one is drawn smaller
+	let (screen_width, _) = emulator.get_resolution();
+	let pixel_size: u32 = (backend::HIRES_SCREEN_WIDTH / screen_width) as u32;
 
-	// set draw colour to white
-	canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 255));
 	// iterate through each point to see if it should be drawn on
 	for (i, pixel) in screen_buffer.iter().enumerate() {
-		if *pixel {
+		if *pixel != 0 {
+			// set draw colour to the one for this pixel's planes
+			let (red, green, blue) = palette[*pixel as usize];
+			canvas.set_draw_color(sdl2::pixels::Color::RGB(red, green, blue));
+
 			// convert 1D array index into a 2D (x, y) coordinate position
-			let x: u32 = (i % SCREEN_WIDTH) as u32;
-			let y: u32 = (i / SCREEN_WIDTH) as u32;
+			let x: u32 = (i % screen_width) as u32;
+			let y: u32 = (i / screen_width) as u32;
 
 			// draw a rectangle at point (x, y), but scaled up
-			let rectangle = sdl2::rect::Rect::new((x * SCALE_SIZE) as i32, (y * SCALE_SIZE) as i32, SCALE_SIZE, SCALE_SIZE);
+			let rectangle = sdl2::rect::Rect::new((x * pixel_size) as i32, (y * pixel_size) as i32, pixel_size, pixel_size);
 			canvas.fill_rect(rectangle).unwrap();
 		}
 	}
 
 	canvas.present();
 }
-
-// take in a Keycode and output an optional u8 value
-fn key_to_btn(key: sdl2::keyboard::Keycode) -> Option<usize> {
-	// switch
-	match key {
-		sdl2::keyboard::Keycode::Num1 => Some(0x1),
-		sdl2::keyboard::Keycode::Num2 => Some(0x2),
-		sdl2::keyboard::Keycode::Num3 => Some(0x3),
-		sdl2::keyboard::Keycode::Num4 => Some(0xC),
-		sdl2::keyboard::Keycode::Q => Some(0x4),
-		sdl2::keyboard::Keycode::W => Some(0x5),
-		sdl2::keyboard::Keycode::E => Some(0x6),
-		sdl2::keyboard::Keycode::R => Some(0xD),
-		sdl2::keyboard::Keycode::A => Some(0x7),
-		sdl2::keyboard::Keycode::S => Some(0x8),
-		sdl2::keyboard::Keycode::D => Some(0x9),
-		sdl2::keyboard::Keycode::F => Some(0xE),
-		sdl2::keyboard::Keycode::Z => Some(0xA),
-		sdl2::keyboard::Keycode::X => Some(0x0),
-		sdl2::keyboard::Keycode::C => Some(0xB),
-		sdl2::keyboard::Keycode::V => Some(0xF),
-		_ => None,
-	}
-}
@@ -0,0 +1,46 @@
+// crates
+pub use backend::Palette;
+
+// black and white, with greys for the other planes
+pub const MONO: Palette = backend::MONO_PALETTE;
+
+// palettes that can be picked by name
+const NAMED_PALETTES: [(&str, Palette); 4] = [
+	("mono", MONO),
+	// old amber monitor
+	("amber", [(0, 0, 0), (255, 176, 0), (153, 106, 0), (77, 53, 0)]),
+	// old green monitor
+	("green", [(0, 0, 0), (51, 255, 51), (31, 153, 31), (15, 77, 15)]),
+	// Octo's own colours
+	("octo", [(153, 102, 0), (255, 204, 0), (255, 102, 0), (102, 34, 0)]),
+];
+
+// a palette's name, or its 4 colours in hex separated by commas,
+// e.g. 000000,FFFFFF,AAAAAA,555555
+pub fn parse_palette(text: &str) -> Result<Palette, String> {
+	if let Some((_, palette)) = NAMED_PALETTES.iter().find(|(name, _)| *name == text) {
+		return Ok(*palette);
+	}
+
+	let colours: Vec<&str> = text.split(',').map(|colour| colour.trim().trim_start_matches('#')).collect();
+	let mut palette: Palette = MONO;
+	if colours.len() != palette.len() {
+		let names: Vec<&str> = NAMED_PALETTES.iter().map(|(name, _)| *name).collect();
+		return Err(format!("unknown palette '{}', expected one of: {}, or {} colours like 000000,FFFFFF,AAAAAA,555555", text, names.join(", "), palette.len()));
+	}
+
+	for (entry, colour) in palette.iter_mut().zip(colours) {
+		*entry = parse_colour(colour).ok_or_else(|| format!("'{}' isn't a colour - colours are 6 hex digits, like FF8800", colour))?;
+	}
+	Ok(palette)
+}
+
+// RRGGBB
+fn parse_colour(text: &str) -> Option<(u8, u8, u8)> {
+	if text.len() != 6 || !text.is_ascii() {
+		return None;
+	}
+	let channel = |start: usize| u8::from_str_radix(&text[start..start + 2], 16).ok();
+
+	Some((channel(0)?, channel(2)?, channel(4)?))
+}
@@ -0,0 +1,68 @@
+// crates
+use std::thread;
+use std::time::{Duration, Instant};
+
+// keeps emulated frames in step with real time, however fast the host's
+// screen refreshes and however often input arrives
+// frame times are counted from a start point rather than added up one at a
+// time, so they don't drift
+pub struct FrameClock {
+	frames_per_second: u32,
+	// most frames run in one go to catch up - any further behind and the rest are skipped
+	max_catch_up: u32,
+	// when frame 0 was due
+	start: Instant,
+	// frames run since start
+	frame_count: u64,
+}
+
+impl FrameClock {
+	// new constructor for FrameClock class - the first frame is due straight away
+	pub fn new(frames_per_second: u32, max_catch_up: u32) -> Self {
+		Self {
+			frames_per_second,
+			max_catch_up,
+			start: Instant::now(),
+			frame_count: 0,
+		}
+	}
+
+	// how many frames should run now - 0 if the next one isn't due yet
+	// if the host has fallen so far behind that running them all would make it
+	// fall further behind still, the extra frames are dropped and the game
+	// carries on from now a little later than it should
+	pub fn frames_due(&mut self) -> u32 {
+		let now: Instant = Instant::now();
+		let elapsed_frames: u64 = self.frames_at(now.duration_since(self.start));
+		// the frame that's just started counts too
+		let due: u64 = (elapsed_frames + 1).saturating_sub(self.frame_count);
+
+		if due > self.max_catch_up as u64 {
+			// the frames run now stand in for the one that's just started
+			self.start = now;
+			self.frame_count = 1;
+			return self.max_catch_up;
+		}
+
+		self.frame_count += due;
+		due as u32
+	}
+
+	// sleep until the next frame is due
+	pub fn wait(&self) {
+		let next_frame: Instant = self.start + self.time_of(self.frame_count);
+		if let Some(remaining) = next_frame.checked_duration_since(Instant::now()) {
+			thread::sleep(remaining);
+		}
+	}
+
+	// whole frames in a length of time
+	fn frames_at(&self, time: Duration) -> u64 {
+		(time.as_nanos() * self.frames_per_second as u128 / 1_000_000_000) as u64
+	}
+
+	// when a frame is due, counting from start
+	fn time_of(&self, frame: u64) -> Duration {
+		Duration::from_nanos((frame as u128 * 1_000_000_000 / self.frames_per_second as u128) as u64)
+	}
+}
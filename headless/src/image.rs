@@ -0,0 +1,143 @@
+// the screen as text or an image, and a hash of it so runs can be compared
+// without keeping the whole picture
+
+// crates
+use std::io;
+
+use backend::{Emulator, MONO_PALETTE};
+
+// character for each combination of bitplanes in an ASCII dump
+const ASCII_PIXELS: [char; 1 << backend::NO_OF_BITPLANES] = ['.', '#', '+', '@'];
+
+// one line of characters per row
+pub fn to_ascii(emulator: &Emulator) -> String {
+	let (width, _) = emulator.get_resolution();
+	let mut text: String = String::new();
+
+	for row in emulator.get_display().chunks(width) {
+		text.extend(row.iter().map(|&pixel| ASCII_PIXELS[pixel as usize]));
+		text.push('\n');
+	}
+
+	text
+}
+
+// plain (P1) PBM - 1 is any lit pixel, whichever planes it's on
+pub fn to_pbm(emulator: &Emulator) -> String {
+	let (width, height) = emulator.get_resolution();
+	let mut text: String = format!("P1\n{} {}\n", width, height);
+
+	for row in emulator.get_display().chunks(width) {
+		let pixels: Vec<&str> = row.iter().map(|&pixel| if pixel != 0 {"1"} else {"0"}).collect();
+		text.push_str(&pixels.join(" "));
+		text.push('\n');
+	}
+
+	text
+}
+
+// RGB PNG in the frontends' default colours, one image pixel per screen pixel
+pub fn to_png(emulator: &Emulator) -> io::Result<Vec<u8>> {
+	let (width, height) = emulator.get_resolution();
+	let data: Vec<u8> = emulator.get_display().iter()
+		.flat_map(|&pixel| {
+			let (red, green, blue) = MONO_PALETTE[pixel as usize];
+			[red, green, blue]
+		})
+		.collect();
+
+	let mut bytes: Vec<u8> = Vec::new();
+	let mut encoder = png::Encoder::new(&mut bytes, width as u32, height as u32);
+	encoder.set_color(png::ColorType::Rgb);
+	encoder.set_depth(png::BitDepth::Eight);
+	let mut writer = encoder.write_header().map_err(io::Error::other)?;
+	writer.write_image_data(&data).map_err(io::Error::other)?;
+	writer.finish().map_err(io::Error::other)?;
+
+	Ok(bytes)
+}
+
+// 64 bit FNV-1a of the width, height and every pixel - the same screen
+// always gives the same hash, on any machine
+pub fn frame_hash(emulator: &Emulator) -> u64 {
+	let (width, height) = emulator.get_resolution();
+	let size: [u8; 4] = [(width >> 8) as u8, width as u8, (height >> 8) as u8, height as u8];
+
+	size.iter().chain(emulator.get_display().iter()).fold(0xcbf29ce484222325, |hash: u64, &byte| {
+		(hash ^ byte as u64).wrapping_mul(0x100000001b3)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use backend::Platform;
+
+	// the font's 0 on plane 1 at the top left, and on plane 2 8 pixels right of it
+	fn drawn_emulator() -> Emulator {
+		let rom: [u8; 16] = [
+			0x60, 0x00, // LD V0, 0
+			0x61, 0x00, // LD V1, 0
+			0xF0, 0x29, // LD F, V0
+			0xD0, 0x15, // DRW V0, V1, 5
+			0x60, 0x08, // LD V0, 8
+			0xF2, 0x01, // PLANE 2
+			0xD0, 0x15, // DRW V0, V1, 5
+			0x12, 0x0E, // JP 0x20E
+		];
+		let mut emulator: Emulator = Emulator::with_seed(Platform::XoChip.profile(), 0).unwrap();
+		emulator.load_rom(&rom).unwrap();
+		for _ in 0..7 {
+			emulator.tick().unwrap();
+		}
+		emulator
+	}
+
+	#[test]
+	fn ascii_has_a_character_per_pixel() {
+		let text: String = to_ascii(&drawn_emulator());
+		let rows: Vec<&str> = text.lines().collect();
+
+		assert_eq!(rows.len(), 32);
+		assert!(rows.iter().all(|row| row.len() == 64));
+		assert!(rows[0].starts_with("####....++++...."));
+		assert!(rows[1].starts_with("#..#....+..+...."));
+		assert_eq!(rows[5], ".".repeat(64));
+	}
+
+	#[test]
+	fn pbm_lights_pixels_on_any_plane() {
+		let text: String = to_pbm(&drawn_emulator());
+		let lines: Vec<&str> = text.lines().collect();
+
+		assert_eq!(&lines[..2], ["P1", "64 32"]);
+		assert_eq!(lines.len(), 2 + 32);
+		assert!(lines[2].starts_with("1 1 1 1 0 0 0 0 1 1 1 1 0 0 0 0 "));
+		assert_eq!(lines[7], vec!["0"; 64].join(" "));
+	}
+
+	#[test]
+	fn png_uses_the_palette() {
+		let bytes: Vec<u8> = to_png(&drawn_emulator()).unwrap();
+		let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+		let mut data: Vec<u8> = vec![0; reader.output_buffer_size()];
+		let info = reader.next_frame(&mut data).unwrap();
+
+		assert_eq!((info.width, info.height), (64, 32));
+		assert_eq!(info.color_type, png::ColorType::Rgb);
+		let pixel = |x: usize, y: usize| {
+			let index: usize = (y * 64 + x) * 3;
+			(data[index], data[index + 1], data[index + 2])
+		};
+		assert_eq!(pixel(0, 0), MONO_PALETTE[1]);
+		assert_eq!(pixel(8, 0), MONO_PALETTE[2]);
+		assert_eq!(pixel(4, 0), MONO_PALETTE[0]);
+	}
+
+	#[test]
+	fn hashes_follow_the_screen() {
+		let blank: Emulator = Emulator::default();
+		assert_eq!(frame_hash(&drawn_emulator()), frame_hash(&drawn_emulator()));
+		assert_ne!(frame_hash(&drawn_emulator()), frame_hash(&blank));
+	}
+}
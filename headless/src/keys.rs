@@ -0,0 +1,137 @@
+// scripted key input - which keys go down and come up on which frames
+//
+// a schedule is a list of frame:key entries separated by commas or
+// whitespace, where key is a hex digit:
+//   30:+5   presses key 5 at the start of frame 30
+//   40:-5   lets go of it at the start of frame 40
+//   50:A    taps key A - pressed on frame 50 and let go on frame 51
+// frames count from 0
+
+// crates
+use std::str::FromStr;
+
+use backend::Emulator;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct KeyEvent {
+	frame: u64,
+	key: usize,
+	pressed: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeySchedule {
+	// in frame order
+	events: Vec<KeyEvent>,
+}
+
+impl KeySchedule {
+	// press and release whatever the schedule says to at the start of a frame
+	pub fn apply(&self, frame: u64, emulator: &mut Emulator) {
+		for event in self.events.iter().filter(|event| event.frame == frame) {
+			emulator.key_press(event.key, event.pressed);
+		}
+	}
+}
+
+impl FromStr for KeySchedule {
+	type Err = String;
+
+	fn from_str(schedule: &str) -> Result<Self, Self::Err> {
+		let mut events: Vec<KeyEvent> = Vec::new();
+
+		for entry in schedule.split(|character: char| character == ',' || character.is_whitespace()).filter(|entry| !entry.is_empty()) {
+			let invalid = || format!("'{}' isn't a key event - expected frame:+key, frame:-key or frame:key", entry);
+
+			let (frame, key): (&str, &str) = entry.split_once(':').ok_or_else(invalid)?;
+			let frame: u64 = frame.parse().map_err(|_| invalid())?;
+			let (pressed, key): (Option<bool>, &str) = match key.strip_prefix('+') {
+				Some(key) => (Some(true), key),
+				None => match key.strip_prefix('-') {
+					Some(key) => (Some(false), key),
+					None => (None, key),
+				},
+			};
+			let key: usize = match usize::from_str_radix(key, 16) {
+				Ok(key) if key < 16 => key,
+				_ => return Err(invalid()),
+			};
+
+			match pressed {
+				Some(pressed) => events.push(KeyEvent { frame, key, pressed }),
+				// a tap is a press and a release on the next frame
+				None => {
+					events.push(KeyEvent { frame, key, pressed: true });
+					events.push(KeyEvent { frame: frame + 1, key, pressed: false });
+				},
+			}
+		}
+
+		// stable, so events on the same frame keep the order they were given in
+		events.sort_by_key(|event| event.frame);
+		Ok(Self { events })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn event(frame: u64, key: usize, pressed: bool) -> KeyEvent {
+		KeyEvent { frame, key, pressed }
+	}
+
+	#[test]
+	fn presses_releases_and_taps() {
+		let schedule: KeySchedule = "40:-5, 30:+5 50:a".parse().unwrap();
+		assert_eq!(schedule.events, vec![
+			event(30, 5, true),
+			event(40, 5, false),
+			event(50, 0xA, true),
+			event(51, 0xA, false),
+		]);
+	}
+
+	#[test]
+	fn empty_schedules_have_no_events() {
+		assert_eq!("".parse::<KeySchedule>(), Ok(KeySchedule::default()));
+		assert_eq!(" ,, ".parse::<KeySchedule>(), Ok(KeySchedule::default()));
+	}
+
+	#[test]
+	fn events_on_the_same_frame_keep_their_order() {
+		let schedule: KeySchedule = "3:-1 3:+1".parse().unwrap();
+		assert_eq!(schedule.events, vec![event(3, 1, false), event(3, 1, true)]);
+	}
+
+	#[test]
+	fn malformed_events_are_rejected() {
+		for entry in ["5", "x:1", "-1:1", "1:G", "1:10", "1:+", "1:", ":1", "1:+-1"] {
+			assert_eq!(
+				entry.parse::<KeySchedule>(),
+				Err(format!("'{}' isn't a key event - expected frame:+key, frame:-key or frame:key", entry)),
+			);
+		}
+	}
+
+	#[test]
+	fn the_bad_entry_is_named() {
+		assert_eq!(
+			"1:+2, 3:Z, 4:-2".parse::<KeySchedule>(),
+			Err(String::from("'3:Z' isn't a key event - expected frame:+key, frame:-key or frame:key")),
+		);
+	}
+
+	#[test]
+	fn apply_only_runs_the_frames_events() {
+		let schedule: KeySchedule = "1:2 1:+3".parse().unwrap();
+		let mut emulator: Emulator = Emulator::default();
+
+		schedule.apply(0, &mut emulator);
+		assert!(!emulator.get_keys()[2] && !emulator.get_keys()[3]);
+		schedule.apply(1, &mut emulator);
+		assert!(emulator.get_keys()[2] && emulator.get_keys()[3]);
+		schedule.apply(2, &mut emulator);
+		assert!(!emulator.get_keys()[2] && emulator.get_keys()[3]);
+	}
+}
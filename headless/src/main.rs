@@ -0,0 +1,304 @@
+// runs a game with no window, sound or keyboard, for CI and scripts
+//
+// the game runs for a number of frames (or instructions), with keys pressed
+// from a schedule (see keys.rs), then the screen is written out as an ASCII
+// dump, a PBM or a PNG, and its hash is printed
+// the random seed is fixed, so the same game and keys always give the same hash
+//
+// exits with 0 if the game ran, 1 if an instruction went wrong, and 2 if the
+// game or the arguments couldn't be used
+
+// crates
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use backend::{Emulator, EmulatorError, Platform, Profile};
+
+// modules
+mod image;
+mod keys;
+
+use keys::KeySchedule;
+
+// printed when the arguments don't make sense
+const USAGE: &str = "Usage: cargo run -p headless -- [--frames n | --cycles n] [--platform platform_name] [--seed n] [--keys schedule] [--output path] path_to_game";
+// a second, unless told otherwise
+const DEFAULT_FRAMES: u64 = 60;
+const EXIT_EMULATOR_ERROR: u8 = 1;
+const EXIT_BAD_INPUT: u8 = 2;
+
+// when to stop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Limit {
+	Frames(u64),
+	Cycles(u64),
+}
+
+struct Options {
+	limit: Limit,
+	profile: Profile,
+	seed: u64,
+	keys: KeySchedule,
+	// .pbm, .png, or anything else for ASCII - None prints ASCII instead
+	output_path: Option<String>,
+	game_path: String,
+}
+
+// how far the game got
+struct Summary {
+	frames: u64,
+	cycles: u64,
+	error: Option<EmulatorError>,
+}
+
+fn main() -> ExitCode {
+	let arguments: Vec<String> = env::args().collect();
+
+	let options: Options = match parse_arguments(&arguments) {
+		Ok(options) => options,
+		Err(message) => {
+			eprintln!("{}", message);
+			return ExitCode::from(EXIT_BAD_INPUT);
+		},
+	};
+
+	let rom: Vec<u8> = match load_game(&options.game_path) {
+		Ok(rom) => rom,
+		Err(message) => {
+			eprintln!("{}", message);
+			return ExitCode::from(EXIT_BAD_INPUT);
+		},
+	};
+	let mut emulator: Emulator = match Emulator::with_seed(options.profile, options.seed) {
+		Ok(emulator) => emulator,
+		Err(error) => {
+			eprintln!("Unable to start: {}", error);
+			return ExitCode::from(EXIT_BAD_INPUT);
+		},
+	};
+	if let Err(error) = emulator.load_rom(&rom) {
+		eprintln!("Unable to load ROM: {}", error);
+		return ExitCode::from(EXIT_BAD_INPUT);
+	}
+
+	let summary: Summary = run(&mut emulator, &options);
+
+	// the screen is written even after an error, to help work out what went wrong
+	if let Err(message) = write_screen(&emulator, options.output_path.as_deref()) {
+		eprintln!("{}", message);
+		return ExitCode::from(EXIT_BAD_INPUT);
+	}
+	println!("frames: {} cycles: {}", summary.frames, summary.cycles);
+	println!("hash: {:016x}", image::frame_hash(&emulator));
+
+	if let Some(error) = summary.error {
+		eprintln!("Emulator error: {}", error);
+		return ExitCode::from(EXIT_EMULATOR_ERROR);
+	}
+	ExitCode::SUCCESS
+}
+
+// options in any order, and exactly one game path
+fn parse_arguments(arguments: &[String]) -> Result<Options, String> {
+	let mut options: Options = Options {
+		limit: Limit::Frames(DEFAULT_FRAMES),
+		profile: Profile::default(),
+		seed: 0,
+		keys: KeySchedule::default(),
+		output_path: None,
+		game_path: String::new(),
+	};
+	let mut game_path: Option<&String> = None;
+
+	let mut remaining = arguments.iter().skip(1);
+	while let Some(argument) = remaining.next() {
+		// every option takes a value
+		let mut value = || remaining.next().ok_or_else(|| format!("{} needs a value", argument));
+
+		match argument.as_str() {
+			"--frames" => options.limit = Limit::Frames(parse_number(argument, value()?)?),
+			"--cycles" => options.limit = Limit::Cycles(parse_number(argument, value()?)?),
+			"--platform" => options.profile = value()?.parse::<Platform>()?.profile(),
+			"--seed" => options.seed = parse_number(argument, value()?)?,
+			"--keys" => options.keys = value()?.parse()?,
+			"--output" => options.output_path = Some(value()?.clone()),
+			_ if game_path.is_none() && !argument.starts_with("--") => game_path = Some(argument),
+			_ => return Err(String::from(USAGE)),
+		}
+	}
+
+	options.game_path = game_path.ok_or(USAGE)?.clone();
+	Ok(options)
+}
+
+fn parse_number(option: &str, value: &str) -> Result<u64, String> {
+	value.parse().map_err(|_| format!("{} needs a number, not '{}'", option, value))
+}
+
+// Octo source is compiled first, anything else is already a ROM
+fn load_game(game_path: &str) -> Result<Vec<u8>, String> {
+	let path: &Path = Path::new(game_path);
+
+	if path.extension().is_some_and(|extension| extension == "8o") {
+		octo::compile_file(path).map(|assembly| assembly.bytes).map_err(|error| format!("Unable to compile: {}", error))
+	} else {
+		fs::read(path).map_err(|error| format!("Unable to open {}: {}", game_path, error))
+	}
+}
+
+// run until the limit, the game exits, or an instruction goes wrong
+// cycles are instructions that ran, like a trace's - a frame spent waiting
+// for the display after drawing a sprite ends early rather than counting
+// ticks that do nothing
+fn run(emulator: &mut Emulator, options: &Options) -> Summary {
+	let ticks_per_frame: usize = emulator.get_profile().ticks_per_frame;
+	let mut summary: Summary = Summary { frames: 0, cycles: 0, error: None };
+
+	loop {
+		if options.limit == Limit::Frames(summary.frames) {
+			return summary;
+		}
+		options.keys.apply(summary.frames, emulator);
+
+		for _ in 0..ticks_per_frame {
+			if emulator.is_halted() || options.limit == Limit::Cycles(summary.cycles) {
+				return summary;
+			}
+			if emulator.is_waiting_for_vblank() {
+				break;
+			}
+			if let Err(error) = emulator.tick() {
+				summary.error = Some(error);
+				return summary;
+			}
+			summary.cycles += 1;
+		}
+
+		emulator.tick_timers();
+		summary.frames += 1;
+	}
+}
+
+// to a file in the format its extension says, or ASCII to stdout
+fn write_screen(emulator: &Emulator, output_path: Option<&str>) -> Result<(), String> {
+	let Some(output_path) = output_path else {
+		print!("{}", image::to_ascii(emulator));
+		return Ok(());
+	};
+
+	let path: &Path = Path::new(output_path);
+	let bytes: Vec<u8> = match path.extension().and_then(|extension| extension.to_str()) {
+		Some("pbm") => image::to_pbm(emulator).into_bytes(),
+		Some("png") => image::to_png(emulator).map_err(|error| format!("Unable to make PNG: {}", error))?,
+		_ => image::to_ascii(emulator).into_bytes(),
+	};
+
+	fs::write(path, bytes).map_err(|error| format!("Unable to write {}: {}", output_path, error))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// CLS, then jump to itself forever
+	const LOOPING_ROM: [u8; 4] = [0x00, 0xE0, 0x12, 0x02];
+
+	fn arguments(arguments: &[&str]) -> Vec<String> {
+		std::iter::once("headless").chain(arguments.iter().copied()).map(String::from).collect()
+	}
+
+	fn options(platform: Platform, limit: Limit) -> Options {
+		Options {
+			limit,
+			profile: platform.profile(),
+			seed: 0,
+			keys: KeySchedule::default(),
+			output_path: None,
+			game_path: String::new(),
+		}
+	}
+
+	fn run_rom(rom: &[u8], options: &Options) -> (Summary, Emulator) {
+		let mut emulator: Emulator = Emulator::with_seed(options.profile, options.seed).unwrap();
+		emulator.load_rom(rom).unwrap();
+		(run(&mut emulator, options), emulator)
+	}
+
+	#[test]
+	fn options_in_any_order() {
+		let options: Options = parse_arguments(&arguments(&["--seed", "7", "game.ch8", "--cycles", "100", "--platform", "xo-chip", "--output", "out.png", "--keys", "1:+2"])).unwrap();
+
+		assert_eq!(options.limit, Limit::Cycles(100));
+		assert_eq!(options.profile, Platform::XoChip.profile());
+		assert_eq!(options.seed, 7);
+		assert_eq!(options.keys, "1:+2".parse().unwrap());
+		assert_eq!(options.output_path.as_deref(), Some("out.png"));
+		assert_eq!(options.game_path, "game.ch8");
+	}
+
+	#[test]
+	fn defaults_without_options() {
+		let options: Options = parse_arguments(&arguments(&["game.ch8"])).unwrap();
+
+		assert_eq!(options.limit, Limit::Frames(DEFAULT_FRAMES));
+		assert_eq!(options.profile, Profile::default());
+		assert_eq!(options.output_path, None);
+	}
+
+	#[test]
+	fn bad_arguments_are_rejected() {
+		assert_eq!(parse_arguments(&arguments(&[])).err(), Some(String::from(USAGE)));
+		assert_eq!(parse_arguments(&arguments(&["a.ch8", "b.ch8"])).err(), Some(String::from(USAGE)));
+		assert_eq!(parse_arguments(&arguments(&["--speed", "2", "a.ch8"])).err(), Some(String::from(USAGE)));
+		assert_eq!(parse_arguments(&arguments(&["a.ch8", "--frames"])).err(), Some(String::from("--frames needs a value")));
+		assert_eq!(parse_arguments(&arguments(&["--seed", "-1", "a.ch8"])).err(), Some(String::from("--seed needs a number, not '-1'")));
+		assert!(parse_arguments(&arguments(&["--keys", "1", "a.ch8"])).is_err());
+		assert!(parse_arguments(&arguments(&["--platform", "nes", "a.ch8"])).is_err());
+	}
+
+	#[test]
+	fn run_stops_at_the_frame_limit() {
+		let (summary, _) = run_rom(&LOOPING_ROM, &options(Platform::Chip48, Limit::Frames(3)));
+
+		assert_eq!(summary.frames, 3);
+		assert_eq!(summary.cycles, 3 * 30);
+		assert!(summary.error.is_none());
+	}
+
+	#[test]
+	fn run_stops_at_the_cycle_limit() {
+		let (summary, emulator) = run_rom(&LOOPING_ROM, &options(Platform::Chip48, Limit::Cycles(45)));
+
+		assert_eq!(summary.cycles, 45);
+		// partway through the second frame
+		assert_eq!(summary.frames, 1);
+		assert_eq!(emulator.get_pc(), 0x202);
+		assert!(summary.error.is_none());
+	}
+
+	#[test]
+	fn frames_waiting_for_the_display_only_count_instructions_that_ran() {
+		// DRW, then jump back to it - with display wait one sprite is drawn a frame
+		let rom: [u8; 4] = [0xD0, 0x05, 0x12, 0x00];
+		let (summary, _) = run_rom(&rom, &options(Platform::CosmacVip, Limit::Frames(3)));
+
+		// the first frame only draws, the rest jump back and draw
+		assert_eq!(summary.frames, 3);
+		assert_eq!(summary.cycles, 1 + 2 + 2);
+	}
+
+	#[test]
+	fn run_stops_at_an_error_or_exit() {
+		let (summary, _) = run_rom(&[0x50, 0x01], &options(Platform::Chip48, Limit::Frames(3)));
+		assert_eq!((summary.frames, summary.cycles), (0, 0));
+		assert!(matches!(summary.error, Some(EmulatorError::UnknownOpcode { pc: 0x200, opcode: 0x5001 })));
+
+		// 00FD is the SUPER-CHIP exit
+		let (summary, emulator) = run_rom(&[0x00, 0xE0, 0x00, 0xFD], &options(Platform::Chip48, Limit::Frames(3)));
+		assert_eq!((summary.frames, summary.cycles), (0, 2));
+		assert!(summary.error.is_none());
+		assert!(emulator.is_halted());
+	}
+}
@@ -0,0 +1,64 @@
+// runs the headless binary on ROMs written to a temporary directory, and
+// checks its exit code and what it prints
+
+// crates
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Output};
+
+// CLS, then jump to itself forever
+const LOOPING_ROM: [u8; 4] = [0x00, 0xE0, 0x12, 0x02];
+// 5001 isn't an instruction on any platform
+const UNKNOWN_OPCODE_ROM: [u8; 2] = [0x50, 0x01];
+
+// write the ROM to a file of its own, then run it with the arguments
+fn headless(name: &str, rom: &[u8], arguments: &[&str]) -> Output {
+	let directory: PathBuf = env::temp_dir().join(format!("headless-{}-{}", name, process::id()));
+	fs::create_dir_all(&directory).unwrap();
+	let rom_path: PathBuf = directory.join("game.ch8");
+	fs::write(&rom_path, rom).unwrap();
+
+	let output: Output = Command::new(env!("CARGO_BIN_EXE_headless")).args(arguments).arg(&rom_path).output().unwrap();
+	let _ = fs::remove_dir_all(&directory);
+	output
+}
+
+#[test]
+fn a_game_that_runs_exits_with_0() {
+	let output: Output = headless("runs", &LOOPING_ROM, &["--frames", "3"]);
+	let text: String = String::from_utf8(output.stdout).unwrap();
+
+	assert_eq!(output.status.code(), Some(0));
+	assert!(text.contains("frames: 3 cycles: "));
+	assert!(text.contains("hash: "));
+	assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn an_emulator_error_exits_with_1() {
+	let output: Output = headless("error", &UNKNOWN_OPCODE_ROM, &[]);
+
+	assert_eq!(output.status.code(), Some(1));
+	// the screen and hash are still printed
+	assert!(String::from_utf8(output.stdout).unwrap().contains("frames: 0 cycles: 0"));
+	assert!(String::from_utf8(output.stderr).unwrap().starts_with("Emulator error: "));
+}
+
+#[test]
+fn a_missing_game_exits_with_2() {
+	let output: Output = Command::new(env!("CARGO_BIN_EXE_headless")).arg("no-such-game.ch8").output().unwrap();
+
+	assert_eq!(output.status.code(), Some(2));
+	assert!(output.stdout.is_empty());
+	assert!(String::from_utf8(output.stderr).unwrap().starts_with("Unable to open no-such-game.ch8: "));
+}
+
+#[test]
+fn bad_arguments_exit_with_2() {
+	let output: Output = headless("arguments", &LOOPING_ROM, &["--frames", "lots"]);
+
+	assert_eq!(output.status.code(), Some(2));
+	assert!(output.stdout.is_empty());
+	assert_eq!(String::from_utf8(output.stderr).unwrap(), "--frames needs a number, not 'lots'\n");
+}
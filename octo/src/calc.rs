@@ -0,0 +1,131 @@
+// :calc expressions - like Octo, these work right to left with no operator
+// precedence, so "2 * 3 + 1" is 2 * (3 + 1), and brackets group as usual
+
+use crate::lexer::{self, Token};
+
+// what a name in an expression refers to
+// the compiler looks up constants, labels, HERE and ROM bytes for @
+pub trait Scope {
+	fn lookup(&self, name: &str) -> Option<f64>;
+	fn byte_at(&self, address: f64) -> Option<f64>;
+}
+
+// errors are (token index, message) so the compiler can say where
+pub type CalcError = (usize, String);
+
+// work out the whole of tokens as one expression
+pub fn evaluate(tokens: &[Token], scope: &dyn Scope) -> Result<f64, CalcError> {
+	let mut calc = Calc { tokens, position: 0, scope };
+	let value: f64 = calc.expression()?;
+
+	if calc.position < tokens.len() {
+		return Err((calc.position, format!("unexpected '{}' in expression", tokens[calc.position].text)));
+	}
+	Ok(value)
+}
+
+struct Calc<'a> {
+	tokens: &'a [Token],
+	position: usize,
+	scope: &'a dyn Scope,
+}
+
+impl<'a> Calc<'a> {
+	fn next(&mut self) -> Result<&'a str, CalcError> {
+		let token: &Token = self.tokens.get(self.position).ok_or((self.position, String::from("expression ended early")))?;
+		self.position += 1;
+		Ok(&token.text)
+	}
+
+	// term, optionally followed by an operator and the rest of the expression
+	fn expression(&mut self) -> Result<f64, CalcError> {
+		let left: f64 = self.term()?;
+
+		let Some(operator) = self.tokens.get(self.position).map(|token| token.text.as_str()) else {
+			return Ok(left);
+		};
+		if operator == ")" {
+			return Ok(left);
+		}
+		let operator_position: usize = self.position;
+		self.position += 1;
+		let right: f64 = self.expression()?;
+
+		// bitwise operators work on whole numbers
+		let (left_int, right_int): (i64, i64) = (left as i64, right as i64);
+		let value: f64 = match operator {
+			"+" => left + right,
+			"-" => left - right,
+			"*" => left * right,
+			"/" => left / right,
+			"%" => left % right,
+			"&" => (left_int & right_int) as f64,
+			"|" => (left_int | right_int) as f64,
+			"^" => (left_int ^ right_int) as f64,
+			"<<" => (left_int << (right_int & 63)) as f64,
+			">>" => (left_int >> (right_int & 63)) as f64,
+			"pow" => left.powf(right),
+			"min" => left.min(right),
+			"max" => left.max(right),
+			"<" => (left < right) as i64 as f64,
+			">" => (left > right) as i64 as f64,
+			"<=" => (left <= right) as i64 as f64,
+			">=" => (left >= right) as i64 as f64,
+			"==" => (left == right) as i64 as f64,
+			"!=" => (left != right) as i64 as f64,
+			_ => return Err((operator_position, format!("unknown operator '{}'", operator))),
+		};
+
+		Ok(value)
+	}
+
+	// number, name, bracketed expression or unary operator
+	fn term(&mut self) -> Result<f64, CalcError> {
+		let position: usize = self.position;
+		let text: &str = self.next()?;
+
+		if text == "(" {
+			let value: f64 = self.expression()?;
+			if self.next()? != ")" {
+				return Err((self.position - 1, String::from("expected ')'")));
+			}
+			return Ok(value);
+		}
+
+		let unary: Option<fn(f64) -> f64> = match text {
+			"-" => Some(|value| -value),
+			"~" => Some(|value| !(value as i64) as f64),
+			"!" => Some(|value| (value == 0.0) as i64 as f64),
+			"sin" => Some(f64::sin),
+			"cos" => Some(f64::cos),
+			"tan" => Some(f64::tan),
+			"exp" => Some(f64::exp),
+			"log" => Some(f64::ln),
+			"abs" => Some(f64::abs),
+			"sqrt" => Some(f64::sqrt),
+			"sign" => Some(f64::signum),
+			"ceil" => Some(f64::ceil),
+			"floor" => Some(f64::floor),
+			_ => None,
+		};
+		if let Some(function) = unary {
+			return Ok(function(self.term()?));
+		}
+
+		// byte already compiled at an address
+		if text == "@" {
+			let operand_position: usize = self.position;
+			let address: f64 = self.term()?;
+			return self.scope.byte_at(address).ok_or((operand_position, format!("there's no byte at {}", address)));
+		}
+
+		if let Some(value) = lexer::parse_number(text) {
+			return Ok(value as f64);
+		}
+		match text {
+			"PI" => Ok(std::f64::consts::PI),
+			"E" => Ok(std::f64::consts::E),
+			_ => self.scope.lookup(text).ok_or((position, format!("'{}' isn't a constant or a label defined before here", text))),
+		}
+	}
+}
@@ -0,0 +1,797 @@
+// crates
+use std::collections::HashMap;
+
+use assembler::{AssembleError, Assembly, SourceLocation, SourceMapEntry};
+
+use crate::calc::{self, Scope};
+use crate::lexer::{self, Token};
+
+// Octo programs start here, with a jump to main
+const START_ADDRESS: u16 = 0x200;
+// macro expansions allowed in one program - more than this means one calls itself
+const MAX_MACRO_EXPANSIONS: usize = 1 << 16;
+
+// :macro name arguments { body }
+struct Macro {
+	arguments: Vec<String>,
+	body: Vec<Token>,
+}
+
+// bytes that need a label that wasn't defined yet when they were compiled
+struct Fixup {
+	// address of the first byte to change
+	address: u16,
+	kind: FixupKind,
+	// the label, and where it was used
+	token: Token,
+}
+
+#[derive(Clone, Copy)]
+enum FixupKind {
+	// low 12 bits of an instruction, e.g. jump or i :=
+	Address,
+	// whole 16 bit word, e.g. i := long or :pointer
+	Word,
+	// high byte of the address for :unpack, with the nibble if it isn't long
+	HighByte(Option<u8>),
+	// low byte of the address for :unpack
+	LowByte,
+}
+
+// open if, else or loop, waiting for its end or again
+enum Block {
+	// address of the jump over the body, patched by else or end
+	If { jump: u16, token: Token },
+	// address of the jump over the else part, patched by end
+	Else { jump: u16, token: Token },
+	// start of the loop, and the jumps out of it from while
+	Loop { start: u16, breaks: Vec<u16>, token: Token },
+}
+
+// condition from if or while, e.g. "v0 == 5" or "v1 -key"
+struct Condition {
+	register: u8,
+	operator: String,
+	operand: Operand,
+	token: Token,
+}
+
+// right hand side of an assignment or comparison
+enum Operand {
+	Register(u8),
+	Byte(u8),
+	None,
+}
+
+pub struct Compiler {
+	file: String,
+	// tokens still to compile, last first - macros push their bodies on the end
+	tokens: Vec<Token>,
+	// index 0 is START_ADDRESS
+	rom: Vec<u8>,
+	// where the next byte goes - wider than an address so running off the end can be caught
+	here: u32,
+	labels: HashMap<String, u16>,
+	constants: HashMap<String, f64>,
+	aliases: HashMap<String, u8>,
+	macros: HashMap<String, Macro>,
+	macro_expansions: usize,
+	fixups: Vec<Fixup>,
+	blocks: Vec<Block>,
+	source_map: Vec<SourceMapEntry>,
+}
+
+impl Compiler {
+	// new constructor for Compiler class - file is only used in errors
+	pub fn new(file: &str, source: &str) -> Self {
+		let mut tokens: Vec<Token> = lexer::tokenize(source);
+		tokens.reverse();
+
+		Self {
+			file: file.to_string(),
+			tokens,
+			rom: Vec::new(),
+			here: START_ADDRESS as u32,
+			labels: HashMap::new(),
+			constants: HashMap::new(),
+			aliases: HashMap::new(),
+			macros: HashMap::new(),
+			macro_expansions: 0,
+			fixups: Vec::new(),
+			blocks: Vec::new(),
+			source_map: Vec::new(),
+		}
+	}
+
+	pub fn compile(mut self) -> Result<Assembly, AssembleError> {
+		// jump to main, filled in at the end
+		self.emit_opcode(0x1000, None)?;
+
+		while let Some(token) = self.tokens.pop() {
+			let start: u32 = self.here;
+			self.statement(token.clone())?;
+
+			if self.here > start {
+				self.source_map.push(SourceMapEntry {
+					address: start as u16,
+					length: (self.here - start) as u16,
+					location: SourceLocation { file: self.file.clone(), line: token.line },
+				});
+			}
+		}
+
+		if let Some(block) = self.blocks.last() {
+			let (token, message) = match block {
+				Block::If { token, .. } | Block::Else { token, .. } => (token, "this 'begin' has no 'end'"),
+				Block::Loop { token, .. } => (token, "this 'loop' has no 'again'"),
+			};
+			return Err(self.error(token, message));
+		}
+
+		let main: u16 = match self.labels.get("main") {
+			Some(&main) if main <= 0xFFF => main,
+			Some(_) => return Err(self.file_error("': main' has to be below 0x1000 so it can be jumped to")),
+			None => return Err(self.file_error("programs need a ': main' label to start from")),
+		};
+		self.rom[0] = 0x10 | (main >> 8) as u8;
+		self.rom[1] = main as u8;
+
+		for fixup in std::mem::take(&mut self.fixups) {
+			let value: i64 = match self.value_of(&fixup.token.text) {
+				Some(value) => value,
+				None => return Err(self.error(&fixup.token, &format!("'{}' is never defined", fixup.token.text))),
+			};
+			self.apply_fixup(&fixup, value)?;
+		}
+
+		let mut source_map: Vec<SourceMapEntry> = std::mem::take(&mut self.source_map);
+		source_map.sort_by_key(|entry| entry.address);
+
+		Ok(Assembly {
+			base: START_ADDRESS,
+			bytes: self.rom,
+			labels: self.labels.into_iter().collect(),
+			source_map,
+		})
+	}
+
+	// error pointing at a token
+	fn error(&self, token: &Token, message: &str) -> AssembleError {
+		AssembleError {
+			file: self.file.clone(),
+			line: token.line,
+			column: token.column,
+			message: message.to_string(),
+		}
+	}
+
+	// error about the program as a whole
+	fn file_error(&self, message: &str) -> AssembleError {
+		AssembleError { file: self.file.clone(), line: 0, column: 0, message: message.to_string() }
+	}
+
+	// next token, or an error at the one before if the source has run out
+	fn next(&mut self, after: &Token, expected: &str) -> Result<Token, AssembleError> {
+		self.tokens.pop().ok_or_else(|| self.error(after, &format!("expected {} after '{}'", expected, after.text)))
+	}
+
+	// next token, which has to be text
+	fn expect(&mut self, after: &Token, text: &str) -> Result<Token, AssembleError> {
+		let token: Token = self.next(after, &format!("'{}'", text))?;
+		if token.text != text {
+			return Err(self.error(&token, &format!("expected '{}', found '{}'", text, token.text)));
+		}
+		Ok(token)
+	}
+
+	// next token's text, without taking it
+	fn peek(&self) -> Option<&str> {
+		self.tokens.last().map(|token| token.text.as_str())
+	}
+
+	fn statement(&mut self, token: Token) -> Result<(), AssembleError> {
+		match token.text.as_str() {
+			// : name
+			":" => {
+				let name: Token = self.next(&token, "a label name")?;
+				let address: u16 = self.here as u16;
+				self.define_label(&name, address)?;
+			},
+			// :next name - label on the second byte of the next instruction, for self modifying code
+			":next" => {
+				let name: Token = self.next(&token, "a label name")?;
+				let address: u16 = (self.here + 1) as u16;
+				self.define_label(&name, address)?;
+			},
+			":const" => {
+				let name: Token = self.next(&token, "a constant name")?;
+				self.check_name(&name)?;
+				let value: Token = self.next(&name, "a value")?;
+				let value: f64 = self.value_of(&value.text).ok_or_else(|| self.error(&value, &format!("'{}' isn't a number or a constant", value.text)))? as f64;
+				self.constants.insert(name.text, value);
+			},
+			":calc" => {
+				let name: Token = self.next(&token, "a constant name")?;
+				self.check_name(&name)?;
+				let value: f64 = self.calc(&name)?;
+				self.constants.insert(name.text, value);
+			},
+			":alias" => {
+				let name: Token = self.next(&token, "an alias name")?;
+				self.check_name(&name)?;
+				let register: u8 = self.register(&name)?;
+				self.aliases.insert(name.text, register);
+			},
+			":macro" => self.define_macro(&token)?,
+			":org" => {
+				let address: i64 = self.number_or_calc(&token)?;
+				if !(START_ADDRESS as i64..=0xFFFF).contains(&address) {
+					return Err(self.error(&token, &format!("address {:#X} is outside of the program's memory ({:#X} to 0xFFFF)", address, START_ADDRESS)));
+				}
+				self.here = address as u32;
+			},
+			":byte" => {
+				let value: i64 = self.number_or_calc(&token)?;
+				self.emit(byte_in_range(value).ok_or_else(|| self.error(&token, &format!("{} doesn't fit in a byte", value)))?, &token)?;
+			},
+			":pointer" => {
+				if self.peek() == Some("{") {
+					let value: i64 = self.calc(&token)? as i64;
+					self.emit_opcode(value as u16, Some(&token))?;
+				} else {
+					let label: Token = self.next(&token, "an address")?;
+					self.emit_address(FixupKind::Word, 0, &label)?;
+				}
+			},
+			// :unpack N label or :unpack long label - v0 and v1 = the address
+			":unpack" => {
+				let kind: Token = self.next(&token, "a nibble or 'long'")?;
+				let nibble: Option<u8> = if kind.text == "long" {
+					None
+				} else {
+					let value: i64 = self.value_of(&kind.text).ok_or_else(|| self.error(&kind, "expected a nibble or 'long'"))?;
+					Some(value as u8 & 0xF)
+				};
+				let label: Token = self.next(&kind, "a label")?;
+
+				self.emit_address(FixupKind::HighByte(nibble), 0x6000, &label)?;
+				self.emit_address(FixupKind::LowByte, 0x6100, &label)?;
+			},
+			// debugger hints - the emulator's debugger sets its own
+			":breakpoint" => {
+				self.next(&token, "a breakpoint name")?;
+			},
+			":monitor" => {
+				self.next(&token, "an address")?;
+				self.next(&token, "a length or format")?;
+			},
+
+			";" | "return" => self.emit_opcode(0x00EE, Some(&token))?,
+			"clear" => self.emit_opcode(0x00E0, Some(&token))?,
+			"scroll-right" => self.emit_opcode(0x00FB, Some(&token))?,
+			"scroll-left" => self.emit_opcode(0x00FC, Some(&token))?,
+			"exit" => self.emit_opcode(0x00FD, Some(&token))?,
+			"lores" => self.emit_opcode(0x00FE, Some(&token))?,
+			"hires" => self.emit_opcode(0x00FF, Some(&token))?,
+			"audio" => self.emit_opcode(0xF002, Some(&token))?,
+			"scroll-down" => {
+				let n: u8 = self.nibble(&token)?;
+				self.emit_opcode(0x00C0 | n as u16, Some(&token))?;
+			},
+			"scroll-up" => {
+				let n: u8 = self.nibble(&token)?;
+				self.emit_opcode(0x00D0 | n as u16, Some(&token))?;
+			},
+			"plane" => {
+				let n: u8 = self.nibble(&token)?;
+				self.emit_opcode(0xF001 | (n as u16) << 8, Some(&token))?;
+			},
+			"bcd" => self.register_opcode(0xF033, &token)?,
+			"saveflags" => self.register_opcode(0xF075, &token)?,
+			"loadflags" => self.register_opcode(0xF085, &token)?,
+			// save vx, or save vx - vy for XO-CHIP
+			"save" | "load" => {
+				let x: u8 = self.register(&token)?;
+				if self.peek() == Some("-") {
+					let dash: Token = self.next(&token, "'-'")?;
+					let y: u8 = self.register(&dash)?;
+					let opcode: u16 = if token.text == "save" {0x5002} else {0x5003};
+					self.emit_opcode(opcode | (x as u16) << 8 | (y as u16) << 4, Some(&token))?;
+				} else {
+					let opcode: u16 = if token.text == "save" {0xF055} else {0xF065};
+					self.emit_opcode(opcode | (x as u16) << 8, Some(&token))?;
+				}
+			},
+			"sprite" => {
+				let x: u8 = self.register(&token)?;
+				let y: u8 = self.register(&token)?;
+				let n: u8 = self.nibble(&token)?;
+				self.emit_opcode(0xD000 | (x as u16) << 8 | (y as u16) << 4 | n as u16, Some(&token))?;
+			},
+			"jump" | "jump0" | "native" => {
+				let opcode: u16 = match token.text.as_str() {
+					"jump" => 0x1000,
+					"jump0" => 0xB000,
+					_ => 0x0000,
+				};
+				let target: Token = self.next(&token, "an address")?;
+				self.emit_address(FixupKind::Address, opcode, &target)?;
+			},
+			"delay" | "buzzer" | "pitch" => {
+				let assign: Token = self.expect(&token, ":=")?;
+				let x: u8 = self.register(&assign)?;
+				let opcode: u16 = match token.text.as_str() {
+					"delay" => 0xF015,
+					"buzzer" => 0xF018,
+					_ => 0xF03A,
+				};
+				self.emit_opcode(opcode | (x as u16) << 8, Some(&token))?;
+			},
+			"i" => self.i_statement(&token)?,
+
+			"if" => {
+				let condition: Condition = self.condition(&token)?;
+				let keyword: Token = self.next(&token, "'then' or 'begin'")?;
+				match keyword.text.as_str() {
+					// skip the next statement if the condition is false
+					"then" => self.emit_condition(&condition, false)?,
+					// skip the jump over the body if the condition is true
+					"begin" => {
+						self.emit_condition(&condition, true)?;
+						let jump: u16 = self.here as u16;
+						self.emit_opcode(0x1000, Some(&keyword))?;
+						self.blocks.push(Block::If { jump, token: keyword });
+					},
+					_ => return Err(self.error(&keyword, &format!("expected 'then' or 'begin', found '{}'", keyword.text))),
+				}
+			},
+			"else" => {
+				let Some(Block::If { jump, .. }) = self.blocks.pop() else {
+					return Err(self.error(&token, "'else' without 'if ... begin'"));
+				};
+				let else_jump: u16 = self.here as u16;
+				self.emit_opcode(0x1000, Some(&token))?;
+				self.patch_jump(jump, &token)?;
+				self.blocks.push(Block::Else { jump: else_jump, token });
+			},
+			"end" => {
+				match self.blocks.pop() {
+					Some(Block::If { jump, .. } | Block::Else { jump, .. }) => self.patch_jump(jump, &token)?,
+					_ => return Err(self.error(&token, "'end' without 'if ... begin'")),
+				}
+			},
+			"loop" => {
+				self.blocks.push(Block::Loop { start: self.here as u16, breaks: Vec::new(), token });
+			},
+			// leave the loop if the condition is false
+			"while" => {
+				let condition: Condition = self.condition(&token)?;
+				self.emit_condition(&condition, true)?;
+				let jump: u16 = self.here as u16;
+				self.emit_opcode(0x1000, Some(&token))?;
+
+				match self.blocks.iter_mut().rev().find(|block| matches!(block, Block::Loop { .. })) {
+					Some(Block::Loop { breaks, .. }) => breaks.push(jump),
+					_ => return Err(self.error(&token, "'while' outside of a loop")),
+				}
+			},
+			"again" => {
+				let Some(Block::Loop { start, breaks, .. }) = self.blocks.pop() else {
+					return Err(self.error(&token, "'again' without 'loop'"));
+				};
+				self.emit_address_value(0x1000, start as i64, &token)?;
+				for jump in breaks {
+					self.patch_jump(jump, &token)?;
+				}
+			},
+
+			"{" | "}" | "then" | "begin" => {
+				return Err(self.error(&token, &format!("unexpected '{}'", token.text)));
+			},
+			text if text.starts_with(':') => {
+				return Err(self.error(&token, &format!("unknown directive '{}'", text)));
+			},
+			text if self.macros.contains_key(text) => self.expand_macro(&token)?,
+			text if self.register_number(text).is_some() => self.assignment(&token)?,
+
+			// a number or constant on its own is a byte of data, anything else calls a subroutine
+			text => match self.value_of(text) {
+				Some(value) if lexer::parse_number(text).is_some() || self.constants.contains_key(text) => {
+					self.emit(byte_in_range(value).ok_or_else(|| self.error(&token, &format!("{} doesn't fit in a byte", value)))?, &token)?;
+				},
+				_ => self.emit_address(FixupKind::Address, 0x2000, &token)?,
+			},
+		}
+
+		Ok(())
+	}
+
+	// i := address, i := long address, i := hex vx, i := bighex vx or i += vx
+	fn i_statement(&mut self, token: &Token) -> Result<(), AssembleError> {
+		let operator: Token = self.next(token, "':=' or '+='")?;
+		match operator.text.as_str() {
+			":=" => {
+				let value: Token = self.next(&operator, "a value")?;
+				match value.text.as_str() {
+					"hex" => self.register_opcode(0xF029, &value),
+					"bighex" => self.register_opcode(0xF030, &value),
+					"long" => {
+						self.emit_opcode(0xF000, Some(token))?;
+						let address: Token = self.next(&value, "an address")?;
+						self.emit_address(FixupKind::Word, 0, &address)
+					},
+					_ => self.emit_address(FixupKind::Address, 0xA000, &value),
+				}
+			},
+			"+=" => self.register_opcode(0xF01E, &operator),
+			_ => Err(self.error(&operator, &format!("expected ':=' or '+=', found '{}'", operator.text))),
+		}
+	}
+
+	// vx := ..., vx += ... and so on
+	fn assignment(&mut self, token: &Token) -> Result<(), AssembleError> {
+		let x: u16 = self.register_number(&token.text).unwrap_or(0) as u16;
+		let operator: Token = self.next(token, "an operator")?;
+		let operand: Token = self.next(&operator, "a value")?;
+
+		let operand_register: Option<u16> = self.register_number(&operand.text).map(|y| y as u16);
+		// registers only
+		let register_opcode = |base: u16| operand_register.map(|y| base | x << 8 | y << 4);
+
+		let opcode: Option<u16> = match operator.text.as_str() {
+			":=" => match operand.text.as_str() {
+				"random" => {
+					let mask: u8 = self.byte(&operand)?;
+					Some(0xC000 | x << 8 | mask as u16)
+				},
+				"key" => Some(0xF00A | x << 8),
+				"delay" => Some(0xF007 | x << 8),
+				_ => match register_opcode(0x8000) {
+					Some(opcode) => Some(opcode),
+					None => Some(0x6000 | x << 8 | self.byte_value(&operand)? as u16),
+				},
+			},
+			"+=" => match register_opcode(0x8004) {
+				Some(opcode) => Some(opcode),
+				None => Some(0x7000 | x << 8 | self.byte_value(&operand)? as u16),
+			},
+			// subtracting a number is adding its negative
+			"-=" => match register_opcode(0x8005) {
+				Some(opcode) => Some(opcode),
+				None => Some(0x7000 | x << 8 | self.byte_value(&operand)?.wrapping_neg() as u16),
+			},
+			"=-" => register_opcode(0x8007),
+			"|=" => register_opcode(0x8001),
+			"&=" => register_opcode(0x8002),
+			"^=" => register_opcode(0x8003),
+			">>=" => register_opcode(0x8006),
+			"<<=" => register_opcode(0x800E),
+			_ => return Err(self.error(&operator, &format!("unknown operator '{}'", operator.text))),
+		};
+
+		match opcode {
+			Some(opcode) => self.emit_opcode(opcode, Some(token)),
+			None => Err(self.error(&operand, &format!("'{}' needs a register", operator.text))),
+		}
+	}
+
+	// vx op operand, where op is == != < > <= >= key or -key
+	fn condition(&mut self, token: &Token) -> Result<Condition, AssembleError> {
+		let register: u8 = self.register(token)?;
+		let operator: Token = self.next(token, "a comparison")?;
+
+		let operand: Operand = match operator.text.as_str() {
+			"key" | "-key" => Operand::None,
+			"==" | "!=" | "<" | ">" | "<=" | ">=" => {
+				let operand: Token = self.next(&operator, "a value")?;
+				match self.register_number(&operand.text) {
+					Some(y) => Operand::Register(y),
+					None => Operand::Byte(self.byte_value(&operand)?),
+				}
+			},
+			_ => return Err(self.error(&operator, &format!("unknown comparison '{}'", operator.text))),
+		};
+
+		Ok(Condition { register, operator: operator.text, operand, token: token.clone() })
+	}
+
+	// instructions that skip the next one if the condition is skip_when
+	fn emit_condition(&mut self, condition: &Condition, skip_when: bool) -> Result<(), AssembleError> {
+		let x: u16 = condition.register as u16;
+		let token: Option<&Token> = Some(&condition.token);
+
+		// skip if equal, or if not equal
+		let (equal, not_equal): (u16, u16) = match condition.operand {
+			Operand::Register(y) => (0x5000 | x << 8 | (y as u16) << 4, 0x9000 | x << 8 | (y as u16) << 4),
+			Operand::Byte(byte) => (0x3000 | x << 8 | byte as u16, 0x4000 | x << 8 | byte as u16),
+			Operand::None => (0xE09E | x << 8, 0xE0A1 | x << 8),
+		};
+
+		match condition.operator.as_str() {
+			"==" | "key" => self.emit_opcode(if skip_when {equal} else {not_equal}, token),
+			"!=" | "-key" => self.emit_opcode(if skip_when {not_equal} else {equal}, token),
+			// VF := operand, then VF -= vx gives VF = (operand >= vx), or
+			// VF =- vx gives VF = (vx >= operand)
+			operator => {
+				let load: u16 = match condition.operand {
+					Operand::Register(y) => 0x8F00 | (y as u16) << 4,
+					Operand::Byte(byte) => 0x6F00 | byte as u16,
+					Operand::None => 0x6F00,
+				};
+				// which subtraction, and the VF that means the condition is true
+				let (subtract, true_flag): (u16, u16) = match operator {
+					">" => (0x8F05 | x << 4, 0),
+					"<" => (0x8F07 | x << 4, 0),
+					">=" => (0x8F07 | x << 4, 1),
+					_ => (0x8F05 | x << 4, 1),
+				};
+
+				self.emit_opcode(load, token)?;
+				self.emit_opcode(subtract, token)?;
+				self.emit_opcode(if skip_when {0x3F00 | true_flag} else {0x4F00 | true_flag}, token)
+			},
+		}
+	}
+
+	// :macro name arguments { body }
+	fn define_macro(&mut self, token: &Token) -> Result<(), AssembleError> {
+		let name: Token = self.next(token, "a macro name")?;
+		self.check_name(&name)?;
+
+		let mut arguments: Vec<String> = Vec::new();
+		loop {
+			let argument: Token = self.next(&name, "'{'")?;
+			if argument.text == "{" {
+				break;
+			}
+			arguments.push(argument.text);
+		}
+
+		let body: Vec<Token> = self.braced(&name)?;
+		self.macros.insert(name.text, Macro { arguments, body });
+		Ok(())
+	}
+
+	// tokens up to the matching }, after the { has been taken
+	fn braced(&mut self, opening: &Token) -> Result<Vec<Token>, AssembleError> {
+		let mut body: Vec<Token> = Vec::new();
+		let mut depth: usize = 0;
+
+		loop {
+			let token: Token = self.tokens.pop().ok_or_else(|| self.error(opening, "'{' has no matching '}'"))?;
+			match token.text.as_str() {
+				"{" => depth += 1,
+				"}" if depth == 0 => return Ok(body),
+				"}" => depth -= 1,
+				_ => {},
+			}
+			body.push(token);
+		}
+	}
+
+	// put a macro's body in place of its name, with the arguments swapped in
+	fn expand_macro(&mut self, token: &Token) -> Result<(), AssembleError> {
+		self.macro_expansions += 1;
+		if self.macro_expansions > MAX_MACRO_EXPANSIONS {
+			return Err(self.error(token, "too many macro expansions - does a macro use itself?"));
+		}
+
+		let arguments: Vec<String> = self.macros[&token.text].arguments.clone();
+		let mut values: HashMap<String, Token> = HashMap::new();
+		for argument in arguments {
+			let value: Token = self.next(token, &format!("a value for '{}'", argument))?;
+			values.insert(argument, value);
+		}
+
+		let expanded: Vec<Token> = self.macros[&token.text].body.iter()
+			.map(|body_token| values.get(&body_token.text).cloned().unwrap_or_else(|| body_token.clone()))
+			.collect();
+		self.tokens.extend(expanded.into_iter().rev());
+		Ok(())
+	}
+
+	// { expression } after token
+	fn calc(&mut self, token: &Token) -> Result<f64, AssembleError> {
+		let opening: Token = self.expect(token, "{")?;
+		let expression: Vec<Token> = self.braced(&opening)?;
+
+		calc::evaluate(&expression, self).map_err(|(index, message)| {
+			let at: &Token = expression.get(index).or(expression.last()).unwrap_or(&opening);
+			self.error(at, &message)
+		})
+	}
+
+	// plain value, or { expression }
+	fn number_or_calc(&mut self, token: &Token) -> Result<i64, AssembleError> {
+		if self.peek() == Some("{") {
+			return Ok(self.calc(token)? as i64);
+		}
+
+		let value: Token = self.next(token, "a value")?;
+		self.value_of(&value.text).ok_or_else(|| self.error(&value, &format!("'{}' isn't a number, constant or label defined before here", value.text)))
+	}
+
+	// number, constant or label that has been defined
+	fn value_of(&self, text: &str) -> Option<i64> {
+		if let Some(value) = lexer::parse_number(text) {
+			return Some(value);
+		}
+		if let Some(&value) = self.constants.get(text) {
+			return Some(value as i64);
+		}
+		self.labels.get(text).map(|&address| address as i64)
+	}
+
+	// v0 - vf, or an alias for one
+	fn register_number(&self, text: &str) -> Option<u8> {
+		if let Some(&register) = self.aliases.get(text) {
+			return Some(register);
+		}
+
+		let mut characters = text.chars();
+		match (characters.next(), characters.next(), characters.next()) {
+			(Some('v' | 'V'), Some(digit), None) => digit.to_digit(16).map(|number| number as u8),
+			_ => None,
+		}
+	}
+
+	// next token as a register
+	fn register(&mut self, after: &Token) -> Result<u8, AssembleError> {
+		let token: Token = self.next(after, "a register")?;
+		self.register_number(&token.text).ok_or_else(|| self.error(&token, &format!("expected a register, found '{}'", token.text)))
+	}
+
+	// next token as a byte
+	fn byte(&mut self, after: &Token) -> Result<u8, AssembleError> {
+		let token: Token = self.next(after, "a value")?;
+		self.byte_value(&token)
+	}
+
+	// token as a byte, allowing -128 to 255
+	fn byte_value(&self, token: &Token) -> Result<u8, AssembleError> {
+		let value: i64 = self.value_of(&token.text).ok_or_else(|| self.error(token, &format!("'{}' isn't a number, constant or label defined before here", token.text)))?;
+		byte_in_range(value).ok_or_else(|| self.error(token, &format!("{} doesn't fit in a byte", value)))
+	}
+
+	// next token as 0 - 15
+	fn nibble(&mut self, after: &Token) -> Result<u8, AssembleError> {
+		let token: Token = self.next(after, "a value")?;
+		match self.value_of(&token.text) {
+			Some(value @ 0..=15) => Ok(value as u8),
+			Some(value) => Err(self.error(&token, &format!("{} is out of range (0 to 15)", value))),
+			None => Err(self.error(&token, &format!("'{}' isn't a number or a constant", token.text))),
+		}
+	}
+
+	// instruction whose X is the next token
+	fn register_opcode(&mut self, opcode: u16, token: &Token) -> Result<(), AssembleError> {
+		let x: u8 = self.register(token)?;
+		self.emit_opcode(opcode | (x as u16) << 8, Some(token))
+	}
+
+	// names can't be registers or already taken
+	fn check_name(&self, name: &Token) -> Result<(), AssembleError> {
+		if self.register_number(&name.text).is_some() || lexer::parse_number(&name.text).is_some() {
+			return Err(self.error(name, &format!("'{}' can't be used as a name", name.text)));
+		}
+		if self.labels.contains_key(&name.text) || self.constants.contains_key(&name.text) || self.macros.contains_key(&name.text) {
+			return Err(self.error(name, &format!("'{}' is already defined", name.text)));
+		}
+		Ok(())
+	}
+
+	fn define_label(&mut self, name: &Token, address: u16) -> Result<(), AssembleError> {
+		self.check_name(name)?;
+		self.labels.insert(name.text.clone(), address);
+		Ok(())
+	}
+
+	fn emit(&mut self, byte: u8, token: &Token) -> Result<(), AssembleError> {
+		if self.here > 0xFFFF {
+			return Err(self.error(token, "program goes past the end of memory at 0xFFFF"));
+		}
+
+		let index: usize = (self.here - START_ADDRESS as u32) as usize;
+		if index >= self.rom.len() {
+			self.rom.resize(index + 1, 0);
+		}
+		self.rom[index] = byte;
+		self.here += 1;
+		Ok(())
+	}
+
+	// token is only needed for errors, and is None for the jump to main
+	fn emit_opcode(&mut self, opcode: u16, token: Option<&Token>) -> Result<(), AssembleError> {
+		let token: Token = token.cloned().unwrap_or(Token { text: String::new(), line: 1, column: 1 });
+		self.emit((opcode >> 8) as u8, &token)?;
+		self.emit(opcode as u8, &token)
+	}
+
+	// opcode with an address in it, filled in later if the label isn't defined yet
+	fn emit_address(&mut self, kind: FixupKind, opcode: u16, label: &Token) -> Result<(), AssembleError> {
+		let address: u16 = self.here as u16;
+		// high and low bytes are the second byte of an instruction
+		let fixup_address: u16 = match kind {
+			FixupKind::HighByte(_) | FixupKind::LowByte => address.wrapping_add(1),
+			FixupKind::Address | FixupKind::Word => address,
+		};
+
+		self.emit_opcode(opcode, Some(label))?;
+		let fixup = Fixup { address: fixup_address, kind, token: label.clone() };
+
+		match self.value_of(&label.text) {
+			Some(value) => self.apply_fixup(&fixup, value),
+			None => {
+				if self.register_number(&label.text).is_some() || label.text.starts_with(':') {
+					return Err(self.error(label, &format!("expected an address, found '{}'", label.text)));
+				}
+				self.fixups.push(fixup);
+				Ok(())
+			},
+		}
+	}
+
+	// opcode with a known address in it
+	fn emit_address_value(&mut self, opcode: u16, value: i64, token: &Token) -> Result<(), AssembleError> {
+		let address: u16 = self.here as u16;
+		self.emit_opcode(opcode, Some(token))?;
+		self.apply_fixup(&Fixup { address, kind: FixupKind::Address, token: token.clone() }, value)
+	}
+
+	// point an if or while jump at here
+	fn patch_jump(&mut self, jump: u16, token: &Token) -> Result<(), AssembleError> {
+		let here: i64 = self.here as i64;
+		self.apply_fixup(&Fixup { address: jump, kind: FixupKind::Address, token: token.clone() }, here)
+	}
+
+	fn apply_fixup(&mut self, fixup: &Fixup, value: i64) -> Result<(), AssembleError> {
+		let index: usize = (fixup.address - START_ADDRESS) as usize;
+		let twelve_bits = |value: i64| if (0..=0xFFF).contains(&value) {Ok(value as u16)} else {
+			Err(self.error(&fixup.token, &format!("address {:#X} is too high - only 0x000 to 0xFFF fit here", value)))
+		};
+
+		match fixup.kind {
+			FixupKind::Address => {
+				let address: u16 = twelve_bits(value)?;
+				self.rom[index] = (self.rom[index] & 0xF0) | (address >> 8) as u8;
+				self.rom[index + 1] = address as u8;
+			},
+			FixupKind::Word => {
+				if !(0..=0xFFFF).contains(&value) {
+					return Err(self.error(&fixup.token, &format!("{} doesn't fit in 16 bits", value)));
+				}
+				self.rom[index] = (value >> 8) as u8;
+				self.rom[index + 1] = value as u8;
+			},
+			FixupKind::HighByte(Some(nibble)) => self.rom[index] = nibble << 4 | (twelve_bits(value)? >> 8) as u8,
+			FixupKind::HighByte(None) => self.rom[index] = (value >> 8) as u8,
+			FixupKind::LowByte => self.rom[index] = value as u8,
+		}
+
+		Ok(())
+	}
+}
+
+// names :calc can use - constants, labels defined so far, and HERE
+impl Scope for Compiler {
+	fn lookup(&self, name: &str) -> Option<f64> {
+		if name == "HERE" {
+			return Some(self.here as f64);
+		}
+		self.constants.get(name).copied().or_else(|| self.labels.get(name).map(|&address| address as f64))
+	}
+
+	fn byte_at(&self, address: f64) -> Option<f64> {
+		let index: usize = (address as usize).checked_sub(START_ADDRESS as usize)?;
+		self.rom.get(index).map(|&byte| byte as f64)
+	}
+}
+
+// -128 to 255, with negatives as two's complement
+fn byte_in_range(value: i64) -> Option<u8> {
+	if (-0x80..=0xFF).contains(&value) {
+		Some(value as u8)
+	} else {
+		None
+	}
+}
@@ -0,0 +1,63 @@
+// Octo source is a stream of whitespace separated words, with # comments
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+	pub text: String,
+	// both 1-based, for error messages and the source map
+	pub line: usize,
+	pub column: usize,
+}
+
+// every word in the source, in order
+pub fn tokenize(source: &str) -> Vec<Token> {
+	let mut tokens: Vec<Token> = Vec::new();
+
+	for (index, line) in source.lines().enumerate() {
+		let characters: Vec<char> = line.chars().collect();
+		let mut position: usize = 0;
+
+		while position < characters.len() {
+			if characters[position].is_whitespace() {
+				position += 1;
+				continue;
+			}
+			// rest of the line is a comment
+			if characters[position] == '#' {
+				break;
+			}
+
+			let start: usize = position;
+			while position < characters.len() && !characters[position].is_whitespace() {
+				position += 1;
+			}
+
+			tokens.push(Token {
+				text: characters[start..position].iter().collect(),
+				line: index + 1,
+				column: start + 1,
+			});
+		}
+	}
+
+	tokens
+}
+
+// 42, -1, 0x2A or 0b101010
+pub fn parse_number(text: &str) -> Option<i64> {
+	let (negative, digits): (bool, &str) = match text.strip_prefix('-') {
+		Some(digits) => (true, digits),
+		None => (false, text),
+	};
+
+	let value: i64 = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+		i64::from_str_radix(hex, 16).ok()?
+	} else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+		i64::from_str_radix(binary, 2).ok()?
+	} else if !digits.is_empty() && digits.chars().all(|character| character.is_ascii_digit()) {
+		digits.parse().ok()?
+	} else {
+		return None;
+	};
+
+	Some(if negative {-value} else {value})
+}
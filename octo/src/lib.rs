@@ -0,0 +1,47 @@
+// compiler for Octo (https://github.com/JohnEarnest/Octo), the language most
+// modern CHIP-8 games are written in
+//
+// supports labels (: name), :const, :alias, :calc, :macro, :org, :byte,
+// :pointer, :unpack, :next, all the register and i forms, if ... then,
+// if ... begin ... else ... end, and loop ... while ... again
+// :breakpoint and :monitor are accepted but do nothing
+//
+// like Octo, everything has to be separated by whitespace, including the
+// { } of :macro and :calc and the ( ) inside :calc
+//
+// output is the same Assembly the assembler crate produces, starting at
+// 0x200 with a jump to main
+
+// crates
+use std::fs;
+use std::path::Path;
+
+pub use assembler::{AssembleError, Assembly};
+
+// modules
+mod calc;
+mod compiler;
+mod lexer;
+
+use compiler::Compiler;
+
+// name used in errors for source that didn't come from a file
+const SOURCE_NAME: &str = "<source>";
+
+// compile Octo source text
+pub fn compile(source: &str) -> Result<Assembly, AssembleError> {
+	Compiler::new(SOURCE_NAME, source).compile()
+}
+
+// compile an Octo file, e.g. a .8o
+pub fn compile_file(path: &Path) -> Result<Assembly, AssembleError> {
+	let name: String = path.display().to_string();
+	let source: String = fs::read_to_string(path).map_err(|error| AssembleError {
+		file: name.clone(),
+		line: 0,
+		column: 0,
+		message: error.to_string(),
+	})?;
+
+	Compiler::new(&name, &source).compile()
+}
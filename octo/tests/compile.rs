@@ -0,0 +1,109 @@
+// compiles Octo source and checks the bytes that come out, and that mistakes
+// are reported at the right line and column
+// every program starts at 0x200 with a jump to main
+
+// crates
+use octo::{compile, AssembleError, Assembly};
+
+// bytes from source that's expected to compile
+fn bytes(source: &str) -> Vec<u8> {
+	match compile(source) {
+		Ok(assembly) => assembly.bytes,
+		Err(error) => panic!("{}\n{}", error, source),
+	}
+}
+
+// line, column and message of the error from source that's expected not to compile
+fn error(source: &str) -> (usize, usize, String) {
+	match compile(source) {
+		Ok(assembly) => panic!("compiled to {:02X?}\n{}", assembly.bytes, source),
+		Err(AssembleError { line, column, message, .. }) => (line, column, message),
+	}
+}
+
+#[test]
+fn labels_before_and_after_use() {
+	let assembly: Assembly = compile("\
+		: data 0x12 0x34\n\
+		: main\n\
+		  i := data\n\
+		  jump later\n\
+		: later ;\n").unwrap();
+	assert_eq!(assembly.bytes, [0x12, 0x04, 0x12, 0x34, 0xA2, 0x02, 0x12, 0x08, 0x00, 0xEE]);
+	assert_eq!(assembly.labels.get("data"), Some(&0x202));
+	assert_eq!(assembly.labels.get("later"), Some(&0x208));
+	assert_eq!(assembly.address_of("<source>", 3), Some(0x204));
+
+	// anything else on its own calls a subroutine
+	assert_eq!(bytes(": main sub : sub ;"), [0x12, 0x02, 0x22, 0x04, 0x00, 0xEE]);
+}
+
+#[test]
+fn constants_and_calc() {
+	assert_eq!(bytes(":const SPEED 3 : main v0 := SPEED SPEED"), [0x12, 0x02, 0x60, 0x03, 0x03]);
+
+	// right to left with no precedence, like Octo
+	assert_eq!(bytes(":const W 8 :calc HALF { W / 2 + 2 } : main v1 := HALF"), [0x12, 0x02, 0x61, 0x02]);
+	assert_eq!(bytes(":calc X { 2 * 3 + 1 } : main v2 := X"), [0x12, 0x02, 0x62, 0x08]);
+	assert_eq!(bytes(":calc X { ( 2 * 3 ) + 1 } : main v2 := X"), [0x12, 0x02, 0x62, 0x07]);
+}
+
+#[test]
+fn if_then() {
+	// the next statement is skipped when the condition is false
+	assert_eq!(bytes(": main if v0 == 5 then v1 := 1"), [0x12, 0x02, 0x40, 0x05, 0x61, 0x01]);
+	assert_eq!(bytes(": main if v2 key then ;"), [0x12, 0x02, 0xE2, 0xA1, 0x00, 0xEE]);
+	// comparisons go through VF
+	assert_eq!(bytes(": main if v1 < v2 then ;"), [0x12, 0x02, 0x8F, 0x20, 0x8F, 0x17, 0x4F, 0x00, 0x00, 0xEE]);
+}
+
+#[test]
+fn if_begin_else_end() {
+	let source: &str = "\
+		: main\n\
+		  if v0 != 3 begin\n\
+		    v1 := 1\n\
+		  else\n\
+		    v1 := 2\n\
+		  end\n";
+	assert_eq!(bytes(source), [0x12, 0x02, 0x40, 0x03, 0x12, 0x0A, 0x61, 0x01, 0x12, 0x0C, 0x61, 0x02]);
+}
+
+#[test]
+fn loop_while_again() {
+	let source: &str = "\
+		: main\n\
+		  loop\n\
+		    v0 += 1\n\
+		    while v0 != 10\n\
+		  again\n";
+	assert_eq!(bytes(source), [0x12, 0x02, 0x70, 0x01, 0x40, 0x0A, 0x12, 0x0A, 0x12, 0x02]);
+}
+
+#[test]
+fn macros() {
+	let source: &str = ":macro add-both A B { v0 += A v1 += B } : main add-both 1 2 add-both 3 4";
+	assert_eq!(bytes(source), [0x12, 0x02, 0x70, 0x01, 0x71, 0x02, 0x70, 0x03, 0x71, 0x04]);
+
+	assert_eq!(error(":macro forever { forever } : main forever"), (1, 18, String::from("too many macro expansions - does a macro use itself?")));
+	assert_eq!(error(":macro two A B { } : main two 1"), (1, 27, String::from("expected a value for 'B' after 'two'")));
+}
+
+#[test]
+fn errors_point_at_the_mistake() {
+	let cases: [(&str, (usize, usize, &str)); 8] = [
+		("\n: main\n  :foo", (3, 3, "unknown directive ':foo'")),
+		(": main end", (1, 8, "'end' without 'if ... begin'")),
+		(": main\nloop\nv0 += 1", (2, 1, "this 'loop' has no 'again'")),
+		(": main\n  if v0 == 1 begin", (2, 14, "this 'begin' has no 'end'")),
+		(": main jump nowhere", (1, 13, "'nowhere' is never defined")),
+		(": main v0 := 300", (1, 14, "300 doesn't fit in a byte")),
+		(":calc X { 1 + }", (1, 13, "expression ended early")),
+		("clear", (0, 0, "programs need a ': main' label to start from")),
+	];
+	for (source, (line, column, message)) in cases {
+		assert_eq!(error(source), (line, column, String::from(message)), "{}", source);
+	}
+
+	assert_eq!(compile(": main end").unwrap_err().to_string(), "<source>:1:8: 'end' without 'if ... begin'");
+}